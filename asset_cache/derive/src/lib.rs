@@ -0,0 +1,144 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, parse_quote, spanned::Spanned, Data, DeriveInput, Error, Fields, Index,
+};
+
+/// Derives `Asset` for a struct whose fields are all `Asset`s.
+///
+/// A `{Name}Weak` struct mirroring the fields with their `WeakType` is generated alongside.
+#[proc_macro_derive(Asset)]
+pub fn derive_asset(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    derive_asset_impl(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn derive_asset_impl(input: DeriveInput) -> syn::Result<TokenStream> {
+    let crate_name = quote! { ::elements_asset_cache };
+
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        Data::Enum(data) => {
+            return Err(Error::new(
+                data.enum_token.span(),
+                "Asset can only be derived for structs",
+            ))
+        }
+        Data::Union(data) => {
+            return Err(Error::new(
+                data.union_token.span(),
+                "Asset can only be derived for structs",
+            ))
+        }
+    };
+
+    let name = &input.ident;
+    let vis = &input.vis;
+    let weak_name = format_ident!("{}Weak", name);
+
+    // Each field type must itself be an asset.
+    //
+    // Non-generic field types are checked by the `Asset` impl itself.
+    let mut generics = input.generics.clone();
+    if !generics.params.is_empty() {
+        let where_clause = generics.make_where_clause();
+        for field in fields {
+            let ty = &field.ty;
+            where_clause
+                .predicates
+                .push(parse_quote! { #ty: #crate_name::Asset });
+        }
+    }
+
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let weak_ty = |ty: &syn::Type| quote! { <#ty as #crate_name::Asset>::WeakType };
+
+    let members = fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| match &field.ident {
+            Some(ident) => quote! { #ident },
+            None => {
+                let index = Index {
+                    index: i as u32,
+                    span: Span::call_site(),
+                };
+                quote! { #index }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let weak_doc = format!("Weak counterpart of [`{name}`]");
+
+    let weak_def = match fields {
+        Fields::Named(fields) => {
+            let fields = fields.named.iter().map(|field| {
+                let vis = &field.vis;
+                let ident = &field.ident;
+                let ty = weak_ty(&field.ty);
+                quote! { #vis #ident: #ty }
+            });
+
+            quote! {
+                #[doc = #weak_doc]
+                #vis struct #weak_name #impl_generics #where_clause {
+                    #(#fields,)*
+                }
+            }
+        }
+        Fields::Unnamed(fields) => {
+            let fields = fields.unnamed.iter().map(|field| {
+                let vis = &field.vis;
+                let ty = weak_ty(&field.ty);
+                quote! { #vis #ty }
+            });
+
+            quote! {
+                #[doc = #weak_doc]
+                #vis struct #weak_name #impl_generics (#(#fields,)*) #where_clause;
+            }
+        }
+        Fields::Unit => quote! {
+            #[doc = #weak_doc]
+            #vis struct #weak_name;
+        },
+    };
+
+    // `#[derive(Clone)]` would require the generic parameters to be `Clone` rather than the weak
+    // types
+    let weak_clone = quote! {
+        impl #impl_generics ::core::clone::Clone for #weak_name #ty_generics #where_clause {
+            fn clone(&self) -> Self {
+                Self {
+                    #(#members: ::core::clone::Clone::clone(&self.#members),)*
+                }
+            }
+        }
+    };
+
+    Ok(quote! {
+        #weak_def
+
+        #weak_clone
+
+        impl #impl_generics #crate_name::Asset for #name #ty_generics #where_clause {
+            type WeakType = #weak_name #ty_generics;
+
+            fn to_weak(strong: &Self) -> Self::WeakType {
+                #weak_name {
+                    #(#members: #crate_name::Asset::to_weak(&strong.#members),)*
+                }
+            }
+
+            fn from_weak(weak: &Self::WeakType) -> ::core::option::Option<Self> {
+                ::core::option::Option::Some(Self {
+                    #(#members: #crate_name::Asset::from_weak(&weak.#members)?,)*
+                })
+            }
+        }
+    })
+}
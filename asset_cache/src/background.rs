@@ -1,3 +1,4 @@
+use anyhow::Context;
 use async_trait::async_trait;
 
 use crate::{Asset, AssetCache, AssetLoadDropPolicy, AsyncAssetKey, AsyncAssetKeyExt};
@@ -29,24 +30,23 @@ where
         AssetLoadDropPolicy::KeepLoading
     }
 
-    async fn load(self, assets: AssetCache) -> T {
+    async fn try_load(self, assets: AssetCache) -> anyhow::Result<T> {
         // Short happy path
         // This is needed as JoinHandle does not complete immediately, even if the spawned future
         // is ready
         if let Some(content) = assets.content_state(&self.0) {
-            if let Some(value) = content.get_loaded_value::<T>() {
-                return value;
+            if let Ok(Some(value)) = content.get_loaded_value::<T>() {
+                return Ok(value);
             }
         }
 
         let key = self.0.clone();
         let runtime = assets.runtime().clone();
-        todo!()
-        // let task = runtime.spawn(Box::pin(async move {
-        //     let v = key.get(&assets).await;
-        //     v
-        // }));
+        let (tx, rx) = futures::channel::oneshot::channel();
+        runtime.spawn(Box::pin(async move {
+            let _ = tx.send(key.try_get(&assets).await);
+        }));
 
-        // task.await.expect("Failed to wait for load task")
+        Ok(rx.await.context("Load task was aborted")??)
     }
 }
@@ -0,0 +1,74 @@
+use std::{fmt::Display, sync::Arc};
+
+use crate::AssetKey;
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum AssetErrorKind {
+    /// The stored value is not of the requested type.
+    ///
+    /// This happens when two different key types share the same `Debug` representation.
+    #[error("Asset is not of the expected type {expected}")]
+    TypeMismatch { expected: &'static str },
+    #[error("Asset slot was removed during loading")]
+    MissingSlot,
+    #[error("Asset loading was aborted")]
+    Aborted,
+    #[error("Asset depends on itself")]
+    Cycle,
+    /// The loader returned an error from `try_load`, or panicked
+    #[error("Asset failed to load")]
+    LoadFailed(#[source] Arc<anyhow::Error>),
+}
+
+/// An error which occurred while loading or retrieving an asset.
+///
+/// Carries the stack of parent keys which led to the failing asset being requested.
+#[derive(Debug, Clone, thiserror::Error)]
+pub struct AssetError {
+    key: AssetKey,
+    stack: Vec<AssetKey>,
+    #[source]
+    kind: AssetErrorKind,
+}
+
+impl AssetError {
+    pub(crate) fn new(key: AssetKey, stack: Vec<AssetKey>, kind: AssetErrorKind) -> Self {
+        Self { key, stack, kind }
+    }
+
+    pub(crate) fn type_mismatch<T>(key: AssetKey, stack: Vec<AssetKey>) -> Self {
+        Self::new(
+            key,
+            stack,
+            AssetErrorKind::TypeMismatch {
+                expected: std::any::type_name::<T>(),
+            },
+        )
+    }
+
+    /// The key of the asset which failed
+    pub fn key(&self) -> &AssetKey {
+        &self.key
+    }
+
+    /// The keys of the assets which (transitively) requested the failing asset, outermost first
+    pub fn stack(&self) -> &[AssetKey] {
+        &self.stack
+    }
+
+    pub fn kind(&self) -> &AssetErrorKind {
+        &self.kind
+    }
+}
+
+impl Display for AssetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Failed to get asset {:?}", self.key.as_str())?;
+
+        for parent in self.stack.iter().rev() {
+            write!(f, "\n    required by {:?}", parent.as_str())?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,9 +1,16 @@
 mod background;
+mod error;
+pub mod task;
+
+// Allows the derive macro to refer to this crate by name from within
+extern crate self as elements_asset_cache;
 
 use std::{
     any::Any,
     collections::{hash_map::Entry, HashMap},
+    hash::{BuildHasher, Hash},
     ops::Deref,
+    panic::AssertUnwindSafe,
     pin::Pin,
     sync::{Arc, Weak},
     task::{Context, Poll},
@@ -12,6 +19,8 @@ use std::{
 
 use async_trait::async_trait;
 use background::BackgroundKey;
+pub use elements_asset_cache_derive::Asset;
+pub use error::*;
 use futures::{
     future::{pending, BoxFuture, Shared, WeakShared},
     Future, FutureExt,
@@ -19,10 +28,7 @@ use futures::{
 use parking_lot::Mutex;
 use pin_project::{pin_project, pinned_drop};
 use serde::{Deserialize, Serialize};
-use utils::{
-    task::{spawn, AbortOnDrop, JoinHandle},
-    timer::sleep,
-};
+use task::{sleep, spawn, AbortOnDrop, JoinHandle};
 
 trait AssetHolder: as_any::AsAny + Sync + Send {}
 impl<T: Clone + Sync + Send + Any + 'static> AssetHolder for T {}
@@ -43,7 +49,10 @@ impl TaskSpawner for tokio::runtime::Handle {
     }
 }
 
-struct WasmRuntime;
+/// Spawns onto the browser event loop
+#[cfg(target_arch = "wasm32")]
+pub struct WasmRuntime;
+#[cfg(target_arch = "wasm32")]
 impl TaskSpawner for WasmRuntime {
     fn spawn(&self, fut: BoxFuture<'static, ()>) -> JoinHandle<()> {
         spawn(fut)
@@ -56,6 +65,14 @@ struct LoadPayload {
     strong: Arc<dyn AssetHolder>,
 }
 
+type LoadFuture = BoxFuture<'static, Result<LoadPayload, AssetError>>;
+
+/// The result of looking up an asset in the cache
+enum AssetLookup<T> {
+    Loaded(AssetKey, T),
+    Loading(Shared<LoadFuture>),
+}
+
 #[derive(Debug, Clone)]
 pub enum AssetLoadDropPolicy {
     StopLoading,
@@ -94,7 +111,7 @@ impl Deref for AssetKey {
 #[derive(Clone)]
 pub(crate) enum ContentState {
     Loading {
-        fut: WeakShared<BoxFuture<'static, LoadPayload>>,
+        fut: WeakShared<LoadFuture>,
     },
     Loaded {
         value: Arc<dyn AssetHolder>,
@@ -146,16 +163,20 @@ impl AsyncAssetLoc {
 
 impl ContentState {
     /// Returns the concrete loaded value if loaded and kept alive (strong count).
-    fn get_loaded_value<T: Asset + Clone + Sync + Send + 'static>(&self) -> Option<T> {
+    fn get_loaded_value<T: Asset + Clone + Sync + Send + 'static>(
+        &self,
+    ) -> Result<Option<T>, AssetErrorKind> {
         if let ContentState::Loaded { value, .. } = &self {
             let content = value
                 .as_any()
                 .downcast_ref::<<T as Asset>::WeakType>()
-                .unwrap();
-            let content = T::from_weak(content)?;
-            Some(content)
+                .ok_or(AssetErrorKind::TypeMismatch {
+                    expected: std::any::type_name::<T>(),
+                })?;
+
+            Ok(T::from_weak(content))
         } else {
-            None
+            Ok(None)
         }
     }
 
@@ -222,51 +243,51 @@ impl AssetCache {
         &self,
         key: K,
         loader: impl FnOnce(AssetCache) -> T + Sync + Send,
-    ) -> T {
+    ) -> Result<T, AssetError> {
+        let key = AssetKey::new(key);
         let loc = {
             let mut cache = self.sync.lock();
-            let key = AssetKey::new(key);
             cache
                 .entry(key.clone())
                 .or_insert_with(|| SyncAssetLoc {
-                    _key: key,
+                    _key: key.clone(),
                     content: Arc::new(Mutex::new(None)),
                 })
                 .clone()
         };
         let mut content = loc.content.lock();
-        if content.is_none() {
-            *content = Some(Arc::new(loader(self.clone())) as Arc<dyn AssetHolder>);
-        }
+        let content =
+            content.get_or_insert_with(|| Arc::new(loader(self.clone())) as Arc<dyn AssetHolder>);
+
         content
-            .as_ref()
-            .unwrap()
             .as_any()
             .downcast_ref::<T>()
-            .unwrap()
-            .clone()
+            .cloned()
+            .ok_or_else(|| AssetError::type_mismatch::<T>(key, self.stack.clone()))
     }
 
     #[deprecated(note = "Use a SyncAssetKey instead")]
     pub fn try_get_sync<K: Into<String>, T: Clone + Sync + Send + 'static>(
         &self,
         key: K,
-    ) -> Option<T> {
+    ) -> Result<Option<T>, AssetError> {
         let cache = self.sync.lock();
         let key = AssetKey::new(key);
         if let Some(entry) = cache.get(&key) {
             let content = entry.content.lock();
-            Some(
-                content
-                    .as_ref()
-                    .unwrap()
-                    .as_any()
-                    .downcast_ref::<T>()
-                    .unwrap()
-                    .clone(),
-            )
+            // The loader panicked while producing the value
+            let content = content
+                .as_ref()
+                .ok_or_else(|| self.error(key.clone(), AssetErrorKind::Aborted))?;
+
+            let value = content
+                .as_any()
+                .downcast_ref::<T>()
+                .ok_or_else(|| AssetError::type_mismatch::<T>(key, self.stack.clone()))?;
+
+            Ok(Some(value.clone()))
         } else {
-            None
+            Ok(None)
         }
     }
 
@@ -323,23 +344,31 @@ impl AssetCache {
         cache
     }
 
+    /// Creates an error for `key` which carries the current stack of parent keys
+    fn error(&self, key: AssetKey, kind: AssetErrorKind) -> AssetError {
+        AssetError::new(key, self.stack.clone(), kind)
+    }
+
     /// Returns the asset or a future for loading the asset
-    fn get_asset_future<K, T>(
-        &self,
-        key: K,
-    ) -> Result<(AssetKey, T), Shared<Pin<Box<dyn Future<Output = LoadPayload> + Send>>>>
+    fn get_asset_future<K, T>(&self, key: K) -> Result<AssetLookup<T>, AssetError>
     where
         K: 'static + Clone + AsyncAssetKey<T>,
         T: 'static + Asset + Clone + Sync + Send,
     {
+        let asset_key = AssetKey::new(key.key());
+
+        // Awaiting an asset which is already being loaded further up the stack would never
+        // complete
+        if self.stack.contains(&asset_key) {
+            return Err(self.error(asset_key, AssetErrorKind::Cycle));
+        }
+
         let mut cache = self.async_cache.lock();
 
         let keepalive = key.keepalive();
 
         let timeline = self.timeline.clone();
 
-        let asset_key = AssetKey::new(key.key());
-
         let load = || {
             tracing::debug!("Loading asset: {asset_key:?}");
 
@@ -362,8 +391,11 @@ impl AssetCache {
                 completed: false,
                 timeline: timeline.clone(),
                 asset_key: asset_key.clone(),
-                fut: async move { key.load(fork).await },
-            }) as BoxFuture<'static, LoadPayload>)
+                stack: self.stack.clone(),
+                // A panicking loader is turned into an error rather than taking down the
+                // caller
+                fut: AssertUnwindSafe(async move { key.try_load(fork).await }).catch_unwind(),
+            }) as LoadFuture)
                 .shared();
 
             let content = ContentState::Loading {
@@ -373,8 +405,7 @@ impl AssetCache {
             // Spawn a task to keep running the shared future even if the key holder drops
             // their part of the future.
             let keepalive = if let AssetLoadDropPolicy::KeepLoading = drop_policy {
-                // Some(self.runtime.spawn(fut.clone().map(|_| {})).into())
-                todo!()
+                Some(self.spawner.spawn(fut.clone().map(|_| {}).boxed()).into())
             } else {
                 None
             };
@@ -384,7 +415,7 @@ impl AssetCache {
         // Acquire or start a future for loading this asset
         let fut = match cache.entry(asset_key.clone()) {
             Entry::Occupied(mut slot) => {
-                let mut loc = slot.get_mut();
+                let loc = slot.get_mut();
 
                 match &mut loc.content {
                     ContentState::Loading { fut } => {
@@ -405,9 +436,15 @@ impl AssetCache {
                         let content = value
                             .as_any()
                             .downcast_ref::<<T as Asset>::WeakType>()
-                            .unwrap();
+                            .ok_or_else(|| {
+                                AssetError::type_mismatch::<T>(
+                                    asset_key.clone(),
+                                    self.stack.clone(),
+                                )
+                            })?;
+
                         if let Some(content) = T::from_weak(content) {
-                            return Ok((asset_key, content));
+                            return Ok(AssetLookup::Loaded(asset_key, content));
                         }
 
                         let (fut, c, k) = load();
@@ -441,22 +478,28 @@ impl AssetCache {
             }
         };
 
-        Err(fut)
+        Ok(AssetLookup::Loading(fut))
     }
 
-    async fn get_async<K, T>(&self, key: K) -> T
+    async fn get_async<K, T>(&self, key: K) -> Result<T, AssetError>
     where
         K: 'static + Clone + AsyncAssetKey<T>,
         T: 'static + Asset + Clone + Sync + Send,
     {
         let keepalive = key.keepalive();
 
-        let (asset_key, value) = match self.get_asset_future(key) {
-            Ok(value) => value,
-            Err(fut) => {
-                let LoadPayload { asset_key, strong } = fut.await;
+        let (asset_key, value) = match self.get_asset_future(key)? {
+            AssetLookup::Loaded(asset_key, value) => (asset_key, value),
+            AssetLookup::Loading(fut) => {
+                let LoadPayload { asset_key, strong } = fut.await?;
 
-                let value = strong.as_any().downcast_ref::<T>().unwrap().clone();
+                let value = strong
+                    .as_any()
+                    .downcast_ref::<T>()
+                    .ok_or_else(|| {
+                        AssetError::type_mismatch::<T>(asset_key.clone(), self.stack.clone())
+                    })?
+                    .clone();
                 (asset_key, value)
             }
         };
@@ -464,7 +507,7 @@ impl AssetCache {
         let mut cache = self.async_cache.lock();
         let loc = cache
             .get_mut(&asset_key)
-            .expect("Asset loc was removed during loading");
+            .ok_or_else(|| self.error(asset_key.clone(), AssetErrorKind::MissingSlot))?;
 
         // Start or replace the keepalive task
 
@@ -508,7 +551,7 @@ impl AssetCache {
             _ => (),
         }
 
-        value
+        Ok(value)
     }
 
     pub fn runtime(&self) -> &Arc<dyn TaskSpawner> {
@@ -532,8 +575,13 @@ pub trait SyncAssetKey<T: Clone + Sync + Send + 'static>: Sync + Send + std::fmt
 }
 pub trait SyncAssetKeyExt<T: Clone + Sync + Send + 'static>: SyncAssetKey<T> {
     fn key(&self) -> String;
+    /// Returns the asset, loading it if necessary.
+    ///
+    /// Panics if the stored asset is not of type `T`. See [`Self::try_get`] for a fallible
+    /// alternative.
     fn get(&self, assets: &AssetCache) -> T;
-    fn try_get(&self, assets: &AssetCache) -> Option<T>;
+    /// Returns the asset if it has been loaded or inserted
+    fn try_get(&self, assets: &AssetCache) -> Result<Option<T>, AssetError>;
     fn insert(&self, assets: &AssetCache, value: T);
     fn exists(&self, assets: &AssetCache) -> bool;
 }
@@ -544,9 +592,11 @@ impl<T: Clone + Sync + Send + 'static, K: SyncAssetKey<T>> SyncAssetKeyExt<T> fo
     }
     fn get(&self, assets: &AssetCache) -> T {
         let assets = assets.clone();
-        assets.get_sync(self.key(), |assets| self.load(assets))
+        assets
+            .get_sync(self.key(), |assets| self.load(assets))
+            .unwrap_or_else(|err| panic!("{:#}", anyhow::Error::new(err)))
     }
-    fn try_get(&self, assets: &AssetCache) -> Option<T> {
+    fn try_get(&self, assets: &AssetCache) -> Result<Option<T>, AssetError> {
         let assets = assets.clone();
         assets.try_get_sync(self.key())
     }
@@ -562,7 +612,28 @@ impl<T: Clone + Sync + Send + 'static, K: SyncAssetKey<T>> SyncAssetKeyExt<T> fo
 pub trait AsyncAssetKey<T: Asset + Clone + Sync + Send + 'static>:
     Sync + Send + std::fmt::Debug
 {
-    async fn load(self, assets: AssetCache) -> T;
+    /// Loads the asset. Loaders which can fail implement [`Self::try_load`] instead.
+    #[allow(clippy::diverging_sub_expression)]
+    async fn load(self, _assets: AssetCache) -> T
+    where
+        Self: Sized,
+    {
+        panic!(
+            "{} resource doesn't implement the load method",
+            std::any::type_name::<T>()
+        )
+    }
+
+    /// Loads the asset, or fails with an error which is returned as the source of
+    /// [`AssetErrorKind::LoadFailed`].
+    ///
+    /// Defaults to [`Self::load`].
+    async fn try_load(self, assets: AssetCache) -> anyhow::Result<T>
+    where
+        Self: Sized,
+    {
+        Ok(self.load(assets).await)
+    }
 
     /// Adapter to make the key load in a background task.
     ///
@@ -596,7 +667,12 @@ pub trait AsyncAssetKey<T: Asset + Clone + Sync + Send + 'static>:
 pub trait AsyncAssetKeyExt<T: Asset + Clone + Sync + Send + 'static>: AsyncAssetKey<T> {
     fn key(&self) -> String;
     fn long_name(&self) -> String;
+    /// Returns the asset, loading it if necessary.
+    ///
+    /// Panics if the asset fails to load. See [`Self::try_get`] for a fallible alternative.
     async fn get(&self, assets: &AssetCache) -> T;
+    /// Returns the asset, loading it if necessary
+    async fn try_get(&self, assets: &AssetCache) -> Result<T, AssetError>;
     /// Returns `Some(T)` if the asset is currently loaded, alive, and well.
     ///
     /// Does not attempt to load the asset in any way
@@ -618,12 +694,19 @@ impl<T: Asset + Clone + Sync + Send + 'static, K: AsyncAssetKey<T> + Clone + 'st
 
     #[tracing::instrument(skip(assets), level = "debug")]
     async fn get(&self, assets: &AssetCache) -> T {
+        self.try_get(assets)
+            .await
+            .unwrap_or_else(|err| panic!("{:#}", anyhow::Error::new(err)))
+    }
+
+    #[tracing::instrument(skip(assets), level = "debug")]
+    async fn try_get(&self, assets: &AssetCache) -> Result<T, AssetError> {
         assets.get_async(self.clone()).await
     }
 
     fn is_loaded(&self, assets: &AssetCache) -> Option<T> {
         if let Some(content) = assets.content_state(self) {
-            if let Some(value) = content.get_loaded_value::<T>() {
+            if let Ok(Some(value)) = content.get_loaded_value::<T>() {
                 return Some(value);
             }
         }
@@ -634,7 +717,18 @@ impl<T: Asset + Clone + Sync + Send + 'static, K: AsyncAssetKey<T> + Clone + 'st
     #[tracing::instrument(skip(assets), level = "debug")]
     fn peek(&self, assets: &AssetCache) -> Option<T> {
         // Use of `in_background` start a task that keeps loading
-        self.clone().in_background().get(assets).now_or_never()
+        match self
+            .clone()
+            .in_background()
+            .try_get(assets)
+            .now_or_never()?
+        {
+            Ok(value) => Some(value),
+            Err(err) => {
+                tracing::error!("{:#}", anyhow::Error::new(err));
+                None
+            }
+        }
     }
 }
 
@@ -684,16 +778,28 @@ impl<T: Asset + Sync + Send> Asset for Option<T> {
     }
 }
 
-impl<T0: Asset + Sync + Send, T1: Asset + Sync + Send> Asset for (T0, T1) {
-    type WeakType = (T0::WeakType, T1::WeakType);
-    fn to_weak((a, b): &Self) -> Self::WeakType {
-        (T0::to_weak(a), T1::to_weak(b))
-    }
-    fn from_weak((a, b): &Self::WeakType) -> Option<Self> {
-        Some((T0::from_weak(a)?, T1::from_weak(b)?))
-    }
+macro_rules! impl_asset_tuple {
+    ($($ty: ident => $idx: tt),*) => {
+        impl<$($ty: Asset + Sync + Send),*> Asset for ($($ty,)*) {
+            type WeakType = ($($ty::WeakType,)*);
+            fn to_weak(strong: &Self) -> Self::WeakType {
+                ($($ty::to_weak(&strong.$idx),)*)
+            }
+            fn from_weak(weak: &Self::WeakType) -> Option<Self> {
+                Some(($($ty::from_weak(&weak.$idx)?,)*))
+            }
+        }
+    };
 }
 
+impl_asset_tuple!(T0 => 0, T1 => 1);
+impl_asset_tuple!(T0 => 0, T1 => 1, T2 => 2);
+impl_asset_tuple!(T0 => 0, T1 => 1, T2 => 2, T3 => 3);
+impl_asset_tuple!(T0 => 0, T1 => 1, T2 => 2, T3 => 3, T4 => 4);
+impl_asset_tuple!(T0 => 0, T1 => 1, T2 => 2, T3 => 3, T4 => 4, T5 => 5);
+impl_asset_tuple!(T0 => 0, T1 => 1, T2 => 2, T3 => 3, T4 => 4, T5 => 5, T6 => 6);
+impl_asset_tuple!(T0 => 0, T1 => 1, T2 => 2, T3 => 3, T4 => 4, T5 => 5, T6 => 6, T7 => 7);
+
 impl<T: Asset + Sync + Send> Asset for Vec<T> {
     type WeakType = Vec<T::WeakType>;
     fn to_weak(v: &Self) -> Self::WeakType {
@@ -706,6 +812,37 @@ impl<T: Asset + Sync + Send> Asset for Vec<T> {
     }
 }
 
+impl<T: Asset + Sync + Send, const N: usize> Asset for [T; N] {
+    type WeakType = [T::WeakType; N];
+    fn to_weak(v: &Self) -> Self::WeakType {
+        std::array::from_fn(|i| T::to_weak(&v[i]))
+    }
+    fn from_weak(v: &Self::WeakType) -> Option<Self> {
+        v.iter()
+            .map(|x| T::from_weak(x))
+            .collect::<Option<Vec<_>>>()?
+            .try_into()
+            .ok()
+    }
+}
+
+impl<K, V, S> Asset for HashMap<K, V, S>
+where
+    K: Clone + Eq + Hash + Sync + Send,
+    V: Asset + Sync + Send,
+    S: BuildHasher + Clone + Default + Sync + Send,
+{
+    type WeakType = HashMap<K, V::WeakType, S>;
+    fn to_weak(v: &Self) -> Self::WeakType {
+        v.iter().map(|(k, x)| (k.clone(), V::to_weak(x))).collect()
+    }
+    fn from_weak(v: &Self::WeakType) -> Option<Self> {
+        v.iter()
+            .map(|(k, x)| Some((k.clone(), V::from_weak(x)?)))
+            .collect()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AssetLifetime {
     pub start_load: chrono::DateTime<chrono::Utc>,
@@ -837,6 +974,8 @@ struct AssetLoadFuture<F, K> {
     // Where to store the result
     cache: Arc<Mutex<HashMap<AssetKey, AsyncAssetLoc>>>,
     asset_key: AssetKey,
+    /// The keys which led to this asset being loaded, used for error context
+    stack: Vec<AssetKey>,
     timeline: Arc<Mutex<AssetsTimeline>>,
     #[pin]
     fut: F,
@@ -847,11 +986,11 @@ struct AssetLoadFuture<F, K> {
 impl<K, F, T> Future for AssetLoadFuture<F, K>
 where
     K: AsyncAssetKey<T>,
-    F: Future<Output = T>,
+    F: Future<Output = std::thread::Result<anyhow::Result<T>>>,
     T: 'static + Asset + Clone + Send + Sync,
 {
     /// Returns the strong variant
-    type Output = LoadPayload;
+    type Output = Result<LoadPayload, AssetError>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let p = self.project();
@@ -859,6 +998,22 @@ where
         if let Poll::Ready(res) = p.fut.poll(cx) {
             *p.completed = true;
 
+            let error = |kind| AssetError::new(p.asset_key.clone(), p.stack.clone(), kind);
+
+            let res = match res.map_err(panic_error).and_then(|v| v) {
+                Ok(res) => res,
+                Err(err) => {
+                    let mut cache = p.cache.lock();
+                    if let Some(loc) = cache.get_mut(p.asset_key) {
+                        if loc.content.is_loading() {
+                            loc.content = ContentState::Aborted;
+                        }
+                    }
+
+                    return Poll::Ready(Err(error(AssetErrorKind::LoadFailed(Arc::new(err)))));
+                }
+            };
+
             // Update the timeline with the size
             let cpu_size = p.key.cpu_size(&res);
             let gpu_size = p.key.gpu_size(&res);
@@ -876,21 +1031,26 @@ where
 
             // Update the content state
             let mut cache = p.cache.lock();
-            let mut loc = cache
-                .get_mut(p.asset_key)
-                .expect("Asset loc was removed during loading");
+            let loc = match cache.get_mut(p.asset_key) {
+                Some(loc) => loc,
+                None => return Poll::Ready(Err(error(AssetErrorKind::MissingSlot))),
+            };
+
+            // The slot was taken over by another load
+            if !loc.content.is_loading() {
+                return Poll::Ready(Err(error(AssetErrorKind::Aborted)));
+            }
 
             // Replace the loading state with the loaded state
-            assert!(loc.content.is_loading());
             loc.content = ContentState::Loaded {
                 value: weak_res,
                 check_alive,
             };
 
-            Poll::Ready(LoadPayload {
+            Poll::Ready(Ok(LoadPayload {
                 asset_key: p.asset_key.clone(),
                 strong: value,
-            })
+            }))
         } else {
             Poll::Pending
         }
@@ -902,18 +1062,50 @@ impl<F, K> PinnedDrop for AssetLoadFuture<F, K> {
     fn drop(self: Pin<&mut Self>) {
         if !self.completed {
             let mut cache = self.cache.lock();
-            let loc = cache
-                .get_mut(&self.asset_key)
-                .expect("Asset loc was removed during loading");
-            assert!(loc.content.is_loading());
-            loc.content = ContentState::Aborted;
+            if let Some(loc) = cache.get_mut(&self.asset_key) {
+                if loc.content.is_loading() {
+                    loc.content = ContentState::Aborted;
+                }
+            }
         }
     }
 }
 
+/// Converts the payload of a panicking loader into an error
+fn panic_error(payload: Box<dyn Any + Send>) -> anyhow::Error {
+    let msg = if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "Unknown panic payload".to_string()
+    };
+
+    anyhow::anyhow!("Asset loader panicked: {msg}")
+}
+
+struct KeepaliveGuard {
+    key: AssetKey,
+    timeline: Arc<Mutex<AssetsTimeline>>,
+}
+
+impl KeepaliveGuard {
+    fn begin(key: AssetKey, timeline: Arc<Mutex<AssetsTimeline>>) -> Self {
+        timeline.lock().keepalive_start(&key);
+        Self { key, timeline }
+    }
+}
+
+impl Drop for KeepaliveGuard {
+    fn drop(&mut self) {
+        self.timeline.lock().keepalive_end(&self.key)
+    }
+}
+
 #[cfg(test)]
 #[cfg(not(target_arch = "wasm32"))]
 mod test {
+    use itertools::Itertools;
     use tokio::{runtime, time::timeout};
 
     use super::*;
@@ -954,7 +1146,7 @@ mod test {
         assert!(matches!(state, Some(ContentState::Aborted)));
 
         let state = assets.content_state(&TestAssetKey { name: "bar".into() });
-        assert!(matches!(state, None));
+        assert!(state.is_none());
 
         let asset = TestAssetKey { name: "foo".into() }.get(&assets).await;
 
@@ -964,6 +1156,21 @@ mod test {
         assert!(matches!(state, Some(ContentState::Loaded { .. })));
     }
 
+    #[tokio::test]
+    async fn peek() {
+        let assets = AssetCache::new(Arc::new(runtime::Handle::current()));
+        let key = TestAssetKey { name: "foo".into() };
+
+        // Keeps loading in the background after the first peek
+        assert_eq!(key.peek(&assets), None);
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+
+        assert_eq!(
+            key.peek(&assets).as_deref(),
+            Some(&TestAsset { name: "foo".into() })
+        );
+    }
+
     #[tokio::test]
     async fn test_weak_asset() {
         use std::sync::atomic::{AtomicU32, Ordering};
@@ -1006,22 +1213,171 @@ mod test {
             assert_eq!(val, 3);
         }
     }
-}
 
-struct KeepaliveGuard {
-    key: AssetKey,
-    timeline: Arc<Mutex<AssetsTimeline>>,
-}
+    #[test]
+    fn derive_asset() {
+        #[derive(Asset, Clone, Debug, PartialEq)]
+        struct Model {
+            mesh: Arc<String>,
+            textures: [Arc<u32>; 2],
+            materials: HashMap<String, Arc<u32>>,
+            lod: Option<(Arc<String>, Arc<u32>, Arc<u32>)>,
+        }
 
-impl KeepaliveGuard {
-    fn begin(key: AssetKey, timeline: Arc<Mutex<AssetsTimeline>>) -> Self {
-        timeline.lock().keepalive_start(&key);
-        Self { key, timeline }
+        #[derive(Asset, Clone, Debug, PartialEq)]
+        struct Wrapper<T>(T, Vec<Arc<u32>>);
+
+        // Bounds and const parameters are kept by the weak struct
+        #[derive(Asset, Clone, Debug, PartialEq)]
+        struct Lods<T: Clone, const N: usize> {
+            levels: [T; N],
+        }
+
+        let model = Model {
+            mesh: Arc::new("mesh".into()),
+            textures: [Arc::new(1), Arc::new(2)],
+            materials: [("metal".to_string(), Arc::new(3))].into_iter().collect(),
+            lod: Some((Arc::new("lod".into()), Arc::new(4), Arc::new(5))),
+        };
+
+        let value = Wrapper(model, vec![Arc::new(6)]);
+        let weak = Asset::to_weak(&value);
+
+        assert_eq!(Wrapper::from_weak(&weak).as_ref(), Some(&value));
+
+        // Releasing any field invalidates the whole asset
+        let Wrapper(model, _) = value;
+        drop(model.materials);
+
+        assert_eq!(Wrapper::<Model>::from_weak(&weak), None);
+
+        let lods = Lods {
+            levels: [Arc::new(1), Arc::new(2)],
+        };
+        let weak = Asset::to_weak(&lods);
+        assert_eq!(Lods::from_weak(&weak).as_ref(), Some(&lods));
     }
-}
 
-impl Drop for KeepaliveGuard {
-    fn drop(&mut self) {
-        self.timeline.lock().keepalive_end(&self.key)
+    #[tokio::test]
+    async fn type_mismatch() {
+        mod a {
+            #[derive(Debug, Clone)]
+            pub struct Key;
+        }
+
+        mod b {
+            #[derive(Debug, Clone)]
+            pub struct Key;
+        }
+
+        #[async_trait]
+        impl AsyncAssetKey<Arc<u32>> for a::Key {
+            async fn load(self, _: AssetCache) -> Arc<u32> {
+                Arc::new(5)
+            }
+        }
+
+        #[async_trait]
+        impl AsyncAssetKey<Arc<String>> for b::Key {
+            async fn load(self, _: AssetCache) -> Arc<String> {
+                Arc::new("five".into())
+            }
+        }
+
+        let assets = AssetCache::new(Arc::new(runtime::Handle::current()));
+
+        assert_eq!(*a::Key.try_get(&assets).await.unwrap(), 5);
+
+        let err = b::Key.try_get(&assets).await.unwrap_err();
+        assert_eq!(err.key().as_str(), "Key");
+        assert!(matches!(err.kind(), AssetErrorKind::TypeMismatch { .. }));
+
+        // The original asset is unaffected
+        assert_eq!(*a::Key.try_get(&assets).await.unwrap(), 5);
+    }
+
+    #[tokio::test]
+    async fn cycle() {
+        #[derive(Debug, Clone)]
+        struct Outer;
+
+        #[derive(Debug, Clone)]
+        struct Inner;
+
+        #[async_trait]
+        impl AsyncAssetKey<Arc<Result<(), AssetError>>> for Outer {
+            async fn load(self, assets: AssetCache) -> Arc<Result<(), AssetError>> {
+                Inner.try_get(&assets).await.unwrap()
+            }
+        }
+
+        #[async_trait]
+        impl AsyncAssetKey<Arc<Result<(), AssetError>>> for Inner {
+            async fn load(self, assets: AssetCache) -> Arc<Result<(), AssetError>> {
+                Arc::new(Outer.try_get(&assets).await.map(|_| ()))
+            }
+        }
+
+        let assets = AssetCache::new(Arc::new(runtime::Handle::current()));
+
+        let res = timeout(Duration::from_secs(1), Outer.try_get(&assets))
+            .await
+            .expect("Cycle was not detected")
+            .unwrap();
+
+        let err = res.as_ref().as_ref().unwrap_err();
+        assert!(matches!(err.kind(), AssetErrorKind::Cycle));
+        assert_eq!(err.key().as_str(), "Outer");
+        assert_eq!(
+            err.stack().iter().map(|v| v.as_str()).collect_vec(),
+            ["Outer", "Inner"]
+        );
+    }
+
+    #[tokio::test]
+    async fn try_load_failed() {
+        #[derive(Debug, Clone)]
+        struct Key;
+
+        #[async_trait]
+        impl AsyncAssetKey<Arc<u32>> for Key {
+            async fn try_load(self, _: AssetCache) -> anyhow::Result<Arc<u32>> {
+                Err(anyhow::anyhow!("Corrupt asset"))
+            }
+        }
+
+        let assets = AssetCache::new(Arc::new(runtime::Handle::current()));
+
+        let err = Key.try_get(&assets).await.unwrap_err();
+        let AssetErrorKind::LoadFailed(source) = err.kind() else {
+            panic!("Unexpected error {err:?}");
+        };
+        assert_eq!(source.to_string(), "Corrupt asset");
+
+        let state = assets.content_state(&Key);
+        assert!(matches!(state, Some(ContentState::Aborted)));
+    }
+
+    #[tokio::test]
+    async fn load_failed() {
+        #[derive(Debug, Clone)]
+        struct Key;
+
+        #[async_trait]
+        impl AsyncAssetKey<Arc<u32>> for Key {
+            #[allow(clippy::diverging_sub_expression)]
+            async fn load(self, _: AssetCache) -> Arc<u32> {
+                panic!("Corrupt asset")
+            }
+        }
+
+        let assets = AssetCache::new(Arc::new(runtime::Handle::current()));
+
+        let err = Key.try_get(&assets).await.unwrap_err();
+        assert!(matches!(err.kind(), AssetErrorKind::LoadFailed(_)));
+        assert!(err.stack().is_empty());
+
+        let state = assets.content_state(&Key);
+        assert!(matches!(state, Some(ContentState::Aborted)));
     }
 }
@@ -0,0 +1,120 @@
+//! Spawning and timers for both tokio and the browser
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("Task was aborted")]
+pub struct Aborted;
+
+/// Owned permission to await or abort a spawned task.
+///
+/// Dropping the handle detaches the task.
+pub struct JoinHandle<T> {
+    #[cfg(not(target_arch = "wasm32"))]
+    inner: tokio::task::JoinHandle<T>,
+    #[cfg(target_arch = "wasm32")]
+    abort: futures::future::AbortHandle,
+    #[cfg(target_arch = "wasm32")]
+    result: futures::channel::oneshot::Receiver<T>,
+}
+
+impl<T> JoinHandle<T> {
+    pub fn abort(&self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        self.inner.abort();
+        #[cfg(target_arch = "wasm32")]
+        self.abort.abort();
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<T> From<tokio::task::JoinHandle<T>> for JoinHandle<T> {
+    fn from(inner: tokio::task::JoinHandle<T>) -> Self {
+        Self { inner }
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, Aborted>;
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.inner).poll(cx).map(|res| match res {
+            Ok(value) => Ok(value),
+            Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+            Err(_) => Err(Aborted),
+        })
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.result)
+            .poll(cx)
+            .map(|res| res.map_err(|_| Aborted))
+    }
+}
+
+/// Aborts the task when dropped
+pub struct AbortOnDrop<T>(JoinHandle<T>);
+
+impl<T> From<JoinHandle<T>> for AbortOnDrop<T> {
+    fn from(handle: JoinHandle<T>) -> Self {
+        Self(handle)
+    }
+}
+
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Spawns a task on the current tokio runtime, or the browser event loop
+#[cfg(not(target_arch = "wasm32"))]
+pub fn spawn<T, F>(fut: F) -> JoinHandle<T>
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::spawn(fut).into()
+}
+
+/// Spawns a task on the current tokio runtime, or the browser event loop
+#[cfg(target_arch = "wasm32")]
+pub fn spawn<T, F>(fut: F) -> JoinHandle<T>
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    let (fut, abort) = futures::future::abortable(fut);
+    let (tx, result) = futures::channel::oneshot::channel();
+
+    wasm_bindgen_futures::spawn_local(async move {
+        if let Ok(value) = fut.await {
+            let _ = tx.send(value);
+        }
+    });
+
+    JoinHandle { abort, result }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await;
+}
+
+/// Waits on a channel rather than a timer future, which keeps the future `Send`
+#[cfg(target_arch = "wasm32")]
+pub async fn sleep(duration: Duration) {
+    let (tx, rx) = futures::channel::oneshot::channel();
+    gloo_timers::callback::Timeout::new(duration.as_millis() as u32, move || {
+        let _ = tx.send(());
+    })
+    .forget();
+
+    let _ = rx.await;
+}
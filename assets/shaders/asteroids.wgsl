@@ -1,12 +1,5 @@
-struct Camera {
-    view: mat4x4<f32>,
-    proj: mat4x4<f32>,
-}
-
-struct ObjectData {
-    model: mat4x4<f32>,
-    color: vec4<f32>,
-}
+#import camera
+#import object
 
 struct VertexInput {
     @location(0) pos: vec3<f32>,
@@ -24,20 +17,33 @@ struct VertexOutput {
 var<uniform> camera: Camera;
 
 @group(0) @binding(1)
-var<uniform> object_data: array<ObjectData, 128>;
-// var<storage> object_data: array<ObjectData>;
+#ifdef STORAGE_OBJECTS
+var<storage> object_data: array<ObjectData>;
+#else
+var<uniform> object_data: array<ObjectData, MAX_OBJECTS>;
+#endif
 
 @group(0) @binding(2)
 var diffuse: texture_2d<f32>;
 @group(0) @binding(3)
 var diffuse_sampler: sampler;
 
+#ifdef CULLING
+// Indices of the visible objects, written by the culling pass
+@group(0) @binding(4)
+var<storage> visible: array<u32>;
+#endif
+
 @vertex
 fn vs_main(
     in: VertexInput,
     @builtin(instance_index) instance: u32,
 ) -> VertexOutput {
+#ifdef CULLING
+    let object = object_data[visible[instance]];
+#else
     let object = object_data[instance];
+#endif
     var out: VertexOutput;
 
     let mvp = camera.proj * camera.view * object.model;
@@ -54,10 +60,7 @@ fn vs_main(
 // Fragment shader
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    var color = in.color * textureSample(diffuse, diffuse_sampler, in.tex_coords);
-
-
-    color = vec4(pow(color.rgb, vec3(1.0 / 2.2)), color.a);
-
-    return color;
+    // Linear, as the texture is decoded from sRGB when sampled. The color can exceed 1 for
+    // emissive objects, which the HDR framebuffer keeps until tonemapping.
+    return in.color * textureSample(diffuse, diffuse_sampler, in.tex_coords);
 }
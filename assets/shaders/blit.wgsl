@@ -0,0 +1,13 @@
+#import fullscreen
+
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+@group(0) @binding(1)
+var source_sampler: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // The level is selected by the view, or by the lod clamp of the sampler where views of single
+    // levels are not supported
+    return textureSampleLevel(source, source_sampler, in.tex_coords, 0.0);
+}
@@ -0,0 +1,52 @@
+#import fullscreen
+#import post_process
+
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+@group(0) @binding(1)
+var source_sampler: sampler;
+@group(0) @binding(2)
+var<uniform> params: PostProcessParams;
+
+// Keeps the parts brighter than the threshold, with a soft transition of `knee` around it
+@fragment
+fn fs_threshold(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSampleLevel(source, source_sampler, in.tex_coords, 0.0).rgb;
+
+    let threshold = params.bloom.x;
+    let knee = params.bloom.y;
+
+    let brightness = max(color.r, max(color.g, color.b));
+    var soft = clamp(brightness - threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee + 0.0001);
+
+    let contribution = max(soft, brightness - threshold) / max(brightness, 0.0001);
+
+    return vec4(color * contribution, 1.0);
+}
+
+// A 9 tap gaussian, using linear filtering to sample two texels at once
+fn blur(uv: vec2<f32>, direction: vec2<f32>) -> vec4<f32> {
+    let step = direction / vec2<f32>(textureDimensions(source));
+
+    var offsets = array(1.3846153846, 3.2307692308);
+    var weights = array(0.3162162162, 0.0702702703);
+
+    var color = textureSampleLevel(source, source_sampler, uv, 0.0) * 0.2270270270;
+    for (var i = 0; i < 2; i++) {
+        color += textureSampleLevel(source, source_sampler, uv + step * offsets[i], 0.0) * weights[i];
+        color += textureSampleLevel(source, source_sampler, uv - step * offsets[i], 0.0) * weights[i];
+    }
+
+    return color;
+}
+
+@fragment
+fn fs_blur_x(in: VertexOutput) -> @location(0) vec4<f32> {
+    return blur(in.tex_coords, vec2(1.0, 0.0));
+}
+
+@fragment
+fn fs_blur_y(in: VertexOutput) -> @location(0) vec4<f32> {
+    return blur(in.tex_coords, vec2(0.0, 1.0));
+}
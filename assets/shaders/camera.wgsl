@@ -0,0 +1,4 @@
+struct Camera {
+    view: mat4x4<f32>,
+    proj: mat4x4<f32>,
+}
@@ -0,0 +1,43 @@
+#import fullscreen
+#import post_process
+#import tonemap
+
+@group(0) @binding(0)
+var scene: texture_2d<f32>;
+@group(0) @binding(1)
+var bloom: texture_2d<f32>;
+@group(0) @binding(2)
+var linear_sampler: sampler;
+@group(0) @binding(3)
+var<uniform> params: PostProcessParams;
+
+// The pivot of the contrast, middle grey in linear space
+const MIDDLE_GREY: f32 = 0.18;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let scene_color = textureSampleLevel(scene, linear_sampler, in.tex_coords, 0.0);
+    var color = scene_color.rgb;
+
+    color += textureSampleLevel(bloom, linear_sampler, in.tex_coords, 0.0).rgb * params.bloom.z;
+
+    // Color grading
+    color *= params.grading.x * params.tint.rgb;
+
+    let luma = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    color = mix(vec3(luma), color, params.grading.z);
+    color = max((color - MIDDLE_GREY) * params.grading.y + MIDDLE_GREY, vec3(0.0));
+
+    // Vignette, with the distance normalized to 1 in the corners
+    let distance = length(in.tex_coords - 0.5) * sqrt(2.0);
+    let radius = params.vignette.y;
+    color *= 1.0 - params.vignette.x * smoothstep(radius, radius + params.vignette.z, distance);
+
+    color = tonemap(color, params.tonemapper);
+
+#ifdef ENCODE_SRGB
+    color = linear_to_srgb(color);
+#endif
+
+    return vec4(color, scene_color.a);
+}
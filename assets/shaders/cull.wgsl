@@ -0,0 +1,159 @@
+#import camera
+#import object
+
+struct CullParams {
+    object_count: u32,
+}
+
+struct DrawIndexedIndirect {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+@group(0) @binding(1)
+var<storage> object_data: array<ObjectData>;
+
+@group(0) @binding(2)
+var<uniform> params: CullParams;
+
+// For each object, its slot among the visible objects of its workgroup or NOT_VISIBLE, followed
+// by the number of visible objects in each workgroup, which `cs_scan` turns into the offset of the
+// workgroup. These share a buffer to stay within the storage buffer limit of downlevel devices.
+@group(0) @binding(3)
+var<storage, read_write> scratch: array<u32>;
+
+// Compacted indices of the objects which passed culling, in their original order
+@group(0) @binding(4)
+var<storage, read_write> visible: array<u32>;
+
+@group(0) @binding(5)
+var<storage, read_write> draw: DrawIndexedIndirect;
+
+const WORKGROUP_SIZE: u32 = 64u;
+const NOT_VISIBLE: u32 = 0xffffffffu;
+
+var<workgroup> scan: array<u32, WORKGROUP_SIZE>;
+
+fn row(m: mat4x4<f32>, i: u32) -> vec4<f32> {
+    return vec4(m[0][i], m[1][i], m[2][i], m[3][i]);
+}
+
+// Returns true if the sphere is at least partially on the inner side of the plane
+fn inside(plane: vec4<f32>, center: vec3<f32>, radius: f32) -> bool {
+    return dot(plane.xyz, center) + plane.w >= -radius * length(plane.xyz);
+}
+
+fn is_visible(index: u32) -> bool {
+    let model = object_data[index].model;
+    let center = model[3].xyz;
+    // The meshes fit within a unit sphere
+    let radius = max(length(model[0].xyz), max(length(model[1].xyz), length(model[2].xyz)));
+
+    // Frustum planes extracted from the view projection, with a 0..1 depth range
+    let view_proj = camera.proj * camera.view;
+    let r0 = row(view_proj, 0u);
+    let r1 = row(view_proj, 1u);
+    let r2 = row(view_proj, 2u);
+    let r3 = row(view_proj, 3u);
+
+    var planes = array(r3 + r0, r3 - r0, r3 + r1, r3 - r1, r2, r3 - r2);
+    for (var i = 0; i < 6; i++) {
+        if !inside(planes[i], center, radius) {
+            return false;
+        }
+    }
+
+    return true;
+}
+
+// Inclusive prefix sum of `value` across the workgroup
+fn prefix_sum(local: u32, value: u32) -> u32 {
+    scan[local] = value;
+    workgroupBarrier();
+
+    for (var offset = 1u; offset < WORKGROUP_SIZE; offset *= 2u) {
+        var sum = scan[local];
+        if local >= offset {
+            sum += scan[local - offset];
+        }
+        workgroupBarrier();
+        scan[local] = sum;
+        workgroupBarrier();
+    }
+
+    return scan[local];
+}
+
+// Culls the objects, and numbers the visible objects within each workgroup
+@compute @workgroup_size(64)
+fn cs_cull(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(local_invocation_index) local: u32,
+    @builtin(workgroup_id) group: vec3<u32>,
+) {
+    let index = id.x;
+    let in_bounds = index < params.object_count;
+    let passed = in_bounds && is_visible(index);
+
+    let sum = prefix_sum(local, u32(passed));
+
+    if in_bounds {
+        scratch[index] = select(NOT_VISIBLE, sum - 1u, passed);
+    }
+
+    if local == WORKGROUP_SIZE - 1u {
+        scratch[params.object_count + group.x] = sum;
+    }
+}
+
+// Replaces the count of each workgroup by the visible objects of all previous workgroups, and
+// writes the total to the draw.
+//
+// Dispatched as a single workgroup, which walks the counts a workgroup at a time.
+@compute @workgroup_size(64)
+fn cs_scan(@builtin(local_invocation_index) local: u32) {
+    let group_count = (params.object_count + WORKGROUP_SIZE - 1u) / WORKGROUP_SIZE;
+
+    var carry = 0u;
+    for (var start = 0u; start < group_count; start += WORKGROUP_SIZE) {
+        let i = start + local;
+        var count = 0u;
+        if i < group_count {
+            count = scratch[params.object_count + i];
+        }
+
+        let sum = prefix_sum(local, count);
+        if i < group_count {
+            scratch[params.object_count + i] = carry + sum - count;
+        }
+
+        carry += scan[WORKGROUP_SIZE - 1u];
+        // The next chunk overwrites `scan`
+        workgroupBarrier();
+    }
+
+    if local == 0u {
+        draw.instance_count = carry;
+    }
+}
+
+// Writes the visible objects after those of all previous workgroups, which keeps their order
+@compute @workgroup_size(64)
+fn cs_compact(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(workgroup_id) group: vec3<u32>,
+) {
+    let index = id.x;
+    if index < params.object_count {
+        let slot = scratch[index];
+        if slot != NOT_VISIBLE {
+            visible[scratch[params.object_count + group.x] + slot] = index;
+        }
+    }
+}
@@ -0,0 +1,4 @@
+struct ObjectData {
+    model: mat4x4<f32>,
+    color: vec4<f32>,
+}
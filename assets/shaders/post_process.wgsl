@@ -0,0 +1,10 @@
+struct PostProcessParams {
+    // threshold, knee, intensity
+    bloom: vec4<f32>,
+    // intensity, radius, smoothness
+    vignette: vec4<f32>,
+    // exposure, contrast, saturation
+    grading: vec4<f32>,
+    tint: vec4<f32>,
+    tonemapper: u32,
+}
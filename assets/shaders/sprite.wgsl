@@ -0,0 +1,55 @@
+#import camera
+
+struct VertexInput {
+    // A unit square centered on the origin
+    @location(0) pos: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+}
+
+// Matches `SpriteInstance` on the cpu
+struct InstanceInput {
+    @location(2) position: vec2<f32>,
+    @location(3) scale: vec2<f32>,
+    @location(4) rotation: f32,
+    @location(5) page: u32,
+    @location(6) uv_min: vec2<f32>,
+    @location(7) uv_max: vec2<f32>,
+    @location(8) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) @interpolate(flat) page: u32,
+    @location(2) color: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+@group(1) @binding(0)
+var sprite_texture: texture_2d_array<f32>;
+@group(1) @binding(1)
+var sprite_sampler: sampler;
+
+@vertex
+fn vs_main(vertex: VertexInput, instance: InstanceInput) -> VertexOutput {
+    let c = cos(instance.rotation);
+    let s = sin(instance.rotation);
+
+    let local = vertex.pos.xy * instance.scale;
+    let world = vec2(local.x * c - local.y * s, local.x * s + local.y * c) + instance.position;
+
+    var out: VertexOutput;
+    out.clip_position = camera.proj * camera.view * vec4(world, 0.0, 1.0);
+    out.tex_coords = mix(instance.uv_min, instance.uv_max, vertex.tex_coords);
+    out.page = instance.page;
+    out.color = instance.color;
+
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color * textureSample(sprite_texture, sprite_sampler, in.tex_coords, in.page);
+}
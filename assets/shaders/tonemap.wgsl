@@ -0,0 +1,73 @@
+// Tonemappers, compressing linear HDR colors into the 0 to 1 range of the display
+
+fn tonemap_reinhard(color: vec3<f32>) -> vec3<f32> {
+    return color / (1.0 + color);
+}
+
+// The filmic curve of ACES, as fitted by Krzysztof Narkowicz. The input is scaled to match the
+// brightness of the reference transform.
+fn tonemap_aces(color: vec3<f32>) -> vec3<f32> {
+    let x = color * 0.6;
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), vec3(0.0), vec3(1.0));
+}
+
+// A polynomial fit of the default AgX contrast curve
+fn agx_contrast(x: vec3<f32>) -> vec3<f32> {
+    let x2 = x * x;
+    let x4 = x2 * x2;
+
+    return 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2
+        + 0.1191 * x - 0.00232;
+}
+
+// AgX, which desaturates bright colors towards white rather than skewing their hue
+fn tonemap_agx(color: vec3<f32>) -> vec3<f32> {
+    let inset = mat3x3<f32>(
+        0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+        0.0784335999999992, 0.878468636469772, 0.0784336,
+        0.0792237451477643, 0.0791661274605434, 0.879142973793104,
+    );
+    let outset = mat3x3<f32>(
+        1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+        -0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+        -0.0990297440797205, -0.0989611768448433, 1.15107367264116,
+    );
+
+    // The range of the log encoding, in stops around middle grey
+    let min_ev = -12.47393;
+    let max_ev = 4.026069;
+
+    var x = inset * max(color, vec3(1e-10));
+    x = clamp(log2(x), vec3(min_ev), vec3(max_ev));
+    x = (x - min_ev) / (max_ev - min_ev);
+    x = agx_contrast(x);
+    x = outset * x;
+
+    // The curve outputs display encoded colors, which are decoded to stay in linear space
+    return pow(max(x, vec3(0.0)), vec3(2.2));
+}
+
+// Selects the tonemapper by the value of `Tonemapper` on the cpu, clamping for `None`
+fn tonemap(color: vec3<f32>, tonemapper: u32) -> vec3<f32> {
+    switch tonemapper {
+        case 1u: {
+            return tonemap_reinhard(color);
+        }
+        case 2u: {
+            return tonemap_aces(color);
+        }
+        case 3u: {
+            return tonemap_agx(color);
+        }
+        default: {
+            return clamp(color, vec3(0.0), vec3(1.0));
+        }
+    }
+}
+
+// Encodes linear colors for surfaces which are not sRGB
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055;
+    return select(high, low, color <= vec3(0.0031308));
+}
@@ -2,7 +2,11 @@ use std::sync::Arc;
 
 use anyhow::Context;
 
-use shared::{game::Game, graphics::Gpu, renderer::Renderer};
+use shared::{
+    game::Game,
+    graphics::{Gpu, GpuConfig},
+    renderer::Renderer,
+};
 use tracing::metadata::LevelFilter;
 use tracing_subscriber::{
     fmt::time::UtcTime, prelude::__tracing_subscriber_SubscriberExt, registry,
@@ -70,10 +74,12 @@ pub async fn run() -> anyhow::Result<()> {
         .build(&event_loop)
         .unwrap();
 
+    let main_window = window.id();
+
     insert_canvas(&window);
 
-    let gpu = Arc::new(Gpu::new(window).await);
-    let mut renderer = Renderer::new(&gpu);
+    let gpu = Arc::new(Gpu::new(window, &GpuConfig::web()).await?);
+    let mut renderer = Renderer::new(&gpu)?;
 
     let mut game = Game::new(gpu.clone()).await.unwrap();
 
@@ -82,19 +88,28 @@ pub async fn run() -> anyhow::Result<()> {
     let dt = 1.0 / 50.0;
 
     event_loop.spawn(move |event, _, control_flow| match event {
+        // Input used by the game, such as to move the camera, is not handled any further
+        Event::WindowEvent {
+            ref event,
+            window_id,
+        } if window_id == main_window && game.input(event) => {}
         Event::WindowEvent {
             ref event,
             window_id,
-        } if window_id == gpu.window().id() => match event {
+        } if window_id == main_window => match event {
             WindowEvent::ReceivedCharacter(c) => {
                 tracing::info!("Typed: {c}");
             }
             WindowEvent::Resized(physical_size) => {
                 gpu.resize(*physical_size);
+                renderer.resize();
+                game.resize();
             }
             WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
                 // new_inner_size is &&mut so we have to dereference it twice
                 gpu.resize(**new_inner_size);
+                renderer.resize();
+                game.resize();
             }
             WindowEvent::CloseRequested
             | WindowEvent::KeyboardInput {
@@ -108,7 +123,7 @@ pub async fn run() -> anyhow::Result<()> {
             } => *control_flow = ControlFlow::Exit,
             _ => {}
         },
-        Event::RedrawRequested(window_id) if window_id == gpu.window().id() => {
+        Event::RedrawRequested(window_id) if window_id == main_window => {
             match gpu.render(|encoder, view| renderer.render(encoder, view, &mut game)) {
                 Ok(_) => {}
                 // Reconfigure the surface if lost
@@ -2,7 +2,11 @@ use std::{sync::Arc, time::Instant};
 
 use anyhow::Context;
 
-use shared::{game::Game, graphics::Gpu, renderer::Renderer};
+use shared::{
+    game::Game,
+    graphics::{parse_present_mode, Gpu, GpuConfig},
+    renderer::Renderer,
+};
 use tracing::metadata::LevelFilter;
 use tracing_subscriber::{
     prelude::__tracing_subscriber_SubscriberExt, registry, util::SubscriberInitExt, EnvFilter,
@@ -16,7 +20,7 @@ use winit::{
 
 #[tokio::main(flavor = "current_thread")]
 pub async fn main() -> eyre::Result<()> {
-    color_eyre::install().unwrap();
+    color_eyre::install()?;
     let fmt_layer = tracing_subscriber::fmt::layer();
 
     registry()
@@ -28,10 +32,11 @@ pub async fn main() -> eyre::Result<()> {
         .with(fmt_layer)
         .init();
 
-    run().await
+    // Keeps the whole chain of causes, as `anyhow::Error` does not implement `Error`
+    run().await.map_err(|err| eyre::eyre!("{err:?}"))
 }
 
-pub async fn run() -> eyre::Result<()> {
+pub async fn run() -> anyhow::Result<()> {
     let event_loop = EventLoop::new();
 
     let start = Instant::now();
@@ -39,31 +44,62 @@ pub async fn run() -> eyre::Result<()> {
     let window = WindowBuilder::new()
         .with_title("Winit window")
         .build(&event_loop)
-        .unwrap();
+        .context("Failed to create window")?;
+
+    let main_window = window.id();
+
+    let mut config = GpuConfig::native();
+    if let Some(backends) = wgpu::util::backend_bits_from_env() {
+        config = config.with_backends(backends);
+    }
+
+    if let Ok(present_mode) = std::env::var("ORION_PRESENT_MODE") {
+        match parse_present_mode(&present_mode) {
+            Some(present_mode) => config = config.with_present_mode(present_mode),
+            None => tracing::warn!("Unknown present mode: {present_mode:?}"),
+        }
+    }
+
+    if let Ok(sample_count) = std::env::var("ORION_MSAA") {
+        match sample_count.parse() {
+            Ok(sample_count) => config = config.with_sample_count(sample_count),
+            Err(_) => tracing::warn!("Invalid sample count: {sample_count:?}"),
+        }
+    }
 
-    let gpu = Arc::new(Gpu::new(window).await);
-    let mut renderer = Renderer::new(&gpu);
+    let gpu = Arc::new(Gpu::new(window, &config).await?);
+    let mut renderer = Renderer::new(&gpu)?;
 
-    let mut game = Game::new(gpu.clone()).await.unwrap();
+    let mut game = Game::new(gpu.clone()).await?;
 
     let mut current_time = start.elapsed().as_secs_f64();
+    let mut last_shader_reload = current_time;
     let mut acc = 0.0;
     let dt = 1.0 / 50.0;
 
     event_loop.run(move |event, _, control_flow| match event {
+        // Input used by the game, such as to move the camera, is not handled any further
+        Event::WindowEvent {
+            ref event,
+            window_id,
+        } if window_id == main_window && game.input(event) => {}
         Event::WindowEvent {
             ref event,
             window_id,
-        } if window_id == gpu.window().id() => match event {
+        } if window_id == main_window => match event {
             WindowEvent::ReceivedCharacter(c) => {
                 tracing::info!("Typed: {c}");
             }
             WindowEvent::Resized(physical_size) => {
                 gpu.resize(*physical_size);
+                renderer.resize();
+                game.resize();
             }
             WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
                 // new_inner_size is &&mut so we have to dereference it twice
                 gpu.resize(**new_inner_size);
+                renderer.resize();
+                game.resize();
             }
             WindowEvent::CloseRequested
             | WindowEvent::KeyboardInput {
@@ -77,7 +113,7 @@ pub async fn run() -> eyre::Result<()> {
             } => *control_flow = ControlFlow::Exit,
             _ => {}
         },
-        Event::RedrawRequested(window_id) if window_id == gpu.window().id() => {
+        Event::RedrawRequested(window_id) if window_id == main_window => {
             match gpu.render(|encoder, view| renderer.render(encoder, view, &mut game)) {
                 Ok(_) => {}
                 // Reconfigure the surface if lost
@@ -101,6 +137,11 @@ pub async fn run() -> eyre::Result<()> {
                 acc -= dt;
             }
 
+            if new_time - last_shader_reload > 1.0 {
+                game.reload_shaders();
+                last_shader_reload = new_time;
+            }
+
             match gpu.render(|encoder, view| renderer.render(encoder, view, &mut game)) {
                 Ok(_) => {}
                 // Reconfigure the surface if lost
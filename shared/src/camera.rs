@@ -1,4 +1,8 @@
-use glam::Mat4;
+use glam::{vec2, vec3, Mat4, Vec2};
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize, Position},
+    event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent},
+};
 
 #[repr(C)]
 #[derive(bytemuck::Zeroable, bytemuck::Pod, Copy, Debug, Clone)]
@@ -12,3 +16,464 @@ impl Camera {
         Self { view, proj }
     }
 }
+
+/// The part of the world shown by a [`CameraController`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct View {
+    center: Vec2,
+    /// Half the visible height in world units. The width follows from the aspect ratio.
+    extent: f32,
+}
+
+/// A zoom which is easing in, keeping the world position under the cursor in place
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Zoom {
+    anchor: Vec2,
+    /// The view when the zoom started
+    start: View,
+}
+
+/// A 2D camera looking down the z axis, panned by dragging with the mouse and zoomed towards the
+/// cursor with the scroll wheel.
+///
+/// Zooming eases towards the requested view, while dragging follows the cursor directly. Screen
+/// positions are in physical pixels with the origin in the top left, and world positions have y
+/// pointing up.
+#[derive(Debug, Clone)]
+pub struct CameraController {
+    current: View,
+    target: View,
+    zoom: Option<Zoom>,
+    min_extent: f32,
+    max_extent: f32,
+    /// The area the view is kept within, as its minimum and maximum corner
+    bounds: Option<(Vec2, Vec2)>,
+    /// How quickly the view approaches the target, as the fraction remaining after a second is
+    /// `exp(-smoothing)`
+    smoothing: f32,
+    /// The zoom factor of each line scrolled
+    zoom_speed: f32,
+    pan_button: MouseButton,
+    size: PhysicalSize<u32>,
+    scale_factor: f64,
+    cursor: Option<PhysicalPosition<f64>>,
+    dragging: bool,
+    /// Whether the view changed since the last [`Self::take_changed`]
+    changed: bool,
+}
+
+impl CameraController {
+    /// Pixels scrolled by touchpads which count as one line of a mouse wheel
+    const PIXELS_PER_LINE: f64 = 50.0;
+
+    /// Creates a controller showing 10 units above and below the origin, for a window of `size`
+    /// physical pixels
+    pub fn new(size: PhysicalSize<u32>, scale_factor: f64) -> Self {
+        let view = View {
+            center: Vec2::ZERO,
+            extent: 10.0,
+        };
+
+        Self {
+            current: view,
+            target: view,
+            zoom: None,
+            min_extent: 1.0,
+            max_extent: 100.0,
+            bounds: None,
+            smoothing: 12.0,
+            zoom_speed: 1.1,
+            pan_button: MouseButton::Left,
+            size,
+            scale_factor,
+            cursor: None,
+            dragging: false,
+            changed: true,
+        }
+    }
+
+    /// Sets half of the visible height, which is kept when the window is resized
+    pub fn with_extent(mut self, extent: f32) -> Self {
+        self.target.extent = extent;
+        self.current = self.clamp(self.target);
+        self.target = self.current;
+        self
+    }
+
+    /// Limits how far the camera can zoom in and out, as half of the visible height
+    pub fn with_zoom_limits(mut self, min_extent: f32, max_extent: f32) -> Self {
+        self.min_extent = min_extent;
+        self.max_extent = max_extent;
+        self.current = self.clamp(self.current);
+        self.target = self.current;
+        self
+    }
+
+    /// Keeps the view within the rectangle from `min` to `max`, or centered on it when the view
+    /// is larger
+    pub fn with_bounds(mut self, min: Vec2, max: Vec2) -> Self {
+        self.bounds = Some((min, max));
+        self.current = self.clamp(self.current);
+        self.target = self.current;
+        self
+    }
+
+    /// Sets how quickly zooming eases in, where higher is faster and 0 disables the easing
+    pub fn with_smoothing(mut self, smoothing: f32) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+
+    pub fn with_zoom_speed(mut self, zoom_speed: f32) -> Self {
+        self.zoom_speed = zoom_speed;
+        self
+    }
+
+    pub fn with_pan_button(mut self, pan_button: MouseButton) -> Self {
+        self.pan_button = pan_button;
+        self
+    }
+
+    /// Returns the center of the view in world units
+    pub fn center(&self) -> Vec2 {
+        self.current.center
+    }
+
+    /// Returns half of the visible width and height in world units
+    pub fn half_size(&self) -> Vec2 {
+        self.half_size_of(self.current)
+    }
+
+    fn half_size_of(&self, view: View) -> Vec2 {
+        let aspect = self.size.width.max(1) as f32 / self.size.height.max(1) as f32;
+        vec2(view.extent * aspect, view.extent)
+    }
+
+    /// Handles mouse input, returning true if the event was used
+    pub fn input(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                let previous = self.cursor.replace(*position);
+
+                match previous {
+                    Some(previous) if self.dragging => {
+                        self.pan(vec2(
+                            (position.x - previous.x) as f32,
+                            (position.y - previous.y) as f32,
+                        ));
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor = None;
+                self.dragging = false;
+                false
+            }
+            WindowEvent::MouseInput { state, button, .. } if *button == self.pan_button => {
+                self.dragging = *state == ElementState::Pressed;
+                true
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(v) => (v.y / Self::PIXELS_PER_LINE) as f32,
+                };
+
+                let center = vec2(self.size.width as f32, self.size.height as f32) / 2.0;
+                let cursor = self.cursor.map_or(center, |v| vec2(v.x as f32, v.y as f32));
+
+                // Scrolling up zooms in, which shrinks the extent
+                self.zoom_at(cursor, self.zoom_speed.powf(-lines));
+                true
+            }
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.scale_factor = *scale_factor;
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// Moves the view by a distance in physical pixels, such that the world follows the cursor
+    pub fn pan(&mut self, delta: Vec2) {
+        // The visible view, which may still be zooming
+        let scale = 2.0 * self.current.extent / self.size.height.max(1) as f32;
+        let offset = vec2(-delta.x, delta.y) * scale;
+
+        // Dragging follows the cursor without easing
+        match &mut self.zoom {
+            Some(zoom) => {
+                self.current.center += offset;
+
+                // The zoom continues around the same world position, now from the moved view
+                let current = self.current;
+                let from_anchor = |view: View| {
+                    zoom.anchor + (current.center - zoom.anchor) * view.extent / current.extent
+                };
+                zoom.start.center = from_anchor(zoom.start);
+                self.target.center = from_anchor(self.target);
+            }
+            None => {
+                self.target.center += offset;
+                self.current.center = self.target.center;
+            }
+        }
+
+        self.target = self.clamp(self.target);
+        self.current = self.clamp(self.current);
+        self.changed = true;
+    }
+
+    /// Zooms by `factor` while keeping the world position under the `cursor`, in physical
+    /// pixels, in place. Factors below 1 zoom in.
+    pub fn zoom_at(&mut self, cursor: Vec2, factor: f32) {
+        let anchor = self.to_world(self.current, cursor);
+
+        let extent = (self.target.extent * factor).clamp(self.min_extent, self.max_extent);
+        let factor = extent / self.current.extent;
+
+        self.target = self.clamp(View {
+            center: anchor + (self.current.center - anchor) * factor,
+            extent,
+        });
+        self.zoom = Some(Zoom {
+            anchor,
+            start: self.current,
+        });
+    }
+
+    /// Moves the view to `center`, easing towards it
+    pub fn look_at(&mut self, center: Vec2) {
+        self.target.center = center;
+        self.target = self.clamp(self.target);
+        self.zoom = None;
+    }
+
+    /// Updates the size of the window, keeping the visible height
+    pub fn resize(&mut self, size: PhysicalSize<u32>) {
+        self.size = size;
+        self.current = self.clamp(self.current);
+        self.target = self.clamp(self.target);
+        self.changed = true;
+    }
+
+    /// Eases the view towards the target
+    pub fn update(&mut self, dt: f32) {
+        if self.current == self.target {
+            return;
+        }
+
+        let t = if self.smoothing > 0.0 {
+            1.0 - (-self.smoothing * dt).exp()
+        } else {
+            1.0
+        };
+
+        // The extent is interpolated in log space, so zooming in and out feels the same
+        let extent = self.current.extent * (self.target.extent / self.current.extent).powf(t);
+
+        // Zooming scales the view around the anchor, rather than moving its center in a straight
+        // line, which would let the anchor drift until the zoom ends
+        let center = match self.zoom {
+            Some(Zoom { anchor, start }) => {
+                anchor + (start.center - anchor) * extent / start.extent
+            }
+            None => self.current.center.lerp(self.target.center, t),
+        };
+
+        let close = (extent / self.target.extent - 1.0).abs() < 1e-4
+            && center.distance(self.target.center) < 1e-4 * extent;
+
+        self.current = if close {
+            self.zoom = None;
+            self.target
+        } else {
+            self.clamp(View { center, extent })
+        };
+
+        self.changed = true;
+    }
+
+    /// Returns whether the view changed since the last call, such that the camera needs to be
+    /// uploaded again
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+
+    pub fn camera(&self) -> Camera {
+        let half = self.half_size();
+        let center = self.current.center;
+
+        Camera::new(
+            Mat4::from_translation(vec3(-center.x, -center.y, 1.0)),
+            Mat4::orthographic_lh(-half.x, half.x, -half.y, half.y, 0.1, 1000.0),
+        )
+    }
+
+    /// Converts a position in the window to world units. Logical positions are scaled by the
+    /// scale factor of the window.
+    pub fn screen_to_world(&self, position: impl Into<Position>) -> Vec2 {
+        let position = position.into().to_physical::<f64>(self.scale_factor);
+        self.to_world(self.current, vec2(position.x as f32, position.y as f32))
+    }
+
+    /// Converts a world position to physical pixels in the window
+    pub fn world_to_screen(&self, world: Vec2) -> PhysicalPosition<f64> {
+        let size = vec2(self.size.width as f32, self.size.height as f32);
+        let ndc = (world - self.current.center) / self.half_size();
+
+        PhysicalPosition::new(
+            ((ndc.x + 1.0) / 2.0 * size.x) as f64,
+            ((1.0 - ndc.y) / 2.0 * size.y) as f64,
+        )
+    }
+
+    fn to_world(&self, view: View, screen: Vec2) -> Vec2 {
+        let size = vec2(
+            self.size.width.max(1) as f32,
+            self.size.height.max(1) as f32,
+        );
+        let ndc = vec2(screen.x / size.x * 2.0 - 1.0, 1.0 - screen.y / size.y * 2.0);
+
+        view.center + ndc * self.half_size_of(view)
+    }
+
+    /// Limits the zoom and keeps the view within the bounds
+    fn clamp(&self, view: View) -> View {
+        let extent = view.extent.clamp(self.min_extent, self.max_extent);
+        let mut center = view.center;
+
+        if let Some((min, max)) = self.bounds {
+            let half = self.half_size_of(View { center, extent });
+
+            for i in 0..2 {
+                center[i] = if max[i] - min[i] < 2.0 * half[i] {
+                    (min[i] + max[i]) / 2.0
+                } else {
+                    center[i].clamp(min[i] + half[i], max[i] - half[i])
+                };
+            }
+        }
+
+        View { center, extent }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use winit::dpi::LogicalPosition;
+
+    use super::*;
+
+    fn controller() -> CameraController {
+        CameraController::new(PhysicalSize::new(400, 200), 2.0).with_smoothing(0.0)
+    }
+
+    fn assert_close(a: Vec2, b: Vec2) {
+        assert!(a.distance(b) < 1e-4, "{a} != {b}");
+    }
+
+    #[test]
+    fn screen_to_world() {
+        let camera = controller();
+
+        assert_close(
+            camera.screen_to_world(PhysicalPosition::new(200.0, 100.0)),
+            Vec2::ZERO,
+        );
+        assert_close(
+            camera.screen_to_world(PhysicalPosition::new(0.0, 0.0)),
+            vec2(-20.0, 10.0),
+        );
+        // Logical positions are half the physical ones at a scale factor of 2
+        assert_close(
+            camera.screen_to_world(LogicalPosition::new(200.0, 100.0)),
+            vec2(20.0, -10.0),
+        );
+
+        let screen = camera.world_to_screen(vec2(5.0, 5.0));
+        assert_close(camera.screen_to_world(screen), vec2(5.0, 5.0));
+    }
+
+    #[test]
+    fn zoom_towards_cursor() {
+        let mut camera = controller();
+
+        let cursor = vec2(300.0, 50.0);
+        let before = camera.screen_to_world(PhysicalPosition::new(300.0, 50.0));
+
+        camera.zoom_at(cursor, 0.5);
+        camera.update(1.0);
+
+        assert_eq!(camera.half_size(), vec2(10.0, 5.0));
+        assert_close(
+            camera.screen_to_world(PhysicalPosition::new(300.0, 50.0)),
+            before,
+        );
+    }
+
+    #[test]
+    fn zoom_keeps_anchor_while_easing() {
+        let mut camera = CameraController::new(PhysicalSize::new(400, 200), 1.0);
+        camera.look_at(vec2(3.0, 0.0));
+        camera.update(0.05);
+
+        let cursor = PhysicalPosition::new(300.0, 50.0);
+        let anchor = camera.screen_to_world(cursor);
+        camera.zoom_at(vec2(300.0, 50.0), 0.25);
+
+        for _ in 0..10 {
+            camera.update(0.02);
+            assert!(camera.half_size().y > 2.5 && camera.half_size().y < 10.0);
+            assert_close(camera.screen_to_world(cursor), anchor);
+        }
+
+        // Dragging mid zoom follows the cursor at the visible scale
+        let moved = PhysicalPosition::new(250.0, 80.0);
+        camera.pan(vec2(-50.0, 30.0));
+        assert_close(camera.screen_to_world(moved), anchor);
+
+        for _ in 0..100 {
+            camera.update(0.02);
+            assert_close(camera.screen_to_world(moved), anchor);
+        }
+        assert_eq!(camera.half_size().y, 2.5);
+    }
+
+    #[test]
+    fn easing() {
+        let mut camera = CameraController::new(PhysicalSize::new(400, 200), 1.0);
+        camera.look_at(vec2(10.0, 0.0));
+
+        camera.update(0.05);
+        let x = camera.center().x;
+        assert!(x > 0.0 && x < 10.0);
+
+        for _ in 0..100 {
+            camera.update(0.05);
+        }
+        assert_eq!(camera.center(), vec2(10.0, 0.0));
+
+        assert!(camera.take_changed());
+        camera.update(0.05);
+        assert!(!camera.take_changed());
+    }
+
+    #[test]
+    fn bounds() {
+        let mut camera = controller()
+            .with_bounds(vec2(-30.0, -30.0), vec2(30.0, 30.0))
+            .with_zoom_limits(1.0, 20.0);
+
+        camera.pan(vec2(-10000.0, 0.0));
+        assert_eq!(camera.center(), vec2(10.0, 0.0));
+
+        // Wider than the bounds, which centers the view horizontally
+        camera.zoom_at(vec2(0.0, 0.0), 100.0);
+        camera.update(1.0);
+        assert_eq!(camera.half_size(), vec2(40.0, 20.0));
+        assert_eq!(camera.center().x, 0.0);
+    }
+}
@@ -2,19 +2,50 @@ use std::{f32::consts::TAU, sync::Arc};
 
 use bytemuck::{Pod, Zeroable};
 
-use glam::{vec2, vec3, Mat4, Quat, Vec2, Vec3, Vec4};
+use glam::{vec2, Mat4, Quat, Vec2, Vec3, Vec4};
+use image::DynamicImage;
 use rand::{Rng, SeedableRng};
 use rand_pcg::Pcg32;
-use wgpu::{BindGroup, BufferUsages, IndexFormat, RenderPass, ShaderStages, TextureView};
+use wgpu::{BindGroup, BufferUsages, CommandEncoder, RenderPass, TextureView};
+use winit::event::WindowEvent;
 
 use crate::{
-    camera::Camera,
+    camera::{Camera, CameraController},
     graphics::{
-        BindGroupBuilder, BindGroupLayoutBuilder, Gpu, Mesh, Shader, ShaderDesc, Texture,
-        TypedBuffer, Vertex,
+        BindGroupBuilder, BindGroupLayout, BlendMode, ComputeShader, ComputeShaderDesc, Gpu, Mesh,
+        MeshBuilder, SamplerDesc, Shader, ShaderDefines, ShaderDesc, ShaderLibrary, Texture,
+        TypedBuffer, Vertex, VertexFormat,
     },
 };
 
+/// Uniform buffers have a strict byte size limit, so objects are drawn in chunks of this size
+const CHUNK_SIZE: usize = 128;
+
+/// Returns the WGSL modules used by the game
+pub fn shader_library() -> ShaderLibrary {
+    ShaderLibrary::new()
+        .with_module(
+            "camera",
+            "assets/shaders/camera.wgsl",
+            include_str!("../../assets/shaders/camera.wgsl"),
+        )
+        .with_module(
+            "object",
+            "assets/shaders/object.wgsl",
+            include_str!("../../assets/shaders/object.wgsl"),
+        )
+        .with_module(
+            "cull",
+            "assets/shaders/cull.wgsl",
+            include_str!("../../assets/shaders/cull.wgsl"),
+        )
+        .with_module(
+            "asteroids",
+            "assets/shaders/asteroids.wgsl",
+            include_str!("../../assets/shaders/asteroids.wgsl"),
+        )
+}
+
 pub struct Asteroid {
     pub color: Vec3,
     pub radius: f32,
@@ -24,6 +55,74 @@ pub struct Asteroid {
     pub lifetime: f32,
 }
 
+/// Generates the irregular outlines of asteroids
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AsteroidShape {
+    pub vertex_count: u32,
+    /// How far the angles between vertices deviate from even spacing, from 0 to 1
+    pub irregularity: f32,
+    /// How far vertices are pulled in from the outer radius, from 0 to 1
+    pub roughness: f32,
+}
+
+impl Default for AsteroidShape {
+    fn default() -> Self {
+        Self {
+            vertex_count: 12,
+            irregularity: 0.5,
+            roughness: 0.35,
+        }
+    }
+}
+
+impl AsteroidShape {
+    /// Returns a counter-clockwise outline around the origin which touches a radius of 0.5, so
+    /// it fills the same space as [`Mesh::square`].
+    ///
+    /// The same seed always produces the same outline.
+    pub fn generate(&self, seed: u64) -> Vec<Vec2> {
+        let mut rng = Pcg32::seed_from_u64(seed);
+
+        let n = self.vertex_count.max(3) as usize;
+        let step = TAU / n as f32;
+
+        // Keeping the jitter below half a step keeps the vertices in order, and below a quarter of
+        // the steps past 2 keeps every gap below half a turn, which keeps the outline star-shaped
+        // around the origin
+        let max_jitter = 0.95 * (n as f32 - 2.0) / 4.0;
+        let jitter = (self.irregularity.clamp(0.0, 0.95) / 2.0).min(max_jitter);
+        let roughness = self.roughness.clamp(0.0, 1.0);
+
+        let angles = (0..n)
+            .map(|i| (i as f32 + rng.gen_range(-jitter..=jitter)) * step)
+            .collect::<Vec<_>>();
+        let radii = (0..n)
+            .map(|_| 1.0 - roughness * rng.gen::<f32>())
+            .collect::<Vec<_>>();
+
+        // Averaging with the neighbours rounds off single spikes
+        let smoothed = (0..n)
+            .map(|i| (radii[(i + n - 1) % n] + 2.0 * radii[i] + radii[(i + 1) % n]) / 4.0)
+            .collect::<Vec<_>>();
+
+        let max = smoothed.iter().copied().fold(f32::EPSILON, f32::max);
+
+        angles
+            .iter()
+            .zip(&smoothed)
+            .map(|(&angle, &radius)| Vec2::from_angle(angle) * radius / max * 0.5)
+            .collect()
+    }
+
+    /// The outline is only guaranteed to be star-shaped around the origin, rather than around its
+    /// centroid, so it is triangulated from the origin
+    pub fn mesh(&self, gpu: &Gpu, seed: u64) -> Mesh {
+        MeshBuilder::new()
+            .polygon_around(Vec2::ZERO, &self.generate(seed))
+            .build(gpu)
+    }
+}
+
 #[repr(C)]
 #[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, Default, Debug)]
 struct Object {
@@ -31,39 +130,155 @@ struct Object {
     color: Vec4,
 }
 
+/// The arguments of [`RenderPass::draw_indexed_indirect`]
 #[derive(Copy, Clone, Debug, Default, Pod, Zeroable)]
 #[repr(C)]
 pub struct DrawIndexedIndirect {
     pub index_count: u32,
     pub instance_count: u32,
     pub first_index: u32,
-    pub base_vertex: u32,
+    pub base_vertex: i32,
     pub first_instance: u32,
 }
 
+#[derive(Copy, Clone, Debug, Default, Pod, Zeroable)]
+#[repr(C)]
+struct CullParams {
+    object_count: u32,
+    _padding: [u32; 3],
+}
+
+/// Frustum culls the objects on the gpu, writing the indices of the visible objects and the
+/// arguments of an indirect draw.
+///
+/// The visible objects keep their order, so that overlapping objects are drawn the same way as
+/// without culling.
+struct Culling {
+    /// Numbers the visible objects within each workgroup
+    cull: ComputeShader,
+    /// Sums the visible objects of the previous workgroups, and counts the instances to draw
+    scan: ComputeShader,
+    /// Offsets the numbers by the visible objects of the previous workgroups
+    compact: ComputeShader,
+    layout: BindGroupLayout,
+    params: TypedBuffer<CullParams>,
+    /// The slots of the objects within their workgroup, followed by the count, then offset, of
+    /// each workgroup
+    scratch: TypedBuffer<u32>,
+    visible: TypedBuffer<u32>,
+    draw: TypedBuffer<DrawIndexedIndirect>,
+    /// Rebuilt when the object or culling buffers grow
+    bind_group: Option<(u64, BindGroup)>,
+}
+
+impl Culling {
+    fn new(gpu: &Gpu, library: &ShaderLibrary) -> anyhow::Result<Self> {
+        let source = library.compose("cull", &ShaderDefines::new())?;
+
+        let layout = source
+            .reflect()?
+            .bind_group_layout("cull_bind_group_layout", 0)
+            .build(gpu);
+
+        let cull = ComputeShader::new(
+            gpu,
+            ComputeShaderDesc::new("cull", &source)
+                .with_entry_point("cs_cull")
+                .with_layouts(&[&layout]),
+        )?;
+
+        let scan = ComputeShader::new(
+            gpu,
+            ComputeShaderDesc::new("scan", &source)
+                .with_entry_point("cs_scan")
+                .with_layouts(&[&layout]),
+        )?;
+
+        let compact = ComputeShader::new(
+            gpu,
+            ComputeShaderDesc::new("compact", &source)
+                .with_entry_point("cs_compact")
+                .with_layouts(&[&layout]),
+        )?;
+
+        let group_count = CHUNK_SIZE.div_ceil(cull.workgroup_size()[0] as usize);
+
+        Ok(Self {
+            cull,
+            scan,
+            compact,
+            layout,
+            params: TypedBuffer::new(
+                gpu,
+                "cull_params",
+                BufferUsages::UNIFORM,
+                &[Zeroable::zeroed()],
+            ),
+            scratch: TypedBuffer::new_uninit(
+                gpu,
+                "cull_scratch_buffer",
+                BufferUsages::STORAGE,
+                CHUNK_SIZE + group_count,
+            ),
+            visible: TypedBuffer::new_uninit(
+                gpu,
+                "visible_buffer",
+                BufferUsages::STORAGE,
+                CHUNK_SIZE,
+            ),
+            draw: TypedBuffer::new(
+                gpu,
+                "indirect_buffer",
+                BufferUsages::STORAGE | BufferUsages::INDIRECT,
+                &[Zeroable::zeroed()],
+            ),
+            bind_group: None,
+        })
+    }
+}
+
+/// Where the per-object data is uploaded to
+enum ObjectBuffers {
+    /// All objects are drawn with a single instanced call
+    Storage {
+        buffer: TypedBuffer<Object>,
+        /// Rebuilt when the buffer grows
+        bind_group: Option<(u64, BindGroup)>,
+        /// Used where compute shaders and indirect draws are supported
+        culling: Option<Box<Culling>>,
+    },
+    /// Uniform buffers have a strict byte size limit, so the objects are split into chunks which
+    /// are drawn separately.
+    ///
+    /// Used where vertex shaders can not read storage buffers, such as WebGL2.
+    Chunked {
+        buffers: Vec<TypedBuffer<Object>>,
+        bind_groups: Vec<BindGroup>,
+    },
+}
+
 pub struct Game {
     asteroids: Vec<Asteroid>,
     object_data: Vec<Object>,
     gpu: Arc<Gpu>,
+    shader_library: ShaderLibrary,
     shader: Shader,
-    square: Mesh,
+    asteroid_mesh: Mesh,
+    camera: CameraController,
     camera_buffer: TypedBuffer<Camera>,
     asteroid_texture: TextureView,
-    indirect_buffer: TypedBuffer<DrawIndexedIndirect>,
 
-    object_buffers: Vec<TypedBuffer<Object>>,
-    asteroid_bind_groups: Vec<BindGroup>,
+    objects: ObjectBuffers,
 
-    sampler: wgpu::Sampler,
+    sampler: Arc<wgpu::Sampler>,
 
     orbit_time: f32,
-    asteroid_bind_group_layout: wgpu::BindGroupLayout,
+    asteroid_bind_group_layout: BindGroupLayout,
+    rng: Pcg32,
 }
 
 impl Game {
     pub async fn new(gpu: Arc<Gpu>) -> anyhow::Result<Self> {
-        let square = Mesh::square(&gpu);
-
         tracing::info!("Downloading asteroid texture");
 
         let image = reqwest::Client::builder()
@@ -76,83 +291,154 @@ impl Game {
 
         tracing::info!("Downloaded asteroid texture");
 
-        let image = image::load_from_memory(&image).unwrap();
+        let image = image::load_from_memory(&image)?;
 
-        let asteroid_texture = Texture::from_image(&gpu, image).create_view(&Default::default());
+        Self::from_image(gpu, image, rand::random())
+    }
 
-        let sampler = gpu.device.create_sampler(&wgpu::SamplerDescriptor {
-            ..Default::default()
-        });
+    /// Creates a game using the given asteroid texture.
+    ///
+    /// All randomness is derived from `seed`, which makes the simulation deterministic.
+    pub fn from_image(gpu: Arc<Gpu>, image: DynamicImage, seed: u64) -> anyhow::Result<Self> {
+        let asteroid_mesh = AsteroidShape::default().mesh(&gpu, seed);
 
-        let size = 10.0;
-        let window_size = gpu.window().inner_size();
+        // Mips keep the asteroids from aliasing when zoomed out
+        let asteroid_texture = Texture::from_image(&gpu, image).view();
 
-        let aspect = window_size.width as f32 / window_size.height as f32;
+        let sampler = gpu.sampler(&SamplerDesc::linear());
 
-        let bounds = vec2(size * aspect, size);
+        let scale_factor = gpu.window().map_or(1.0, |v| v.scale_factor());
 
-        tracing::info!(?bounds, "bounds");
-        let camera = Camera::new(
-            Mat4::from_rotation_translation(Quat::IDENTITY, vec3(0.0, 0.0, 1.0)),
-            Mat4::orthographic_lh(-bounds.x, bounds.x, -bounds.y, bounds.y, 0.1, 1000.0),
-        );
+        // Keeps the outermost orbits in view
+        let camera = CameraController::new(gpu.size(), scale_factor)
+            .with_extent(10.0)
+            .with_zoom_limits(2.0, 25.0)
+            .with_bounds(Vec2::splat(-25.0), Vec2::splat(25.0));
 
         let camera_buffer = TypedBuffer::new(
             &gpu,
             "camera_buffer",
             BufferUsages::UNIFORM | BufferUsages::COPY_DST,
-            &[camera],
+            &[camera.camera()],
         );
 
-        let object_data = vec![Default::default(); 1024];
-
-        let indirect_buffer = TypedBuffer::new(
-            &gpu,
-            "indirect_buffer",
-            BufferUsages::INDIRECT | BufferUsages::COPY_DST,
-            &[DrawIndexedIndirect::zeroed(); 16],
-        );
-
-        let asteroid_bind_group_layout = BindGroupLayoutBuilder::new("asteroid_bind_group_layout")
-            .bind_uniform_buffer(ShaderStages::VERTEX)
-            .bind_uniform_buffer(ShaderStages::VERTEX)
-            .bind_texture(ShaderStages::FRAGMENT)
-            .bind_sampler(ShaderStages::FRAGMENT)
+        let object_data = Vec::new();
+
+        let shader_library = shader_library();
+
+        let (defines, objects) = if gpu.supports_vertex_storage() {
+            tracing::info!("Using a storage buffer for object data");
+            let mut defines = ShaderDefines::new().with_flag("STORAGE_OBJECTS");
+
+            let culling = if gpu.supports_compute() && gpu.supports_indirect() {
+                tracing::info!("Culling objects on the gpu");
+                defines = defines.with_flag("CULLING");
+                Some(Box::new(Culling::new(&gpu, &shader_library)?))
+            } else {
+                None
+            };
+
+            let objects = ObjectBuffers::Storage {
+                buffer: TypedBuffer::new_uninit(
+                    &gpu,
+                    "object_buffer",
+                    BufferUsages::STORAGE,
+                    CHUNK_SIZE,
+                ),
+                bind_group: None,
+                culling,
+            };
+
+            (defines, objects)
+        } else {
+            tracing::info!("Using chunked uniform buffers for object data");
+            let objects = ObjectBuffers::Chunked {
+                buffers: Vec::new(),
+                bind_groups: Vec::new(),
+            };
+
+            (
+                ShaderDefines::new().with_value("MAX_OBJECTS", CHUNK_SIZE),
+                objects,
+            )
+        };
+
+        let source = shader_library.compose("asteroids", &defines)?;
+
+        let asteroid_bind_group_layout = source
+            .reflect()?
+            .bind_group_layout("asteroid_bind_group_layout", 0)
             .build(&gpu);
 
         let shader = Shader::new(
             &gpu,
-            ShaderDesc {
-                label: "asteroids",
-                source: include_str!("../../assets/shaders.wgsl").into(),
-                format: gpu.surface_format(),
-                vertex_layouts: vec![Vertex::layout()].into(),
-                layouts: &[&asteroid_bind_group_layout],
-            },
-        );
+            ShaderDesc::new("asteroids", &source)
+                .with_target(gpu.hdr_format(), BlendMode::Alpha)
+                .with_vertex_layouts(vec![Vertex::layout()])
+                .with_layouts(&[&asteroid_bind_group_layout]),
+        )?;
 
         Ok(Self {
             asteroids: Vec::new(),
             gpu,
+            shader_library,
             shader,
-            square,
-            asteroid_bind_groups: Vec::new(),
+            asteroid_mesh,
             asteroid_bind_group_layout,
-            object_buffers: Vec::new(),
+            objects,
             object_data,
-            indirect_buffer,
             orbit_time: 0.0,
+            camera,
             camera_buffer,
             asteroid_texture,
             sampler,
+            rng: Pcg32::seed_from_u64(seed),
         })
     }
 
+    /// Recomputes the camera projection for the current size of the gpu
+    pub fn resize(&mut self) {
+        self.camera.resize(self.gpu.size());
+    }
+
+    /// Handles window input, returning true if the event was used
+    pub fn input(&mut self, event: &WindowEvent) -> bool {
+        self.camera.input(event)
+    }
+
+    pub fn camera(&self) -> &CameraController {
+        &self.camera
+    }
+
+    pub fn camera_mut(&mut self) -> &mut CameraController {
+        &mut self.camera
+    }
+
+    /// The asteroids are moved along their orbits by [`Self::update`], and drawn where they are
+    /// when [`Self::prepare`] is called
+    pub fn asteroids_mut(&mut self) -> &mut [Asteroid] {
+        &mut self.asteroids
+    }
+
+    /// Rebuilds the pipelines which depend on shader modules that changed on disk
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn reload_shaders(&mut self) {
+        for module in self.shader_library.reload_modified() {
+            self.gpu
+                .pipeline_cache()
+                .reload(&self.gpu.device, &self.shader_library, &module);
+        }
+
+        self.shader.update();
+    }
+
     pub fn update(&mut self, dt: f32) {
+        self.camera.update(dt);
+
         self.orbit_time += dt;
         let layers = [2, 8, 32, 48, 64, 96];
 
-        let mut rng = Pcg32::from_entropy();
+        let rng = &mut self.rng;
         let total_count: usize = layers.iter().sum();
 
         let orbit_time = self.orbit_time;
@@ -188,20 +474,21 @@ impl Game {
         // self.spawner.update(&mut self.asteroids, dt);
     }
 
-    pub fn render<'a>(&'a mut self, render_pass: &mut RenderPass<'a>) {
-        if self.asteroids.is_empty() {
-            return;
+    /// Uploads the object data and culls the objects on the gpu if supported.
+    ///
+    /// Must be called before [`Self::render`], outside of the render pass.
+    pub fn prepare(&mut self, encoder: &mut CommandEncoder) {
+        if self.camera.take_changed() {
+            self.camera_buffer
+                .write(&self.gpu.queue, &[self.camera.camera()]);
         }
 
-        let mut cmds = [DrawIndexedIndirect::zeroed(); 512];
-        let index_count = self.square.index_count();
-
-        // Update the object data
+        self.object_data
+            .resize(self.asteroids.len(), Default::default());
         self.object_data
             .iter_mut()
             .zip(&self.asteroids)
-            .enumerate()
-            .for_each(|(i, (object, v))| {
+            .for_each(|(object, v)| {
                 object.model = Mat4::from_scale_rotation_translation(
                     Vec3::ONE * 2.0 * v.radius,
                     Quat::from_scaled_axis(Vec3::Z * v.rot),
@@ -209,75 +496,206 @@ impl Game {
                 );
 
                 object.color = Vec4::ONE * v.lifetime.clamp(0.0, 1.0);
+            });
 
-                // let cmd = DrawIndexedIndirect {
-                //     index_count,
-                //     instance_count: 1,
-                //     base_vertex: 0,
-                //     first_index: 0,
-                //     first_instance: i as u32,
-                // };
+        let count = self.object_data.len();
+        if count == 0 {
+            return;
+        }
 
-                // cmds[i] = cmd;
-            });
+        match &mut self.objects {
+            ObjectBuffers::Storage {
+                buffer,
+                bind_group,
+                culling,
+            } => {
+                buffer.write_growing(&self.gpu, 0, &self.object_data);
+
+                let mut generation = buffer.generation();
+
+                if let Some(culling) = culling {
+                    let group_count = count.div_ceil(culling.cull.workgroup_size()[0] as usize);
+                    culling.scratch.reserve(&self.gpu, count + group_count);
+                    culling.visible.reserve(&self.gpu, count);
+                    generation += culling.scratch.generation() + culling.visible.generation();
+
+                    culling.params.write(
+                        &self.gpu.queue,
+                        &[CullParams {
+                            object_count: count as u32,
+                            ..Default::default()
+                        }],
+                    );
+
+                    // The culling pass counts the visible instances
+                    culling.draw.write(
+                        &self.gpu.queue,
+                        &[DrawIndexedIndirect {
+                            index_count: self.asteroid_mesh.index_count(),
+                            ..Default::default()
+                        }],
+                    );
+
+                    if culling.bind_group.as_ref().map(|v| v.0) != Some(generation) {
+                        let new_bind_group = BindGroupBuilder::new("cull_bind_group")
+                            .bind_buffer(&self.camera_buffer)
+                            .bind_buffer(buffer)
+                            .bind_buffer(&culling.params)
+                            .bind_buffer(&culling.scratch)
+                            .bind_buffer(&culling.visible)
+                            .bind_buffer(&culling.draw)
+                            .build(&self.gpu, &culling.layout);
+
+                        culling.bind_group = Some((generation, new_bind_group));
+                    }
+
+                    let (_, cull_bind_group) = culling.bind_group.as_ref().unwrap();
+                    culling
+                        .cull
+                        .record(encoder, &[cull_bind_group], count as u32);
+                    // A single workgroup scans the counts of all the others
+                    culling.scan.record(encoder, &[cull_bind_group], 1);
+                    culling
+                        .compact
+                        .record(encoder, &[cull_bind_group], count as u32);
+                }
+
+                if bind_group.as_ref().map(|v| v.0) != Some(generation) {
+                    let mut builder = BindGroupBuilder::new("asteroid_bind_group");
+                    builder
+                        .bind_buffer(&self.camera_buffer)
+                        .bind_buffer(buffer)
+                        .bind_texture(&self.asteroid_texture)
+                        .bind_sampler(&self.sampler);
+
+                    if let Some(culling) = culling {
+                        builder.bind_buffer(&culling.visible);
+                    }
+
+                    *bind_group = Some((
+                        generation,
+                        builder.build(&self.gpu, &self.asteroid_bind_group_layout),
+                    ));
+                }
+            }
+            ObjectBuffers::Chunked {
+                buffers,
+                bind_groups,
+            } => {
+                for i in buffers.len()..count.div_ceil(CHUNK_SIZE) {
+                    tracing::info!("creating buffer {i}");
+
+                    let buffer = TypedBuffer::new_uninit(
+                        &self.gpu,
+                        "object_buffer",
+                        BufferUsages::UNIFORM,
+                        CHUNK_SIZE,
+                    );
+
+                    let bind_group = BindGroupBuilder::new("asteroid_bind_group")
+                        .bind_buffer(&self.camera_buffer)
+                        .bind_buffer(&buffer)
+                        .bind_texture(&self.asteroid_texture)
+                        .bind_sampler(&self.sampler)
+                        .build(&self.gpu, &self.asteroid_bind_group_layout);
+
+                    buffers.push(buffer);
+                    bind_groups.push(bind_group);
+                }
+
+                // Each chunk is written to its own buffer, as the writes are only enqueued and
+                // all happen before the draw calls execute
+                for (buffer, chunk) in buffers.iter().zip(self.object_data.chunks(CHUNK_SIZE)) {
+                    buffer.write(&self.gpu.queue, chunk);
+                }
+            }
+        }
+    }
 
-        const CHUNK_SIZE: usize = 128;
-        for i in self.object_buffers.len()..(self.asteroids.len().div_ceil(CHUNK_SIZE)) {
-            tracing::info!("creating buffer {i}");
-            // Uniform buffers have a strict byte size limit
-            let object_data = vec![Default::default(); CHUNK_SIZE];
-
-            let object_buffer = TypedBuffer::new(
-                &self.gpu,
-                "object_buffer",
-                BufferUsages::UNIFORM | BufferUsages::COPY_DST,
-                &object_data,
-            );
-
-            let asteroid_bind_group = BindGroupBuilder::new("asteroid_bind_group")
-                .bind_buffer(&self.camera_buffer)
-                .bind_buffer(&object_buffer)
-                .bind_texture(&self.asteroid_texture)
-                .bind_sampler(&self.sampler)
-                .build(&self.gpu, &self.asteroid_bind_group_layout);
-
-            self.object_buffers.push(object_buffer);
-            self.asteroid_bind_groups.push(asteroid_bind_group);
+    pub fn render<'a>(&'a mut self, render_pass: &mut RenderPass<'a>) {
+        self.shader.update();
+
+        let Some(pipeline) = self.shader.pipeline() else {
+            return;
+        };
+
+        let count = self.object_data.len();
+        if count == 0 {
+            return;
         }
 
-        // The uniform buffer is limited in size, so we need to split the data into chunks and
-        // separately write them to the buffer and issue draw calls
-        for (i, chunk) in self.asteroids.chunks(CHUNK_SIZE).enumerate() {
-            // The buffer is enqueued for writing to the GPU
-            //
-            // As such, we need a ring of them
-            let chunk_start = i * CHUNK_SIZE;
-            self.object_buffers[i].write(
-                &self.gpu.queue,
-                &self.object_data[chunk_start..chunk_start + chunk.len()],
-            );
-            // self.indirect_buffer.write(&self.gpu.queue, &cmds.);
-
-            render_pass.set_pipeline(self.shader.pipeline());
-
-            render_pass.set_bind_group(0, &self.asteroid_bind_groups[i], &[]);
-
-            render_pass.set_vertex_buffer(0, self.square.vertex_buffer.slice(..));
-
-            render_pass.set_index_buffer(self.square.index_buffer.slice(..), IndexFormat::Uint32);
-
-            // for i in 0..cmds.len() {
-            // render_pass.draw_indexed_indirect(
-            //     &self.indirect_buffer,
-            //     i as u64 * std::mem::size_of::<DrawIndexedIndirect>() as u64,
-            // );
-            // }
-
-            render_pass.draw_indexed(
-                0..index_count,
-                0,
-                chunk_start as u32..(chunk_start + chunk.len()) as u32,
-            );
+        let index_count = self.asteroid_mesh.index_count();
+
+        render_pass.set_pipeline(pipeline);
+        self.asteroid_mesh.bind(render_pass);
+
+        match &self.objects {
+            ObjectBuffers::Storage {
+                bind_group,
+                culling,
+                ..
+            } => {
+                let (_, bind_group) = bind_group.as_ref().expect("Objects were not prepared");
+                render_pass.set_bind_group(0, bind_group, &[]);
+
+                match culling {
+                    Some(culling) => render_pass.draw_indexed_indirect(&culling.draw, 0),
+                    None => render_pass.draw_indexed(0..index_count, 0, 0..count as u32),
+                }
+            }
+            ObjectBuffers::Chunked { bind_groups, .. } => {
+                for (i, bind_group) in bind_groups
+                    .iter()
+                    .take(count.div_ceil(CHUNK_SIZE))
+                    .enumerate()
+                {
+                    render_pass.set_bind_group(0, bind_group, &[]);
+
+                    // The instance index is used to index into the chunk
+                    let len = CHUNK_SIZE.min(count - i * CHUNK_SIZE);
+                    render_pass.draw_indexed(0..index_count, 0, 0..len as u32);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn asteroid_shape() {
+        let shape = AsteroidShape::default();
+        let outline = shape.generate(7);
+
+        assert_eq!(outline, shape.generate(7));
+        assert_ne!(outline, shape.generate(8));
+        assert_eq!(outline.len(), shape.vertex_count as usize);
+
+        let radii = outline.iter().map(|v| v.length()).collect::<Vec<_>>();
+        assert!(radii
+            .iter()
+            .all(|&v| v > 0.5 * (1.0 - shape.roughness) - 1e-5));
+        assert!((radii.iter().copied().fold(0.0, f32::max) - 0.5).abs() < 1e-5);
+
+        // Every edge turns counter-clockwise around the origin
+        let n = outline.len();
+        assert!((0..n).all(|i| outline[i].perp_dot(outline[(i + 1) % n]) > 0.0));
+
+        // Which still holds for the most irregular and rough shapes, including triangles, whose
+        // gaps between vertices are closest to half a turn
+        for vertex_count in [3, 4, 7] {
+            let shape = AsteroidShape {
+                vertex_count,
+                irregularity: 1.0,
+                roughness: 1.0,
+            };
+            for seed in 0..256 {
+                let outline = shape.generate(seed);
+                let n = outline.len();
+                assert!((0..n).all(|i| outline[i].perp_dot(outline[(i + 1) % n]) > 0.0));
+            }
         }
     }
 }
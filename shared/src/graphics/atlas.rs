@@ -0,0 +1,332 @@
+//! Packs many small images into the layers of a single array texture, so that they can be drawn
+//! with one bind group.
+use glam::{vec2, Vec2};
+use image::{DynamicImage, RgbaImage};
+use wgpu::{TextureUsages, TextureView};
+
+use super::{ColorSpace, Gpu, Texture, TextureDesc};
+
+#[derive(Debug, thiserror::Error)]
+pub enum AtlasError {
+    #[error("Image of size {width}x{height} does not fit in an atlas page of size {page_size} with a padding of {padding}")]
+    TooLarge {
+        width: u32,
+        height: u32,
+        page_size: u32,
+        padding: u32,
+    },
+    #[error("All {pages} atlas pages are full")]
+    Full { pages: u32 },
+}
+
+/// Describes a [`TextureAtlas`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtlasDesc {
+    /// The width and height of each page
+    pub page_size: u32,
+    /// Pages are the layers of an array texture, which is allocated up front
+    pub max_pages: u32,
+    /// The edges of each sprite are repeated into this many pixels around it, which keeps
+    /// neighbouring sprites from bleeding in when sampled with linear filtering
+    pub padding: u32,
+    pub color_space: ColorSpace,
+}
+
+impl Default for AtlasDesc {
+    fn default() -> Self {
+        Self {
+            page_size: 1024,
+            max_pages: 4,
+            padding: 2,
+            color_space: ColorSpace::Srgb,
+        }
+    }
+}
+
+/// Identifies a sprite added to a [`TextureAtlas`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SpriteId(u32);
+
+/// The normalized texture coordinates of a sprite within its page
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UvRect {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl UvRect {
+    pub fn size(&self) -> Vec2 {
+        self.max - self.min
+    }
+}
+
+/// The placement of a sprite in the atlas
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AtlasSprite {
+    /// The array layer of the page
+    pub page: u32,
+    /// The pixel position within the page, excluding the padding
+    pub origin: [u32; 2],
+    pub size: [u32; 2],
+    pub uv: UvRect,
+}
+
+/// A shelf of the packer, which holds rectangles of at most its height side by side
+#[derive(Debug, Clone)]
+struct Shelf {
+    y: u32,
+    height: u32,
+    /// The start of the free space
+    x: u32,
+}
+
+/// Allocates rectangles in rows of shelves.
+///
+/// Allocations are never moved, so rectangles can be added without repacking.
+#[derive(Debug, Clone)]
+struct ShelfPacker {
+    size: u32,
+    shelves: Vec<Shelf>,
+}
+
+impl ShelfPacker {
+    fn new(size: u32) -> Self {
+        Self {
+            size,
+            shelves: Vec::new(),
+        }
+    }
+
+    /// Returns the top left corner of the allocated rectangle
+    fn allocate(&mut self, width: u32, height: u32) -> Option<[u32; 2]> {
+        if width > self.size || height > self.size {
+            return None;
+        }
+
+        // Use the shelf which wastes the least height
+        let size = self.size;
+        let best = self
+            .shelves
+            .iter_mut()
+            .filter(|v| v.height >= height && size - v.x >= width)
+            .min_by_key(|v| v.height - height);
+
+        if let Some(shelf) = best {
+            let origin = [shelf.x, shelf.y];
+            shelf.x += width;
+            return Some(origin);
+        }
+
+        let y = self.shelves.last().map_or(0, |v| v.y + v.height);
+        if self.size - y < height {
+            return None;
+        }
+
+        self.shelves.push(Shelf {
+            y,
+            height,
+            x: width,
+        });
+
+        Some([0, y])
+    }
+}
+
+/// Packs images into the pages of an array texture.
+///
+/// Sprites can be added at any time, and are placed in the first page with enough space. The
+/// atlas has no mips, as minifying would blend neighbouring sprites despite the padding.
+pub struct TextureAtlas {
+    desc: AtlasDesc,
+    texture: Texture,
+    view: TextureView,
+    pages: Vec<ShelfPacker>,
+    sprites: Vec<AtlasSprite>,
+}
+
+impl TextureAtlas {
+    pub fn new(gpu: &Gpu, desc: AtlasDesc) -> Self {
+        // Textures with a single layer are not array textures on GL
+        let layers = desc.max_pages.max(2);
+
+        let texture = Texture::from_desc(
+            gpu,
+            &TextureDesc::new(
+                desc.page_size,
+                desc.page_size,
+                desc.color_space.rgba8(),
+                // Copying from the pages allows inspecting them
+                TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST | TextureUsages::COPY_SRC,
+            )
+            .with_label("TextureAtlas")
+            .with_layers(layers),
+        );
+
+        let view = texture.view();
+
+        Self {
+            desc,
+            texture,
+            view,
+            pages: Vec::new(),
+            sprites: Vec::new(),
+        }
+    }
+
+    /// Packs `image` into the atlas and uploads it
+    pub fn add(&mut self, gpu: &Gpu, image: &DynamicImage) -> Result<SpriteId, AtlasError> {
+        let AtlasDesc {
+            page_size,
+            max_pages,
+            padding,
+            ..
+        } = self.desc;
+
+        let (width, height) = (image.width(), image.height());
+        let padded = [width + 2 * padding, height + 2 * padding];
+
+        if padded[0] > page_size || padded[1] > page_size {
+            return Err(AtlasError::TooLarge {
+                width,
+                height,
+                page_size,
+                padding,
+            });
+        }
+
+        let allocated = self
+            .pages
+            .iter_mut()
+            .enumerate()
+            .find_map(|(page, packer)| Some((page, packer.allocate(padded[0], padded[1])?)));
+
+        let (page, corner) = match allocated {
+            Some(v) => v,
+            None if (self.pages.len() as u32) < max_pages => {
+                let mut packer = ShelfPacker::new(page_size);
+                let corner = packer
+                    .allocate(padded[0], padded[1])
+                    .expect("Image fits in an empty page");
+
+                self.pages.push(packer);
+                (self.pages.len() - 1, corner)
+            }
+            None => return Err(AtlasError::Full { pages: max_pages }),
+        };
+
+        let image = extrude(&image.to_rgba8(), padding);
+        self.texture
+            .write_region(gpu, page as u32, corner, padded, &image);
+
+        let origin = [corner[0] + padding, corner[1] + padding];
+        let min = vec2(origin[0] as f32, origin[1] as f32) / page_size as f32;
+        let max = min + vec2(width as f32, height as f32) / page_size as f32;
+
+        let id = SpriteId(self.sprites.len() as u32);
+        self.sprites.push(AtlasSprite {
+            page: page as u32,
+            origin,
+            size: [width, height],
+            uv: UvRect { min, max },
+        });
+
+        Ok(id)
+    }
+
+    pub fn get(&self, id: SpriteId) -> &AtlasSprite {
+        &self.sprites[id.0 as usize]
+    }
+
+    /// A view of all pages, to be bound as `texture_2d_array<f32>`
+    pub fn view(&self) -> &TextureView {
+        &self.view
+    }
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    /// The number of pages which contain sprites
+    pub fn page_count(&self) -> u32 {
+        self.pages.len() as u32
+    }
+
+    pub fn len(&self) -> usize {
+        self.sprites.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sprites.is_empty()
+    }
+}
+
+/// Surrounds the image by `padding` pixels repeating its edges
+fn extrude(image: &RgbaImage, padding: u32) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return RgbaImage::new(width + 2 * padding, height + 2 * padding);
+    }
+
+    RgbaImage::from_fn(width + 2 * padding, height + 2 * padding, |x, y| {
+        let x = x.saturating_sub(padding).min(width - 1);
+        let y = y.saturating_sub(padding).min(height - 1);
+        *image.get_pixel(x, y)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn overlaps(a: ([u32; 2], [u32; 2]), b: ([u32; 2], [u32; 2])) -> bool {
+        (0..2).all(|i| a.0[i] < b.0[i] + b.1[i] && b.0[i] < a.0[i] + a.1[i])
+    }
+
+    #[test]
+    fn pack() {
+        let mut packer = ShelfPacker::new(64);
+
+        let sizes = [
+            [16, 16],
+            [8, 16],
+            [40, 8],
+            [16, 16],
+            [30, 20],
+            [64, 10],
+            [10, 10],
+        ];
+        let mut rects = Vec::new();
+
+        for size in sizes {
+            let origin = packer.allocate(size[0], size[1]).unwrap();
+            assert!(origin[0] + size[0] <= 64 && origin[1] + size[1] <= 64);
+
+            for &other in &rects {
+                assert!(
+                    !overlaps((origin, size), other),
+                    "{origin:?} {size:?} overlaps {other:?}"
+                );
+            }
+
+            rects.push((origin, size));
+        }
+
+        // The second rectangle shares the first shelf
+        assert_eq!(rects[1].0, [16, 0]);
+
+        assert_eq!(packer.allocate(65, 1), None);
+        assert_eq!(packer.allocate(64, 64), None);
+    }
+
+    #[test]
+    fn extrude_edges() {
+        let image = RgbaImage::from_fn(2, 1, |x, _| image::Rgba([x as u8, 0, 0, 255]));
+        let extruded = extrude(&image, 2);
+
+        assert_eq!(extruded.dimensions(), (6, 5));
+        let row = (0..6)
+            .map(|x| extruded.get_pixel(x, 4)[0])
+            .collect::<Vec<_>>();
+        assert_eq!(row, [0, 0, 0, 1, 1, 1]);
+    }
+}
@@ -1,13 +1,33 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, ops::Deref};
 
 use wgpu::{
-    BindGroup, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
-    BindingResource, BindingType, Buffer, BufferBindingType, Sampler, SamplerBindingType,
-    ShaderStages, TextureView,
+    BindGroup, BindGroupEntry, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource,
+    BindingType, Buffer, BufferBindingType, Sampler, SamplerBindingType, ShaderStages, TextureView,
 };
 
 use super::Gpu;
 
+/// A bind group layout which remembers its entries, so that it can be checked against shaders
+#[derive(Debug)]
+pub struct BindGroupLayout {
+    layout: wgpu::BindGroupLayout,
+    entries: Vec<BindGroupLayoutEntry>,
+}
+
+impl BindGroupLayout {
+    pub fn entries(&self) -> &[BindGroupLayoutEntry] {
+        &self.entries
+    }
+}
+
+impl Deref for BindGroupLayout {
+    type Target = wgpu::BindGroupLayout;
+
+    fn deref(&self) -> &Self::Target {
+        &self.layout
+    }
+}
+
 /// Incrementally construct a bind group layout
 #[derive(Debug, Clone)]
 pub struct BindGroupLayoutBuilder {
@@ -23,6 +43,16 @@ impl BindGroupLayoutBuilder {
         }
     }
 
+    /// Adds an entry with an explicit binding index
+    pub fn push(&mut self, entry: BindGroupLayoutEntry) -> &mut Self {
+        self.entries.push(entry);
+        self
+    }
+
+    pub fn entries(&self) -> &[BindGroupLayoutEntry] {
+        &self.entries
+    }
+
     pub fn bind(&mut self, visibility: ShaderStages, ty: BindingType) -> &mut Self {
         let binding = self.entries.len() as u32;
 
@@ -76,11 +106,17 @@ impl BindGroupLayoutBuilder {
     }
 
     pub fn build(&self, gpu: &Gpu) -> BindGroupLayout {
-        gpu.device
+        let layout = gpu
+            .device
             .create_bind_group_layout(&BindGroupLayoutDescriptor {
                 label: Some(self.label.as_ref()),
                 entries: &self.entries,
-            })
+            });
+
+        BindGroupLayout {
+            layout,
+            entries: self.entries.clone(),
+        }
     }
 }
 
@@ -120,7 +156,7 @@ impl<'a> BindGroupBuilder<'a> {
         self.bind(buffer.as_entire_binding())
     }
 
-    pub fn build(&self, gpu: &Gpu, layout: &BindGroupLayout) -> BindGroup {
+    pub fn build(&self, gpu: &Gpu, layout: &wgpu::BindGroupLayout) -> BindGroup {
         gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some(self.label),
             layout: &layout,
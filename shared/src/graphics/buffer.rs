@@ -1,6 +1,8 @@
-use std::marker::PhantomData;
+use std::{marker::PhantomData, ops::Range};
 
+use anyhow::Context;
 use bytemuck::Pod;
+use futures::channel::oneshot;
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
     Buffer, BufferUsages, Queue,
@@ -8,9 +10,16 @@ use wgpu::{
 
 use super::Gpu;
 
+/// A buffer of `T`s.
+///
+/// The buffer can always be copied from and to, which is used to grow it and to read it back.
+/// Copies require the copied byte ranges to be multiples of 4.
 pub struct TypedBuffer<T> {
     buffer: Buffer,
+    label: String,
+    usage: BufferUsages,
     len: usize,
+    generation: u64,
     _marker: PhantomData<T>,
 }
 
@@ -27,6 +36,7 @@ where
     T: Pod,
 {
     pub fn new(gpu: &Gpu, label: &str, usage: BufferUsages, data: &[T]) -> Self {
+        let usage = usage | BufferUsages::COPY_SRC | BufferUsages::COPY_DST;
         let buffer = gpu.device.create_buffer_init(&BufferInitDescriptor {
             label: Some(label),
             contents: bytemuck::cast_slice(data),
@@ -35,17 +45,173 @@ where
 
         Self {
             buffer,
+            label: label.into(),
+            usage,
             len: data.len(),
+            generation: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Creates a zeroed buffer of `len` elements
+    pub fn new_uninit(gpu: &Gpu, label: &str, usage: BufferUsages, len: usize) -> Self {
+        let usage = usage | BufferUsages::COPY_SRC | BufferUsages::COPY_DST;
+
+        Self {
+            buffer: Self::create_buffer(gpu, label, usage, len),
+            label: label.into(),
+            usage,
+            len,
+            generation: 0,
             _marker: PhantomData,
         }
     }
 
+    fn create_buffer(gpu: &Gpu, label: &str, usage: BufferUsages, len: usize) -> Buffer {
+        gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: Self::byte_size(len),
+            usage,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn byte_size(len: usize) -> u64 {
+        (len * std::mem::size_of::<T>()) as u64
+    }
+
     pub fn len(&self) -> usize {
         self.len
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Incremented each time the buffer is reallocated.
+    ///
+    /// Bind groups referencing the buffer need to be rebuilt when this changes.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
     pub fn write(&self, queue: &Queue, data: &[T]) {
-        assert!(data.len() <= self.len);
-        queue.write_buffer(self, 0, bytemuck::cast_slice(data));
+        self.write_at(queue, 0, data)
+    }
+
+    /// Writes `data` starting at the element `offset`
+    pub fn write_at(&self, queue: &Queue, offset: usize, data: &[T]) {
+        assert!(
+            offset + data.len() <= self.len,
+            "Write of {}..{} is out of bounds for buffer {:?} of length {}",
+            offset,
+            offset + data.len(),
+            self.label,
+            self.len
+        );
+
+        queue.write_buffer(self, Self::byte_size(offset), bytemuck::cast_slice(data));
+    }
+
+    /// Grows the buffer to hold at least `len` elements, keeping the current contents.
+    ///
+    /// The capacity is doubled to amortize reallocations. Returns true if the buffer was
+    /// reallocated, in which case dependent bind groups need to be rebuilt.
+    pub fn reserve(&mut self, gpu: &Gpu, len: usize) -> bool {
+        if len <= self.len {
+            return false;
+        }
+
+        let new_len = len.max(self.len * 2);
+        tracing::debug!(
+            label = self.label,
+            old_len = self.len,
+            new_len,
+            "Growing buffer"
+        );
+
+        let buffer = Self::create_buffer(gpu, &self.label, self.usage, new_len);
+
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("TypedBuffer.reserve"),
+            });
+
+        encoder.copy_buffer_to_buffer(&self.buffer, 0, &buffer, 0, Self::byte_size(self.len));
+        gpu.queue.submit([encoder.finish()]);
+
+        self.buffer = buffer;
+        self.len = new_len;
+        self.generation += 1;
+        true
+    }
+
+    /// Writes `data` at `offset`, growing the buffer if necessary.
+    ///
+    /// Returns true if the buffer was reallocated.
+    pub fn write_growing(&mut self, gpu: &Gpu, offset: usize, data: &[T]) -> bool {
+        let reallocated = self.reserve(gpu, offset + data.len());
+        self.write_at(&gpu.queue, offset, data);
+        reallocated
+    }
+
+    /// Reads back the elements in `range`.
+    ///
+    /// Pending writes are submitted before reading.
+    pub async fn read(&self, gpu: &Gpu, range: Range<usize>) -> anyhow::Result<Vec<T>> {
+        anyhow::ensure!(
+            range.start <= range.end && range.end <= self.len,
+            "Read of {range:?} is out of bounds for buffer {:?} of length {}",
+            self.label,
+            self.len
+        );
+
+        let size = Self::byte_size(range.len());
+        if size == 0 {
+            return Ok(Vec::new());
+        }
+
+        let staging = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("TypedBuffer.staging"),
+            size,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("TypedBuffer.read"),
+            });
+
+        encoder.copy_buffer_to_buffer(
+            &self.buffer,
+            Self::byte_size(range.start),
+            &staging,
+            0,
+            size,
+        );
+        gpu.queue.submit([encoder.finish()]);
+
+        let slice = staging.slice(..);
+        let (tx, rx) = oneshot::channel();
+        slice.map_async(wgpu::MapMode::Read, move |res| {
+            tx.send(res).ok();
+        });
+
+        gpu.device.poll(wgpu::Maintain::Wait);
+        rx.await.context("Readback was cancelled")??;
+
+        // The mapped range is not necessarily aligned for `T`
+        let mut result = vec![T::zeroed(); range.len()];
+        bytemuck::cast_slice_mut(&mut result).copy_from_slice(&slice.get_mapped_range());
+        staging.unmap();
+
+        Ok(result)
+    }
+
+    pub async fn read_all(&self, gpu: &Gpu) -> anyhow::Result<Vec<T>> {
+        self.read(gpu, 0..self.len).await
     }
 }
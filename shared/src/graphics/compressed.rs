@@ -0,0 +1,672 @@
+//! Loading of block compressed textures from KTX2 containers.
+//!
+//! Assets can be shipped in several compressed formats, such as BC for desktop and ETC2 or ASTC
+//! for mobile, of which the first one supported by the adapter is used. BC1 to BC5 can be
+//! decoded on the cpu where no variant is supported.
+use std::io::Read;
+
+use ktx2::{Format, SupercompressionScheme};
+use wgpu::{AstcBlock, AstcChannel, TextureFormat, TextureUsages, TextureViewDimension};
+
+use super::{Gpu, Texture, TextureDesc};
+
+#[derive(Debug, thiserror::Error)]
+pub enum CompressedTextureError {
+    #[error("Invalid KTX2 data: {0}")]
+    Parse(#[from] ktx2::ParseError),
+    #[error("Failed to decompress KTX2 level: {0}")]
+    Decompress(String),
+    #[error("Unsupported supercompression scheme {0:?}")]
+    Supercompression(SupercompressionScheme),
+    #[error("Unsupported KTX2 format {0:?}")]
+    Format(Option<Format>),
+    #[error("3D textures are not supported")]
+    Volume,
+    #[error("{0:?} can not be decoded on the cpu")]
+    Decode(TextureFormat),
+    #[error("{width}x{height} is not a multiple of the block size of {format:?}")]
+    Unaligned {
+        format: TextureFormat,
+        width: u32,
+        height: u32,
+    },
+    #[error("{count} mip levels exceed the {max} of a {width}x{height} image")]
+    LevelCount {
+        count: usize,
+        max: usize,
+        width: u32,
+        height: u32,
+    },
+    #[error("Mip level {level} has {actual} bytes, expected {expected}")]
+    LevelSize {
+        level: usize,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("None of the formats {0:?} are supported by the adapter or can be decoded")]
+    NoSupportedVariant(Vec<TextureFormat>),
+}
+
+/// The contents of a KTX2 container, with any supercompression removed
+#[derive(Debug, Clone)]
+pub struct Ktx2Image {
+    pub format: TextureFormat,
+    pub width: u32,
+    pub height: u32,
+    /// Array layers times faces
+    pub layers: u32,
+    pub dimension: TextureViewDimension,
+    /// The data of each mip level, containing all layers
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl Ktx2Image {
+    /// Parses and validates a container.
+    ///
+    /// Images whose size is not a multiple of the block size are only accepted if they can be
+    /// decoded, as they can not be uploaded as is.
+    pub fn parse(data: &[u8]) -> Result<Self, CompressedTextureError> {
+        let reader = ktx2::Reader::new(data)?;
+        let header = reader.header();
+
+        if header.pixel_depth > 1 {
+            return Err(CompressedTextureError::Volume);
+        }
+
+        let format = header
+            .format
+            .and_then(texture_format)
+            .ok_or(CompressedTextureError::Format(header.format))?;
+
+        let levels = reader
+            .levels()
+            .map(|level| match header.supercompression_scheme {
+                None => Ok(level.to_vec()),
+                Some(SupercompressionScheme::Zstandard) => {
+                    let mut decoded = Vec::new();
+                    ruzstd::StreamingDecoder::new(level)
+                        .map_err(|err| CompressedTextureError::Decompress(err.to_string()))?
+                        .read_to_end(&mut decoded)
+                        .map_err(|err| CompressedTextureError::Decompress(err.to_string()))?;
+
+                    Ok(decoded)
+                }
+                Some(scheme) => Err(CompressedTextureError::Supercompression(scheme)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let arrayed = header.layer_count > 0;
+        let dimension = match (header.face_count, arrayed) {
+            (6, false) => TextureViewDimension::Cube,
+            (6, true) => TextureViewDimension::CubeArray,
+            (_, false) => TextureViewDimension::D2,
+            (_, true) => TextureViewDimension::D2Array,
+        };
+
+        let image = Self {
+            format,
+            width: header.pixel_width.max(1),
+            height: header.pixel_height.max(1),
+            layers: header.layer_count.max(1) * header.face_count,
+            dimension,
+            levels,
+        };
+
+        image.validate_levels()?;
+        if !image.can_decode() {
+            image.validate_alignment()?;
+        }
+
+        Ok(image)
+    }
+
+    /// Returns true if the device can sample the format without decoding
+    pub fn is_supported(&self, gpu: &Gpu) -> bool {
+        self.validate_alignment().is_ok()
+            && gpu
+                .device
+                .features()
+                .contains(self.format.required_features())
+    }
+
+    /// The size in bytes of a single layer of a mip level
+    fn layer_size(&self, level: usize) -> usize {
+        let (block_width, block_height) = self.format.block_dimensions();
+        let block_size = self.format.block_size(None).unwrap_or(0) as usize;
+
+        let width = (self.width >> level).max(1);
+        let height = (self.height >> level).max(1);
+        (width.div_ceil(block_width) * height.div_ceil(block_height)) as usize * block_size
+    }
+
+    /// Checks the number of levels, and that each level holds all layers
+    fn validate_levels(&self) -> Result<(), CompressedTextureError> {
+        let max = (u32::BITS - self.width.max(self.height).leading_zeros()) as usize;
+        if self.levels.is_empty() || self.levels.len() > max {
+            return Err(CompressedTextureError::LevelCount {
+                count: self.levels.len(),
+                max,
+                width: self.width,
+                height: self.height,
+            });
+        }
+
+        for (level, data) in self.levels.iter().enumerate() {
+            let expected = self.layer_size(level) * self.layers as usize;
+            if data.len() != expected {
+                return Err(CompressedTextureError::LevelSize {
+                    level,
+                    expected,
+                    actual: data.len(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Textures need to be a whole number of blocks
+    fn validate_alignment(&self) -> Result<(), CompressedTextureError> {
+        let (block_width, block_height) = self.format.block_dimensions();
+        if !self.width.is_multiple_of(block_width) || !self.height.is_multiple_of(block_height) {
+            return Err(CompressedTextureError::Unaligned {
+                format: self.format,
+                width: self.width,
+                height: self.height,
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn can_decode(&self) -> bool {
+        decoded_format(self.format).is_some()
+    }
+
+    /// Decodes the image to RGBA8 on the cpu
+    pub fn decode(&self) -> Result<Ktx2Image, CompressedTextureError> {
+        let format =
+            decoded_format(self.format).ok_or(CompressedTextureError::Decode(self.format))?;
+        self.validate_levels()?;
+
+        let levels = self
+            .levels
+            .iter()
+            .enumerate()
+            .map(|(level, data)| {
+                let width = (self.width >> level).max(1);
+                let height = (self.height >> level).max(1);
+
+                data.chunks_exact(self.layer_size(level))
+                    .flat_map(|image| bc::decode(self.format, image, width, height))
+                    .collect()
+            })
+            .collect();
+
+        Ok(Self {
+            format,
+            levels,
+            ..self.clone()
+        })
+    }
+}
+
+impl Texture {
+    /// Loads the first of `variants` which the adapter supports, or decodes the first variant
+    /// which can be decoded if none are.
+    ///
+    /// The mips of the container are uploaded as is, so textures without mips are not minified
+    /// smoothly.
+    pub fn from_ktx2(gpu: &Gpu, variants: &[&[u8]]) -> Result<Self, CompressedTextureError> {
+        let images = variants
+            .iter()
+            .map(|v| Ktx2Image::parse(v))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if let Some(image) = images.iter().find(|v| v.is_supported(gpu)) {
+            tracing::debug!(format = ?image.format, "Loading compressed texture");
+            return Self::from_ktx2_image(gpu, image);
+        }
+
+        match images.iter().find(|v| v.can_decode()) {
+            Some(image) => {
+                tracing::info!(format = ?image.format, "Decoding compressed texture on the cpu");
+                Self::from_ktx2_image(gpu, &image.decode()?)
+            }
+            None => Err(CompressedTextureError::NoSupportedVariant(
+                images.iter().map(|v| v.format).collect(),
+            )),
+        }
+    }
+
+    /// Uploads an image in its format, which the adapter needs to support
+    pub fn from_ktx2_image(gpu: &Gpu, image: &Ktx2Image) -> Result<Self, CompressedTextureError> {
+        image.validate_levels()?;
+        image.validate_alignment()?;
+
+        // The mips are uploaded rather than rendered, as compressed formats can not be render
+        // attachments
+        let mut desc = TextureDesc::new(
+            image.width,
+            image.height,
+            image.format,
+            TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        )
+        .with_mip_level_count(image.levels.len() as u32);
+
+        desc.size.depth_or_array_layers = image.layers;
+        desc.dimension = image.dimension;
+
+        let texture = Self::from_desc(gpu, &desc);
+        for (level, data) in image.levels.iter().enumerate() {
+            texture.write_level(gpu, level as u32, data);
+        }
+
+        Ok(texture)
+    }
+}
+
+/// Maps the Vulkan formats of KTX2 to texture formats
+fn texture_format(format: Format) -> Option<TextureFormat> {
+    use TextureFormat as T;
+
+    let format = match format {
+        Format::R8G8B8A8_UNORM => T::Rgba8Unorm,
+        Format::R8G8B8A8_SRGB => T::Rgba8UnormSrgb,
+        // Opaque BC1 decodes the same, except for the rare fourth color of the 3 color mode
+        Format::BC1_RGB_UNORM_BLOCK | Format::BC1_RGBA_UNORM_BLOCK => T::Bc1RgbaUnorm,
+        Format::BC1_RGB_SRGB_BLOCK | Format::BC1_RGBA_SRGB_BLOCK => T::Bc1RgbaUnormSrgb,
+        Format::BC2_UNORM_BLOCK => T::Bc2RgbaUnorm,
+        Format::BC2_SRGB_BLOCK => T::Bc2RgbaUnormSrgb,
+        Format::BC3_UNORM_BLOCK => T::Bc3RgbaUnorm,
+        Format::BC3_SRGB_BLOCK => T::Bc3RgbaUnormSrgb,
+        Format::BC4_UNORM_BLOCK => T::Bc4RUnorm,
+        Format::BC4_SNORM_BLOCK => T::Bc4RSnorm,
+        Format::BC5_UNORM_BLOCK => T::Bc5RgUnorm,
+        Format::BC5_SNORM_BLOCK => T::Bc5RgSnorm,
+        Format::BC6H_UFLOAT_BLOCK => T::Bc6hRgbUfloat,
+        Format::BC6H_SFLOAT_BLOCK => T::Bc6hRgbFloat,
+        Format::BC7_UNORM_BLOCK => T::Bc7RgbaUnorm,
+        Format::BC7_SRGB_BLOCK => T::Bc7RgbaUnormSrgb,
+        Format::ETC2_R8G8B8_UNORM_BLOCK => T::Etc2Rgb8Unorm,
+        Format::ETC2_R8G8B8_SRGB_BLOCK => T::Etc2Rgb8UnormSrgb,
+        Format::ETC2_R8G8B8A1_UNORM_BLOCK => T::Etc2Rgb8A1Unorm,
+        Format::ETC2_R8G8B8A1_SRGB_BLOCK => T::Etc2Rgb8A1UnormSrgb,
+        Format::ETC2_R8G8B8A8_UNORM_BLOCK => T::Etc2Rgba8Unorm,
+        Format::ETC2_R8G8B8A8_SRGB_BLOCK => T::Etc2Rgba8UnormSrgb,
+        Format::EAC_R11_UNORM_BLOCK => T::EacR11Unorm,
+        Format::EAC_R11_SNORM_BLOCK => T::EacR11Snorm,
+        Format::EAC_R11G11_UNORM_BLOCK => T::EacRg11Unorm,
+        Format::EAC_R11G11_SNORM_BLOCK => T::EacRg11Snorm,
+        _ => return astc_format(format),
+    };
+
+    Some(format)
+}
+
+fn astc_format(format: Format) -> Option<TextureFormat> {
+    use AstcBlock::*;
+
+    // The ASTC formats alternate between unorm and sRGB, in order of block size
+    const BLOCKS: [AstcBlock; 14] = [
+        B4x4, B5x4, B5x5, B6x5, B6x6, B8x5, B8x6, B8x8, B10x5, B10x6, B10x8, B10x10, B12x10, B12x12,
+    ];
+
+    let index = format
+        .0
+        .get()
+        .checked_sub(Format::ASTC_4x4_UNORM_BLOCK.0.get())?;
+
+    let block = *BLOCKS.get(index as usize / 2)?;
+    let channel = if index % 2 == 0 {
+        AstcChannel::Unorm
+    } else {
+        AstcChannel::UnormSrgb
+    };
+
+    Some(TextureFormat::Astc { block, channel })
+}
+
+/// Returns the format `format` is decoded to on the cpu
+fn decoded_format(format: TextureFormat) -> Option<TextureFormat> {
+    use TextureFormat as T;
+
+    match format {
+        T::Rgba8Unorm | T::Rgba8UnormSrgb => None,
+        T::Bc1RgbaUnorm | T::Bc2RgbaUnorm | T::Bc3RgbaUnorm | T::Bc4RUnorm | T::Bc5RgUnorm => {
+            Some(T::Rgba8Unorm)
+        }
+        T::Bc1RgbaUnormSrgb | T::Bc2RgbaUnormSrgb | T::Bc3RgbaUnormSrgb => Some(T::Rgba8UnormSrgb),
+        _ => None,
+    }
+}
+
+/// Decoders for the BC1 to BC5 block formats
+mod bc {
+    use wgpu::TextureFormat;
+
+    /// Decodes an image to tightly packed RGBA8
+    pub fn decode(format: TextureFormat, data: &[u8], width: u32, height: u32) -> Vec<u8> {
+        let block_size = format.block_size(None).unwrap() as usize;
+        let blocks_x = width.div_ceil(4) as usize;
+
+        let mut pixels = vec![0; width as usize * height as usize * 4];
+
+        for (i, block) in data.chunks_exact(block_size).enumerate() {
+            let texels = decode_block(format, block);
+
+            let (bx, by) = (i % blocks_x * 4, i / blocks_x * 4);
+            for (j, texel) in texels.iter().enumerate() {
+                let (x, y) = (bx + j % 4, by + j / 4);
+
+                // Blocks at the edges extend past the image
+                if x < width as usize && y < height as usize {
+                    let offset = (y * width as usize + x) * 4;
+                    pixels[offset..offset + 4].copy_from_slice(texel);
+                }
+            }
+        }
+
+        pixels
+    }
+
+    fn decode_block(format: TextureFormat, block: &[u8]) -> [[u8; 4]; 16] {
+        use TextureFormat as T;
+
+        match format {
+            T::Bc1RgbaUnorm | T::Bc1RgbaUnormSrgb => color(block, true),
+            T::Bc2RgbaUnorm | T::Bc2RgbaUnormSrgb => {
+                let mut texels = color(&block[8..], false);
+                let alpha = u64::from_le_bytes(block[..8].try_into().unwrap());
+                for (i, texel) in texels.iter_mut().enumerate() {
+                    texel[3] = ((alpha >> (i * 4)) & 0xf) as u8 * 17;
+                }
+                texels
+            }
+            T::Bc3RgbaUnorm | T::Bc3RgbaUnormSrgb => {
+                let mut texels = color(&block[8..], false);
+                for (texel, alpha) in texels.iter_mut().zip(channel(&block[..8])) {
+                    texel[3] = alpha;
+                }
+                texels
+            }
+            T::Bc4RUnorm => channel(block).map(|r| [r, 0, 0, 255]),
+            T::Bc5RgUnorm => {
+                let (r, g) = (channel(&block[..8]), channel(&block[8..]));
+                std::array::from_fn(|i| [r[i], g[i], 0, 255])
+            }
+            _ => unreachable!("{format:?} can not be decoded"),
+        }
+    }
+
+    fn rgb565(value: u16) -> [u8; 4] {
+        let r = (value >> 11) as u8 & 0x1f;
+        let g = (value >> 5) as u8 & 0x3f;
+        let b = value as u8 & 0x1f;
+        [
+            (r << 3) | (r >> 2),
+            (g << 2) | (g >> 4),
+            (b << 3) | (b >> 2),
+            255,
+        ]
+    }
+
+    /// Decodes the color part of BC1 to BC3.
+    ///
+    /// BC1 uses a mode with 3 colors and transparent black when the endpoints are ordered.
+    fn color(block: &[u8], allow_transparent: bool) -> [[u8; 4]; 16] {
+        let c0 = u16::from_le_bytes([block[0], block[1]]);
+        let c1 = u16::from_le_bytes([block[2], block[3]]);
+        let (a, b) = (rgb565(c0), rgb565(c1));
+
+        let mix = |wa: u32, wb: u32| -> [u8; 4] {
+            std::array::from_fn(|i| ((a[i] as u32 * wa + b[i] as u32 * wb) / (wa + wb)) as u8)
+        };
+
+        let palette = if c0 > c1 || !allow_transparent {
+            [a, b, mix(2, 1), mix(1, 2)]
+        } else {
+            [a, b, mix(1, 1), [0; 4]]
+        };
+
+        let indices = u32::from_le_bytes(block[4..8].try_into().unwrap());
+        std::array::from_fn(|i| palette[(indices >> (i * 2)) as usize & 3])
+    }
+
+    /// Decodes a single channel block, used for the alpha of BC3 and the channels of BC4 and BC5
+    fn channel(block: &[u8]) -> [u8; 16] {
+        let (a0, a1) = (block[0] as u32, block[1] as u32);
+
+        let mut palette = [0u8; 8];
+        palette[0] = a0 as u8;
+        palette[1] = a1 as u8;
+        if a0 > a1 {
+            for k in 0..6 {
+                palette[k + 2] = (((6 - k as u32) * a0 + (1 + k as u32) * a1) / 7) as u8;
+            }
+        } else {
+            for k in 0..4 {
+                palette[k + 2] = (((4 - k as u32) * a0 + (1 + k as u32) * a1) / 5) as u8;
+            }
+            palette[6] = 0;
+            palette[7] = 255;
+        }
+
+        let mut bits = [0u8; 8];
+        bits[..6].copy_from_slice(&block[2..8]);
+        let indices = u64::from_le_bytes(bits);
+
+        std::array::from_fn(|i| palette[(indices >> (i * 3)) as usize & 7])
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        #[test]
+        fn bc1() {
+            // Red and blue endpoints, with the rows using indices 0 to 3
+            let red = 0xf800u16.to_le_bytes();
+            let blue = 0x001fu16.to_le_bytes();
+            let block = [red[0], red[1], blue[0], blue[1], 0x00, 0x55, 0xaa, 0xff];
+
+            let texels = decode_block(TextureFormat::Bc1RgbaUnorm, &block);
+            assert_eq!(texels[0], [255, 0, 0, 255]);
+            assert_eq!(texels[4], [0, 0, 255, 255]);
+            assert_eq!(texels[8], [170, 0, 85, 255]);
+            assert_eq!(texels[12], [85, 0, 170, 255]);
+
+            // Swapped endpoints select the mode with transparency
+            let block = [blue[0], blue[1], red[0], red[1], 0x00, 0x55, 0xaa, 0xff];
+            let texels = decode_block(TextureFormat::Bc1RgbaUnorm, &block);
+            assert_eq!(texels[8], [127, 0, 127, 255]);
+            assert_eq!(texels[12], [0; 4]);
+        }
+
+        #[test]
+        fn bc4() {
+            // Index 1 everywhere selects the second endpoint
+            let block = [
+                200, 100, 0b01001001, 0b10010010, 0b00100100, 0b01001001, 0b10010010, 0b00100100,
+            ];
+            assert_eq!(channel(&block), [100; 16]);
+
+            let block = [200, 100, 0, 0, 0, 0, 0, 0];
+            assert_eq!(
+                decode_block(TextureFormat::Bc4RUnorm, &block)[0],
+                [200, 0, 0, 255]
+            );
+        }
+
+        #[test]
+        fn partial_blocks() {
+            let block = [0xff, 0xff, 0, 0, 0, 0, 0, 0];
+            let pixels = decode(TextureFormat::Bc1RgbaUnorm, &[block, block].concat(), 6, 2);
+
+            assert_eq!(pixels.len(), 6 * 2 * 4);
+            assert!(pixels.chunks(4).all(|v| v == [255, 255, 255, 255]));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Writes a KTX2 container without data format descriptor or key/value data
+    fn ktx2(format: Format, size: u32, scheme: u32, levels: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = b"\xabKTX 20\xbb\r\n\x1a\n".to_vec();
+        for value in [
+            format.0.get(),
+            1,
+            size,
+            size,
+            0,
+            0,
+            1,
+            levels.len() as u32,
+            scheme,
+        ] {
+            data.extend(value.to_le_bytes());
+        }
+        data.extend([0; 32]);
+
+        let mut offset = 80 + 24 * levels.len() as u64;
+        for level in levels {
+            for value in [offset, level.len() as u64, level.len() as u64] {
+                data.extend(value.to_le_bytes());
+            }
+            offset += level.len() as u64;
+        }
+
+        data.extend(levels.concat());
+        data
+    }
+
+    /// Wraps `data` in a zstd frame with a single uncompressed block
+    fn zstd_raw(data: &[u8]) -> Vec<u8> {
+        let size = data.len() as u32;
+        let mut frame = vec![0x28, 0xb5, 0x2f, 0xfd, 0x20, size as u8];
+        frame.extend(&((size << 3) | 1).to_le_bytes()[..3]);
+        frame.extend(data);
+        frame
+    }
+
+    #[test]
+    fn parse() {
+        let white = vec![0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0];
+        let data = ktx2(
+            Format::BC1_RGBA_SRGB_BLOCK,
+            8,
+            0,
+            &[white.repeat(4), white.clone()],
+        );
+
+        let image = Ktx2Image::parse(&data).unwrap();
+        assert_eq!(image.format, TextureFormat::Bc1RgbaUnormSrgb);
+        assert_eq!((image.width, image.height, image.layers), (8, 8, 1));
+        assert_eq!(image.dimension, TextureViewDimension::D2);
+        assert_eq!(image.levels.len(), 2);
+
+        let decoded = image.decode().unwrap();
+        assert_eq!(decoded.format, TextureFormat::Rgba8UnormSrgb);
+        assert_eq!(decoded.levels[0], vec![255; 8 * 8 * 4]);
+        assert_eq!(decoded.levels[1], vec![255; 4 * 4 * 4]);
+    }
+
+    #[test]
+    fn invalid_levels() {
+        let block = vec![0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0];
+
+        let data = ktx2(Format::BC1_RGBA_UNORM_BLOCK, 8, 0, &[block.repeat(3)]);
+        assert!(matches!(
+            Ktx2Image::parse(&data),
+            Err(CompressedTextureError::LevelSize {
+                level: 0,
+                expected: 32,
+                actual: 24
+            })
+        ));
+
+        // An 8x8 image has 4 levels, down to 1x1
+        let levels = [4, 1, 1, 1, 1].map(|count| block.repeat(count));
+        let data = ktx2(Format::BC1_RGBA_UNORM_BLOCK, 8, 0, &levels);
+        assert!(matches!(
+            Ktx2Image::parse(&data),
+            Err(CompressedTextureError::LevelCount {
+                count: 5,
+                max: 4,
+                ..
+            })
+        ));
+
+        // Fewer bytes than layers
+        let image = Ktx2Image {
+            layers: 6,
+            dimension: TextureViewDimension::Cube,
+            levels: vec![vec![0; 4]],
+            ..Ktx2Image::parse(&ktx2(Format::BC1_RGBA_UNORM_BLOCK, 4, 0, &[block])).unwrap()
+        };
+        assert!(matches!(
+            image.decode(),
+            Err(CompressedTextureError::LevelSize { expected: 48, .. })
+        ));
+    }
+
+    #[test]
+    fn unaligned() {
+        // 6x6 BC1 takes 2x2 blocks, and is decoded rather than uploaded
+        let block = [0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0];
+        let data = ktx2(Format::BC1_RGBA_UNORM_BLOCK, 6, 0, &[block.repeat(4)]);
+
+        let image = Ktx2Image::parse(&data).unwrap();
+        assert!(image.validate_alignment().is_err());
+        assert_eq!(image.decode().unwrap().levels[0], vec![255; 6 * 6 * 4]);
+
+        // ASTC can not be decoded
+        let data = ktx2(Format::ASTC_8x8_UNORM_BLOCK, 10, 0, &[vec![0; 4 * 16]]);
+        assert!(matches!(
+            Ktx2Image::parse(&data),
+            Err(CompressedTextureError::Unaligned {
+                width: 10,
+                height: 10,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn zstd() {
+        let level = (0..64).collect::<Vec<u8>>();
+        let data = ktx2(Format::R8G8B8A8_UNORM, 4, 2, &[zstd_raw(&level)]);
+
+        let image = Ktx2Image::parse(&data).unwrap();
+        assert_eq!(image.levels, [level]);
+        assert!(!image.can_decode());
+    }
+
+    #[test]
+    fn formats() {
+        assert_eq!(
+            texture_format(Format::ASTC_6x5_SRGB_BLOCK),
+            Some(TextureFormat::Astc {
+                block: AstcBlock::B6x5,
+                channel: AstcChannel::UnormSrgb
+            })
+        );
+        assert_eq!(
+            texture_format(Format::ASTC_12x12_UNORM_BLOCK),
+            Some(TextureFormat::Astc {
+                block: AstcBlock::B12x12,
+                channel: AstcChannel::Unorm
+            })
+        );
+        assert_eq!(texture_format(Format::R8_UNORM), None);
+
+        let data = ktx2(Format::R8_UNORM, 4, 0, &[vec![0; 16]]);
+        assert!(matches!(
+            Ktx2Image::parse(&data),
+            Err(CompressedTextureError::Format(Some(Format::R8_UNORM)))
+        ));
+    }
+}
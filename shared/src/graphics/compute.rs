@@ -0,0 +1,154 @@
+use wgpu::{BindGroup, CommandEncoder, ComputePass, ComputePipeline, ShaderStages};
+
+use super::{BindGroupLayout, ComposedShader, Gpu, ShaderError};
+
+/// Describes a compute pipeline.
+///
+/// Defaults to the `cs_main` entry point.
+#[derive(Debug, Clone)]
+pub struct ComputeShaderDesc<'a> {
+    pub label: &'a str,
+    pub source: &'a ComposedShader,
+    pub entry_point: &'a str,
+    pub layouts: &'a [&'a BindGroupLayout],
+}
+
+impl<'a> ComputeShaderDesc<'a> {
+    pub fn new(label: &'a str, source: &'a ComposedShader) -> Self {
+        Self {
+            label,
+            source,
+            entry_point: "cs_main",
+            layouts: &[],
+        }
+    }
+
+    pub fn with_entry_point(mut self, entry_point: &'a str) -> Self {
+        self.entry_point = entry_point;
+        self
+    }
+
+    pub fn with_layouts(mut self, layouts: &'a [&'a BindGroupLayout]) -> Self {
+        self.layouts = layouts;
+        self
+    }
+}
+
+/// A compute pipeline, dispatched over a number of elements rather than workgroups
+pub struct ComputeShader {
+    label: String,
+    pipeline: ComputePipeline,
+    workgroup_size: [u32; 3],
+}
+
+impl ComputeShader {
+    /// Creates the pipeline after checking the layouts against the shader's reflection
+    pub fn new(gpu: &Gpu, desc: ComputeShaderDesc) -> Result<Self, ShaderError> {
+        let reflection = desc.source.reflect()?;
+
+        reflection.check_entry_point(desc.entry_point, ShaderStages::COMPUTE)?;
+
+        if desc.layouts.len() < reflection.group_count() as usize {
+            return Err(ShaderError::MissingBindGroups {
+                expected: reflection.group_count(),
+                provided: desc.layouts.len(),
+            });
+        }
+
+        for (group, layout) in desc.layouts.iter().enumerate() {
+            reflection.check_bind_group_layout(group as u32, layout.entries())?;
+        }
+
+        let workgroup_size = reflection
+            .workgroup_size(desc.entry_point)
+            .expect("Entry point was checked");
+
+        let module = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(desc.label),
+                source: wgpu::ShaderSource::Wgsl(desc.source.source().into()),
+            });
+
+        let layouts = desc.layouts.iter().map(|v| &***v).collect::<Vec<_>>();
+
+        let layout = gpu
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(desc.label),
+                bind_group_layouts: &layouts,
+                push_constant_ranges: &[],
+            });
+
+        let pipeline = gpu
+            .device
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(desc.label),
+                layout: Some(&layout),
+                module: &module,
+                entry_point: desc.entry_point,
+            });
+
+        Ok(Self {
+            label: desc.label.into(),
+            pipeline,
+            workgroup_size,
+        })
+    }
+
+    pub fn pipeline(&self) -> &ComputePipeline {
+        &self.pipeline
+    }
+
+    pub fn workgroup_size(&self) -> [u32; 3] {
+        self.workgroup_size
+    }
+
+    /// Returns the number of workgroups needed to cover `elements` in each dimension
+    pub fn workgroup_count(&self, elements: [u32; 3]) -> [u32; 3] {
+        std::array::from_fn(|i| elements[i].div_ceil(self.workgroup_size[i]))
+    }
+
+    /// Dispatches enough workgroups to cover `count` elements along x.
+    ///
+    /// The shader needs to discard invocations past the end, as the last workgroup may be
+    /// partially filled.
+    pub fn dispatch<'a>(
+        &'a self,
+        pass: &mut ComputePass<'a>,
+        bind_groups: &[&'a BindGroup],
+        count: u32,
+    ) {
+        self.dispatch_3d(pass, bind_groups, [count, 1, 1])
+    }
+
+    /// Dispatches enough workgroups to cover `elements` in each dimension
+    pub fn dispatch_3d<'a>(
+        &'a self,
+        pass: &mut ComputePass<'a>,
+        bind_groups: &[&'a BindGroup],
+        elements: [u32; 3],
+    ) {
+        let [x, y, z] = self.workgroup_count(elements);
+        if x == 0 || y == 0 || z == 0 {
+            return;
+        }
+
+        pass.set_pipeline(&self.pipeline);
+        for (i, bind_group) in bind_groups.iter().enumerate() {
+            pass.set_bind_group(i as u32, bind_group, &[]);
+        }
+
+        pass.dispatch_workgroups(x, y, z);
+    }
+
+    /// Records a compute pass dispatching `count` elements, such as in the encoder of
+    /// [`Gpu::render`] or [`Gpu::submit`]
+    pub fn record(&self, encoder: &mut CommandEncoder, bind_groups: &[&BindGroup], count: u32) {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some(&self.label),
+        });
+
+        self.dispatch(&mut pass, bind_groups, count);
+    }
+}
@@ -0,0 +1,100 @@
+use wgpu::{Backends, CompositeAlphaMode, Features, Limits, PowerPreference, PresentMode};
+
+/// Describes what to request from the graphics backend when creating a [`Gpu`](super::Gpu).
+///
+/// Requests which can not be satisfied by the adapter are relaxed rather than failing, and
+/// what was used instead is logged.
+#[derive(Debug, Clone)]
+pub struct GpuConfig {
+    /// Backends to pick an adapter from. Falls back to any backend if none of these are available
+    pub backends: Backends,
+    pub power_preference: PowerPreference,
+    /// Features which are requested if the adapter supports them. Defaults to the texture
+    /// compression formats, of which adapters usually support only some
+    pub features: Features,
+    /// Falls back to the adapter's own limits if they are lower than these
+    pub limits: Limits,
+    /// Falls back to [`PresentMode::Fifo`], which is always supported
+    pub present_mode: PresentMode,
+    /// Falls back to the first alpha mode supported by the surface
+    pub alpha_mode: CompositeAlphaMode,
+    /// The number of samples per pixel of the scene, where 1 disables multisampling. Falls back
+    /// to the highest supported count below it
+    pub sample_count: u32,
+}
+
+impl Default for GpuConfig {
+    fn default() -> Self {
+        Self {
+            backends: Backends::GL,
+            power_preference: PowerPreference::default(),
+            features: Features::TEXTURE_COMPRESSION_BC
+                | Features::TEXTURE_COMPRESSION_ETC2
+                | Features::TEXTURE_COMPRESSION_ASTC,
+            limits: Limits::downlevel_webgl2_defaults(),
+            present_mode: PresentMode::Fifo,
+            alpha_mode: CompositeAlphaMode::Auto,
+            sample_count: 4,
+        }
+    }
+}
+
+impl GpuConfig {
+    /// Prefers the native backends, such as Vulkan, Metal and DX12
+    pub fn native() -> Self {
+        Self {
+            backends: Backends::PRIMARY,
+            limits: Limits::downlevel_defaults(),
+            ..Default::default()
+        }
+    }
+
+    /// Uses WebGL2, which is the only backend the client builds wgpu with.
+    ///
+    /// wgpu 0.17 chooses between WebGPU and WebGL when compiling, so falling back from WebGPU to
+    /// WebGL at runtime is not possible.
+    pub fn web() -> Self {
+        Self {
+            backends: Backends::GL,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_backends(mut self, backends: Backends) -> Self {
+        self.backends = backends;
+        self
+    }
+
+    pub fn with_features(mut self, features: Features) -> Self {
+        self.features = features;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn with_present_mode(mut self, present_mode: PresentMode) -> Self {
+        self.present_mode = present_mode;
+        self
+    }
+
+    pub fn with_sample_count(mut self, sample_count: u32) -> Self {
+        self.sample_count = sample_count;
+        self
+    }
+}
+
+/// Parses a present mode from a user facing setting, such as `vsync`, `mailbox` or `immediate`
+pub fn parse_present_mode(value: &str) -> Option<PresentMode> {
+    match value.to_lowercase().as_str() {
+        "vsync" | "fifo" => Some(PresentMode::Fifo),
+        "fifo_relaxed" => Some(PresentMode::FifoRelaxed),
+        "mailbox" => Some(PresentMode::Mailbox),
+        "immediate" => Some(PresentMode::Immediate),
+        "auto" | "auto_vsync" => Some(PresentMode::AutoVsync),
+        "auto_no_vsync" => Some(PresentMode::AutoNoVsync),
+        _ => None,
+    }
+}
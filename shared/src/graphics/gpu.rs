@@ -1,61 +1,82 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, OnceLock},
+};
+
+use anyhow::Context;
+use parking_lot::Mutex;
 use tracing::info_span;
 use wgpu::{
-    Adapter, CommandEncoder, SurfaceCapabilities, SurfaceConfiguration, TextureFormat, TextureView,
+    Adapter, Backends, CommandEncoder, CompositeAlphaMode, DownlevelFlags, PresentMode,
+    SurfaceConfiguration, TextureFormat, TextureFormatFeatureFlags, TextureUsages, TextureView,
 };
 use winit::{dpi::PhysicalSize, event::WindowEvent, window::Window};
 
+use super::{GpuConfig, MipGenerator, PipelineCache, SamplerDesc};
+
+/// Where the frames rendered by the [`Gpu`] end up
+// There is only ever one per gpu
+#[allow(clippy::large_enum_variant)]
+enum RenderTarget {
+    Surface {
+        surface: wgpu::Surface,
+        /// The configuration used for the surface, excluding the size
+        config: SurfaceConfiguration,
+        window: Window,
+    },
+    /// Renders into a texture which is never presented, used for rendering without a display
+    Offscreen { texture: Mutex<Arc<wgpu::Texture>> },
+}
+
 pub struct Gpu {
-    surface: wgpu::Surface,
+    target: RenderTarget,
     adapter: Adapter,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     surface_format: TextureFormat,
-    surface_caps: SurfaceCapabilities,
-    size: PhysicalSize<u32>,
-    window: Window,
+    sample_count: u32,
+    size: Mutex<PhysicalSize<u32>>,
+    pipeline_cache: PipelineCache,
+    samplers: Mutex<HashMap<SamplerDesc, Arc<wgpu::Sampler>>>,
+    mip_generator: OnceLock<MipGenerator>,
 }
 
 impl Gpu {
     // Creating some of the wgpu types requires async code
     #[tracing::instrument(level = "info")]
-    pub async fn new(window: Window) -> Self {
+    pub async fn new(window: Window, config: &GpuConfig) -> anyhow::Result<Self> {
         let size = window.inner_size();
 
-        // The instance is a handle to our GPU
-        // Backends::all => Vulkan + Metal + DX12 + Browser WebGPU
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::GL,
-            dx12_shader_compiler: Default::default(),
-        });
+        let mut selected = None;
+        for backends in candidate_backends(config.backends) {
+            // The instance is a handle to our GPU
+            let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+                backends,
+                dx12_shader_compiler: Default::default(),
+            });
 
-        // let instance = wgpu::Instance::new(Backends::all());
+            // # Safety
+            //
+            // The surface needs to live as long as the window that created it.
+            // State owns the window so this should be safe.
+            let surface = match unsafe { instance.create_surface(&window) } {
+                Ok(surface) => surface,
+                Err(err) => {
+                    tracing::warn!("Failed to create surface for {backends:?}: {err}");
+                    continue;
+                }
+            };
 
-        // # Safety
-        //
-        // The surface needs to live as long as the window that created it.
-        // State owns the window so this should be safe.
-        let surface = unsafe { instance.create_surface(&window) }.unwrap();
+            if let Some(adapter) = request_adapter(&instance, config, Some(&surface)).await {
+                selected = Some((surface, adapter));
+                break;
+            }
+        }
 
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .unwrap();
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    features: wgpu::Features::default(),
-                    limits: wgpu::Limits::downlevel_webgl2_defaults(),
-                    label: None,
-                },
-                None, // Trace path
-            )
-            .await
-            .unwrap();
+        let (surface, adapter) = selected.context("No suitable adapter found")?;
+
+        let (device, queue) = request_device(&adapter, config).await?;
+        let sample_count = supported_sample_count(&adapter, &device, config.sample_count);
 
         // let surface_formats = surface.get_supported_formats(&adapter);
         // tracing::info!("Available surface formats: {surface_formats:#?}");
@@ -66,9 +87,8 @@ impl Gpu {
         //     .unwrap_or_else(|| surface_formats[0]);
 
         let surface_caps = surface.get_capabilities(&adapter);
-        // Shader code in this tutorial assumes an sRGB surface texture. Using a different
-        // one will result all the colors coming out darker. If you want to support non
-        // sRGB surfaces, you'll need to account for that when drawing to the frame.
+        // sRGB surfaces encode the linear output of the renderer in hardware. Other surfaces are
+        // supported, but are encoded by the composite pass instead.
         let surface_format = surface_caps
             .formats
             .iter()
@@ -77,15 +97,40 @@ impl Gpu {
             .unwrap_or(surface_caps.formats[0]);
 
         tracing::info!("Found surface format: {:?}", surface_format);
+
+        // Fifo is guaranteed to be supported, and the automatic modes resolve to a supported mode
+        let present_mode = match config.present_mode {
+            PresentMode::AutoVsync | PresentMode::AutoNoVsync => config.present_mode,
+            mode if surface_caps.present_modes.contains(&mode) => mode,
+            mode => {
+                tracing::warn!(
+                    "Present mode {mode:?} is not supported, falling back to {:?}. Supported modes: {:?}",
+                    PresentMode::Fifo,
+                    surface_caps.present_modes
+                );
+                PresentMode::Fifo
+            }
+        };
+
+        let alpha_mode = match config.alpha_mode {
+            CompositeAlphaMode::Auto => CompositeAlphaMode::Auto,
+            mode if surface_caps.alpha_modes.contains(&mode) => mode,
+            mode => {
+                tracing::warn!(
+                    "Alpha mode {mode:?} is not supported, falling back to {:?}",
+                    surface_caps.alpha_modes[0]
+                );
+                surface_caps.alpha_modes[0]
+            }
+        };
+
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
             width: size.width,
             height: size.height,
-            // present_mode: surface.get_supported_present_modes(&adapter)[0],
-            // alpha_mode: surface.get_supported_alpha_modes(&adapter)[0],
-            present_mode: surface_caps.present_modes[0],
-            alpha_mode: surface_caps.alpha_modes[0],
+            present_mode,
+            alpha_mode,
             view_formats: vec![],
         };
 
@@ -93,44 +138,209 @@ impl Gpu {
 
         surface.configure(&device, &config);
 
-        Self {
+        Ok(Self {
+            target: RenderTarget::Surface {
+                surface,
+                config,
+                window,
+            },
             adapter,
-            window,
-            surface,
             device,
             queue,
             surface_format,
-            surface_caps,
-            size,
+            sample_count,
+            size: Mutex::new(size),
+            pipeline_cache: PipelineCache::default(),
+            samplers: Default::default(),
+            mip_generator: OnceLock::new(),
+        })
+    }
+
+    /// Creates a gpu without a window, which renders into an offscreen texture of the given size
+    /// and format.
+    ///
+    /// A software fallback adapter is used if no other adapter is available. This allows
+    /// rendering on machines without a display.
+    #[tracing::instrument(level = "info")]
+    pub async fn new_headless(
+        config: &GpuConfig,
+        size: PhysicalSize<u32>,
+        format: TextureFormat,
+    ) -> anyhow::Result<Self> {
+        let mut adapter = None;
+        for backends in candidate_backends(config.backends) {
+            let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+                backends,
+                dx12_shader_compiler: Default::default(),
+            });
+
+            adapter = request_adapter(&instance, config, None).await;
+            if adapter.is_some() {
+                break;
+            }
         }
+
+        let adapter = adapter.context("No suitable adapter found")?;
+
+        let (device, queue) = request_device(&adapter, config).await?;
+
+        let texture = Self::create_offscreen_texture(&device, size, format);
+        let sample_count = supported_sample_count(&adapter, &device, config.sample_count);
+
+        Ok(Self {
+            target: RenderTarget::Offscreen {
+                texture: Mutex::new(Arc::new(texture)),
+            },
+            adapter,
+            device,
+            queue,
+            surface_format: format,
+            sample_count,
+            size: Mutex::new(size),
+            pipeline_cache: PipelineCache::default(),
+            samplers: Default::default(),
+            mip_generator: OnceLock::new(),
+        })
     }
 
-    pub fn surface_config(&self, size: PhysicalSize<u32>) -> SurfaceConfiguration {
-        SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: self.surface_format,
-            width: size.width,
-            height: size.height,
-            // present_mode: self.surface.get_supported_present_modes(&self.adapter)[0],
-            // alpha_mode: self.surface.get_supported_alpha_modes(&self.adapter)[0],
-            present_mode: self.surface_caps.present_modes[0],
-            alpha_mode: self.surface_caps.alpha_modes[0],
-            view_formats: vec![],
+    fn create_offscreen_texture(
+        device: &wgpu::Device,
+        size: PhysicalSize<u32>,
+        format: TextureFormat,
+    ) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Gpu.offscreen_texture"),
+            size: wgpu::Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT
+                | TextureUsages::COPY_SRC
+                | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        })
+    }
+
+    /// Returns the window presented to, or `None` for a headless gpu
+    pub fn window(&self) -> Option<&Window> {
+        match &self.target {
+            RenderTarget::Surface { window, .. } => Some(window),
+            RenderTarget::Offscreen { .. } => None,
+        }
+    }
+
+    /// Returns the texture rendered into by a headless gpu.
+    ///
+    /// The texture is replaced when the gpu is resized.
+    pub fn offscreen_texture(&self) -> Option<Arc<wgpu::Texture>> {
+        match &self.target {
+            RenderTarget::Surface { .. } => None,
+            RenderTarget::Offscreen { texture } => Some(texture.lock().clone()),
         }
     }
 
-    pub fn window(&self) -> &Window {
-        &self.window
+    pub fn is_headless(&self) -> bool {
+        matches!(self.target, RenderTarget::Offscreen { .. })
+    }
+
+    pub fn adapter(&self) -> &Adapter {
+        &self.adapter
+    }
+
+    /// Returns true if vertex shaders can read from storage buffers, which WebGL2 does not support
+    pub fn supports_vertex_storage(&self) -> bool {
+        self.device.limits().max_storage_buffers_per_shader_stage > 0
+            && self
+                .adapter
+                .get_downlevel_capabilities()
+                .flags
+                .contains(DownlevelFlags::VERTEX_STORAGE)
+    }
+
+    /// Returns true if compute shaders can be dispatched, which WebGL2 does not support
+    pub fn supports_compute(&self) -> bool {
+        self.device.limits().max_compute_workgroups_per_dimension > 0
+            && self
+                .adapter
+                .get_downlevel_capabilities()
+                .flags
+                .contains(DownlevelFlags::COMPUTE_SHADERS)
+    }
+
+    /// Returns true if draw arguments can be read from a buffer
+    pub fn supports_indirect(&self) -> bool {
+        self.adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(DownlevelFlags::INDIRECT_EXECUTION)
+    }
+
+    /// Returns the format of HDR render targets.
+    ///
+    /// This is `Rgba16Float` where it can be rendered to and blended, which WebGL2 only supports
+    /// with an extension. Otherwise colors are clamped to 1 and stored as sRGB to limit banding.
+    pub fn hdr_format(&self) -> TextureFormat {
+        hdr_format(&self.adapter)
+    }
+
+    /// Returns the number of samples per pixel of the scene, which is 1 without multisampling
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
     }
 
+    pub fn pipeline_cache(&self) -> &PipelineCache {
+        &self.pipeline_cache
+    }
+
+    /// Returns a sampler for `desc`, shared with all other users of the same description
+    pub fn sampler(&self, desc: &SamplerDesc) -> Arc<wgpu::Sampler> {
+        self.samplers
+            .lock()
+            .entry(*desc)
+            .or_insert_with(|| Arc::new(desc.create(&self.device)))
+            .clone()
+    }
+
+    pub(crate) fn mip_generator(&self) -> &MipGenerator {
+        self.mip_generator.get_or_init(|| MipGenerator::new(self))
+    }
+
+    /// Resizes the render target.
+    ///
+    /// Size dependent resources, such as those of the [`Renderer`](crate::renderer::Renderer),
+    /// need to be resized afterwards using [`Self::size`].
+    ///
+    /// Zero sized requests, such as when the window is minimized, are ignored.
     pub fn resize(&self, new_size: PhysicalSize<u32>) {
-        info_span!("resize", ?new_size);
+        let _span = info_span!("resize", ?new_size).entered();
         if new_size.width > 0 && new_size.height > 0 {
-            // self.size = new_size;
-            // self.config.width = new_size.width;
-            // self.config.height = new_size.height;
-            self.surface
-                .configure(&self.device, &self.surface_config(new_size));
+            *self.size.lock() = new_size;
+
+            match &self.target {
+                RenderTarget::Surface {
+                    surface, config, ..
+                } => {
+                    let config = SurfaceConfiguration {
+                        width: new_size.width,
+                        height: new_size.height,
+                        ..config.clone()
+                    };
+
+                    surface.configure(&self.device, &config);
+                }
+                RenderTarget::Offscreen { texture } => {
+                    *texture.lock() = Arc::new(Self::create_offscreen_texture(
+                        &self.device,
+                        new_size,
+                        self.surface_format,
+                    ));
+                }
+            }
         }
     }
 
@@ -145,11 +355,19 @@ impl Gpu {
         renderer: impl FnOnce(&mut CommandEncoder, &TextureView),
     ) -> Result<(), wgpu::SurfaceError> {
         let _span = info_span!("drawing").entered();
-        let output = self.surface.get_current_texture()?;
+        let (output, view) = match &self.target {
+            RenderTarget::Surface { surface, .. } => {
+                let output = surface.get_current_texture()?;
+                let view = output.texture.create_view(&wgpu::TextureViewDescriptor {
+                    ..Default::default()
+                });
 
-        let view = output.texture.create_view(&wgpu::TextureViewDescriptor {
-            ..Default::default()
-        });
+                (Some(output), view)
+            }
+            RenderTarget::Offscreen { texture } => {
+                (None, texture.lock().create_view(&Default::default()))
+            }
+        };
 
         let mut encoder = self
             .device
@@ -160,13 +378,27 @@ impl Gpu {
         renderer(&mut encoder, &view);
 
         self.queue.submit([encoder.finish()]);
-        output.present();
+        if let Some(output) = output {
+            output.present();
+        }
 
         Ok(())
     }
 
+    /// Records and submits work which does not draw to the render target, such as compute
+    /// passes outside of a frame
+    pub fn submit(&self, label: &str, commands: impl FnOnce(&mut CommandEncoder)) {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some(label) });
+
+        commands(&mut encoder);
+
+        self.queue.submit([encoder.finish()]);
+    }
+
     pub fn size(&self) -> PhysicalSize<u32> {
-        self.size
+        *self.size.lock()
     }
 
     pub fn surface_format(&self) -> TextureFormat {
@@ -177,3 +409,148 @@ impl Gpu {
     //     &self.surface_caps
     // }
 }
+
+fn hdr_format(adapter: &Adapter) -> TextureFormat {
+    let features = adapter.get_texture_format_features(TextureFormat::Rgba16Float);
+
+    if features
+        .allowed_usages
+        .contains(TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING)
+        && features
+            .flags
+            .contains(TextureFormatFeatureFlags::FILTERABLE | TextureFormatFeatureFlags::BLENDABLE)
+    {
+        TextureFormat::Rgba16Float
+    } else {
+        TextureFormat::Rgba8UnormSrgb
+    }
+}
+
+/// Returns the highest sample count up to `requested` which the HDR and depth formats of the
+/// scene support, including resolving the color
+fn supported_sample_count(adapter: &Adapter, device: &wgpu::Device, requested: u32) -> u32 {
+    // Counts other than 1 and 4 depend on the adapter, which the device only allows with a feature
+    let adapter_specific = device
+        .features()
+        .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+
+    let supports = |format: TextureFormat, count: u32, flags: TextureFormatFeatureFlags| {
+        let features = if adapter_specific {
+            adapter.get_texture_format_features(format)
+        } else {
+            format.guaranteed_format_features(device.features())
+        };
+
+        features.flags.contains(flags)
+            && features.flags.sample_count_supported(count)
+            && adapter
+                .get_texture_format_features(format)
+                .flags
+                .sample_count_supported(count)
+    };
+
+    let sample_count = [16, 8, 4, 2, 1]
+        .into_iter()
+        .filter(|&count| count <= requested.max(1))
+        .find(|&count| {
+            count == 1
+                || supports(
+                    hdr_format(adapter),
+                    count,
+                    TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE,
+                ) && supports(
+                    TextureFormat::Depth32Float,
+                    count,
+                    TextureFormatFeatureFlags::empty(),
+                )
+        })
+        .unwrap_or(1);
+
+    if sample_count != requested {
+        tracing::warn!("Sample count {requested} is not supported, falling back to {sample_count}");
+    }
+
+    sample_count
+}
+
+/// Returns the backends to try in order, falling back to any backend
+fn candidate_backends(preferred: Backends) -> impl Iterator<Item = Backends> {
+    [
+        Some(preferred),
+        (preferred != Backends::all()).then_some(Backends::all()),
+    ]
+    .into_iter()
+    .flatten()
+}
+
+async fn request_adapter(
+    instance: &wgpu::Instance,
+    config: &GpuConfig,
+    compatible_surface: Option<&wgpu::Surface>,
+) -> Option<Adapter> {
+    for force_fallback_adapter in [false, true] {
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: config.power_preference,
+                compatible_surface,
+                force_fallback_adapter,
+            })
+            .await;
+
+        if let Some(adapter) = adapter {
+            let info = adapter.get_info();
+            if !config.backends.contains(info.backend.into()) {
+                tracing::warn!(
+                    "None of the requested backends {:?} are available, falling back to {:?}",
+                    config.backends,
+                    info.backend
+                );
+            }
+
+            if force_fallback_adapter {
+                tracing::warn!("No hardware adapter available, using fallback adapter");
+            }
+
+            tracing::info!("Using adapter: {info:?}");
+            return Some(adapter);
+        }
+    }
+
+    None
+}
+
+/// Requests a device with as much of the requested features and limits as the adapter supports
+async fn request_device(
+    adapter: &Adapter,
+    config: &GpuConfig,
+) -> anyhow::Result<(wgpu::Device, wgpu::Queue)> {
+    let supported = adapter.features();
+    let missing = config.features.difference(supported);
+    if !missing.is_empty() {
+        tracing::info!("Requested features {missing:?} are not supported by the adapter");
+    }
+
+    let adapter_limits = adapter.limits();
+    let limits = if config.limits.check_limits(&adapter_limits) {
+        config.limits.clone()
+    } else {
+        tracing::warn!(
+            "Requested limits exceed those of the adapter, falling back to the adapter limits"
+        );
+        adapter_limits
+    };
+
+    let (device, queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                features: config.features & supported,
+                limits,
+                label: None,
+            },
+            None, // Trace path
+        )
+        .await
+        .context("Failed to create device")?;
+
+    Ok((device, queue))
+}
@@ -1,42 +1,117 @@
-use glam::{vec2, vec3, Vec2, Vec3};
+use glam::{vec2, vec3, Vec2, Vec3, Vec4};
 use wgpu::{
-    util::DeviceExt, vertex_attr_array, Buffer, RenderPass, VertexAttribute, VertexBufferLayout,
+    util::DeviceExt, vertex_attr_array, Buffer, IndexFormat, RenderPass, VertexAttribute,
+    VertexBufferLayout,
 };
 
 use super::Gpu;
 
+/// A vertex type which can be stored in the vertex buffer of a [`Mesh`]
+pub trait VertexFormat: bytemuck::Pod {
+    /// The attributes of the vertex, in the order of their shader locations
+    const ATTRIBUTES: &'static [VertexAttribute];
+
+    fn layout() -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// An index type of a [`Mesh`]
+pub trait MeshIndex: bytemuck::Pod {
+    const FORMAT: IndexFormat;
+}
+
+impl MeshIndex for u16 {
+    const FORMAT: IndexFormat = IndexFormat::Uint16;
+}
+
+impl MeshIndex for u32 {
+    const FORMAT: IndexFormat = IndexFormat::Uint32;
+}
+
+/// A textured vertex
 #[repr(C)]
-#[derive(bytemuck::Pod, bytemuck::Zeroable, Copy, Debug, Clone)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Copy, Debug, Clone, PartialEq)]
 pub struct Vertex {
-    pos: Vec3,
-    tex_coords: Vec2,
+    pub pos: Vec3,
+    pub tex_coords: Vec2,
 }
 
 impl Vertex {
-    const fn new(pos: Vec3, tex_coords: Vec2) -> Self {
+    pub const fn new(pos: Vec3, tex_coords: Vec2) -> Self {
         Self { pos, tex_coords }
     }
+}
 
-    pub fn layout() -> VertexBufferLayout<'static> {
-        static ATTRIBUTES: &[VertexAttribute] = &vertex_attr_array![0 => Float32x3, 1 => Float32x2];
+impl VertexFormat for Vertex {
+    const ATTRIBUTES: &'static [VertexAttribute] =
+        &vertex_attr_array![0 => Float32x3, 1 => Float32x2];
+}
 
-        wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: ATTRIBUTES,
+/// A vertex colored without a texture
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Copy, Debug, Clone, PartialEq)]
+pub struct ColorVertex {
+    pub pos: Vec3,
+    /// Stored as an array, as the alignment of `Vec4` would require padding
+    pub color: [f32; 4],
+}
+
+impl ColorVertex {
+    pub fn new(pos: Vec3, color: Vec4) -> Self {
+        Self {
+            pos,
+            color: color.to_array(),
+        }
+    }
+}
+
+impl VertexFormat for ColorVertex {
+    const ATTRIBUTES: &'static [VertexAttribute] =
+        &vertex_attr_array![0 => Float32x3, 1 => Float32x4];
+}
+
+/// A textured vertex with a normal, for lit meshes
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Copy, Debug, Clone, PartialEq)]
+pub struct NormalVertex {
+    pub pos: Vec3,
+    pub normal: Vec3,
+    pub tex_coords: Vec2,
+}
+
+impl NormalVertex {
+    pub const fn new(pos: Vec3, normal: Vec3, tex_coords: Vec2) -> Self {
+        Self {
+            pos,
+            normal,
+            tex_coords,
         }
     }
 }
 
+impl VertexFormat for NormalVertex {
+    const ATTRIBUTES: &'static [VertexAttribute] =
+        &vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x2];
+}
+
+/// Indexed vertices uploaded to the gpu.
+///
+/// The vertex layout is not part of the type, and needs to match the pipeline it is drawn with.
 pub struct Mesh {
     vertex_count: u32,
     index_count: u32,
+    index_format: IndexFormat,
     pub vertex_buffer: Buffer,
     pub index_buffer: Buffer,
 }
 
 impl Mesh {
-    pub fn new(gpu: &Gpu, vertices: &[Vertex], indices: &[u32]) -> Self {
+    pub fn new<V: VertexFormat, I: MeshIndex>(gpu: &Gpu, vertices: &[V], indices: &[I]) -> Self {
         let vertex_buffer = gpu
             .device
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -56,6 +131,7 @@ impl Mesh {
         Self {
             vertex_count: vertices.len() as u32,
             index_count: indices.len() as u32,
+            index_format: I::FORMAT,
             vertex_buffer,
             index_buffer,
         }
@@ -69,14 +145,14 @@ impl Mesh {
             Vertex::new(vec3(-0.5, 0.5, 0.0), vec2(0.0, 0.0)),  // 3
         ];
 
-        const INDICES: &[u32] = &[0, 1, 2, 2, 3, 0];
+        const INDICES: &[u16] = &[0, 1, 2, 2, 3, 0];
 
         Self::new(gpu, VERTICES, INDICES)
     }
 
     pub fn bind<'a>(&'a self, render_pass: &mut RenderPass<'a>) {
         render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.set_index_buffer(self.index_buffer.slice(..), self.index_format);
     }
 
     pub fn index_count(&self) -> u32 {
@@ -86,4 +162,8 @@ impl Mesh {
     pub fn vertex_count(&self) -> u32 {
         self.vertex_count
     }
+
+    pub fn index_format(&self) -> IndexFormat {
+        self.index_format
+    }
 }
@@ -0,0 +1,354 @@
+//! Procedural generation of 2D meshes from primitive shapes.
+use std::f32::consts::{PI, TAU};
+
+use glam::{vec2, Vec2};
+
+use super::{Gpu, Mesh, Vertex, VertexFormat};
+
+/// Builds a mesh in the XY plane out of primitive shapes.
+///
+/// Triangles are wound counter-clockwise. The texture coordinates of each shape span its
+/// bounding square, with the texture upright, so a square maps to the whole texture.
+#[derive(Debug, Clone)]
+pub struct MeshBuilder {
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+    tolerance: f32,
+}
+
+impl Default for MeshBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MeshBuilder {
+    pub fn new() -> Self {
+        Self {
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            tolerance: 0.01,
+        }
+    }
+
+    /// Sets the maximum distance between a curve and the edges approximating it, which
+    /// determines the number of segments of circles, rings and capsules
+    pub fn with_tolerance(mut self, tolerance: f32) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    pub fn vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    /// Adds a vertex, returning its index
+    pub fn push_vertex(&mut self, vertex: Vertex) -> u32 {
+        self.vertices.push(vertex);
+        self.vertices.len() as u32 - 1
+    }
+
+    pub fn push_triangle(&mut self, a: u32, b: u32, c: u32) -> &mut Self {
+        self.indices.extend([a, b, c]);
+        self
+    }
+
+    /// Adds the vertices of a shape, with texture coordinates spanning its bounds. Returns the
+    /// index of the first vertex.
+    fn push_shape(&mut self, points: &[Vec2]) -> u32 {
+        let (min, max) = points.iter().fold(
+            (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+            |(min, max), &p| (min.min(p), max.max(p)),
+        );
+
+        let center = (min + max) / 2.0;
+        let extent = (max - min).max_element().max(f32::EPSILON);
+
+        let first = self.vertices.len() as u32;
+        self.vertices.extend(points.iter().map(|&p| {
+            let uv = (p - center) / extent;
+            Vertex::new(p.extend(0.0), vec2(0.5 + uv.x, 0.5 - uv.y))
+        }));
+
+        first
+    }
+
+    /// Adds a polygon, triangulated as a fan around its centroid.
+    ///
+    /// The polygon needs to be star-shaped around its centroid, meaning every point is visible
+    /// from it, which holds for all convex polygons.
+    pub fn polygon(&mut self, points: &[Vec2]) -> &mut Self {
+        if points.len() < 3 {
+            return self;
+        }
+
+        let centroid = points.iter().sum::<Vec2>() / points.len() as f32;
+        self.polygon_around(centroid, points)
+    }
+
+    /// Adds a polygon, triangulated as a fan around `center`.
+    ///
+    /// Every point needs to be visible from `center`, for shapes which are star-shaped around a
+    /// known point other than their centroid.
+    pub fn polygon_around(&mut self, center: Vec2, points: &[Vec2]) -> &mut Self {
+        if points.len() < 3 {
+            return self;
+        }
+
+        let first = self.push_shape(&[&[center], points].concat());
+        let n = points.len() as u32;
+        for i in 0..n {
+            self.push_triangle(first, first + 1 + i, first + 1 + (i + 1) % n);
+        }
+
+        self
+    }
+
+    pub fn rect(&mut self, center: Vec2, size: Vec2) -> &mut Self {
+        let h = size / 2.0;
+        let first = self.push_shape(&[
+            center + vec2(-h.x, -h.y),
+            center + vec2(h.x, -h.y),
+            center + vec2(h.x, h.y),
+            center + vec2(-h.x, h.y),
+        ]);
+
+        self.push_triangle(first, first + 1, first + 2)
+            .push_triangle(first + 2, first + 3, first)
+    }
+
+    /// Adds a regular polygon with a vertex pointing up
+    pub fn ngon(&mut self, center: Vec2, radius: f32, sides: u32) -> &mut Self {
+        let points = arc(center, radius, PI / 2.0, TAU, sides.max(3));
+        self.polygon(&points[..points.len() - 1])
+    }
+
+    /// Adds a circle, with as many segments as needed for the tolerance
+    pub fn circle(&mut self, center: Vec2, radius: f32) -> &mut Self {
+        let segments = self.segments(radius, TAU);
+        self.ngon(center, radius, segments)
+    }
+
+    /// Adds an annulus between the `inner` and `outer` radius
+    pub fn ring(&mut self, center: Vec2, inner: f32, outer: f32) -> &mut Self {
+        let segments = self.segments(outer, TAU);
+
+        let outer_points = arc(center, outer, 0.0, TAU, segments);
+        let inner_points = arc(center, inner, 0.0, TAU, segments);
+
+        let first = self.push_shape(&[outer_points, inner_points].concat());
+
+        let n = segments + 1;
+        for i in 0..segments {
+            let (o0, o1) = (first + i, first + i + 1);
+            let (i0, i1) = (first + n + i, first + n + i + 1);
+            self.push_triangle(o0, o1, i1).push_triangle(i1, i0, o0);
+        }
+
+        self
+    }
+
+    /// Adds a line segment of `width` with square ends at the end points
+    pub fn line(&mut self, from: Vec2, to: Vec2, width: f32) -> &mut Self {
+        let dir = (to - from).normalize_or_zero();
+        let normal = dir.perp() * width / 2.0;
+
+        let first = self.push_shape(&[from - normal, to - normal, to + normal, from + normal]);
+
+        self.push_triangle(first, first + 1, first + 2)
+            .push_triangle(first + 2, first + 3, first)
+    }
+
+    /// Adds a line segment with round ends of `radius`, also known as a stadium
+    pub fn capsule(&mut self, from: Vec2, to: Vec2, radius: f32) -> &mut Self {
+        let dir = to - from;
+        let angle = if dir == Vec2::ZERO {
+            0.0
+        } else {
+            dir.y.atan2(dir.x)
+        };
+
+        let segments = self.segments(radius, PI);
+        let points = [
+            arc(to, radius, angle - PI / 2.0, PI, segments),
+            arc(from, radius, angle + PI / 2.0, PI, segments),
+        ]
+        .concat();
+
+        self.polygon(&points)
+    }
+
+    /// Appends the shapes of another builder
+    pub fn append(&mut self, other: &MeshBuilder) -> &mut Self {
+        let offset = self.vertices.len() as u32;
+        self.vertices.extend_from_slice(&other.vertices);
+        self.indices
+            .extend(other.indices.iter().map(|v| v + offset));
+        self
+    }
+
+    /// Returns the number of segments approximating an arc of `angle` within the tolerance
+    fn segments(&self, radius: f32, angle: f32) -> u32 {
+        let tolerance = self.tolerance.clamp(f32::EPSILON, radius.max(f32::EPSILON));
+
+        // The largest angle of a segment whose chord stays within the tolerance of the arc
+        let step = 2.0 * (1.0 - tolerance / radius.max(f32::EPSILON)).acos();
+        let segments = (angle / step.max(f32::EPSILON)).ceil() as u32;
+
+        segments.clamp((angle / TAU * 8.0).ceil() as u32, 1024)
+    }
+
+    /// Uploads the mesh, using 16 bit indices where possible
+    pub fn build(&self, gpu: &Gpu) -> Mesh {
+        self.build_with(gpu, |v| *v)
+    }
+
+    /// Uploads the mesh, converting the vertices to another format
+    pub fn build_with<V: VertexFormat>(&self, gpu: &Gpu, f: impl Fn(&Vertex) -> V) -> Mesh {
+        let vertices = self.vertices.iter().map(f).collect::<Vec<_>>();
+
+        if vertices.len() <= u16::MAX as usize + 1 {
+            let indices = self.indices.iter().map(|&v| v as u16).collect::<Vec<_>>();
+            Mesh::new(gpu, &vertices, &indices)
+        } else {
+            Mesh::new(gpu, &vertices, &self.indices)
+        }
+    }
+}
+
+/// Returns `segments + 1` points along an arc, counter-clockwise from `start` over `angle`
+fn arc(center: Vec2, radius: f32, start: f32, angle: f32, segments: u32) -> Vec<Vec2> {
+    (0..=segments)
+        .map(|i| {
+            let theta = start + angle * i as f32 / segments as f32;
+            center + Vec2::from_angle(theta) * radius
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Returns twice the signed area of each triangle, which is positive when counter-clockwise
+    fn areas(builder: &MeshBuilder) -> Vec<f32> {
+        builder
+            .indices()
+            .chunks(3)
+            .map(|v| {
+                let [a, b, c] = [0, 1, 2].map(|i| builder.vertices()[v[i] as usize].pos.truncate());
+                (b - a).perp_dot(c - a)
+            })
+            .collect()
+    }
+
+    fn total_area(builder: &MeshBuilder) -> f32 {
+        areas(builder).iter().sum::<f32>() / 2.0
+    }
+
+    #[test]
+    fn winding() {
+        let mut builder = MeshBuilder::new();
+        builder
+            .rect(Vec2::ZERO, vec2(2.0, 1.0))
+            .ngon(Vec2::ZERO, 1.0, 5)
+            .circle(vec2(3.0, 0.0), 2.0)
+            .ring(Vec2::ZERO, 1.0, 2.0)
+            .line(Vec2::ZERO, vec2(1.0, 1.0), 0.1)
+            .capsule(Vec2::ZERO, vec2(0.0, -2.0), 0.5);
+
+        assert!(areas(&builder).iter().all(|&v| v > 0.0));
+        assert!(builder
+            .indices()
+            .iter()
+            .all(|&v| (v as usize) < builder.vertices().len()));
+    }
+
+    #[test]
+    fn star_shaped() {
+        // Star-shaped around the origin, but with a notch hiding part of it from the centroid
+        let points = [
+            vec2(1.0, -0.2),
+            vec2(1.0, 0.2),
+            vec2(0.1, 0.1),
+            vec2(0.0, 1.0),
+            vec2(-1.0, 0.0),
+            vec2(0.0, -1.0),
+        ];
+        let area = (0..points.len())
+            .map(|i| points[i].perp_dot(points[(i + 1) % points.len()]))
+            .sum::<f32>()
+            / 2.0;
+
+        let mut builder = MeshBuilder::new();
+        builder.polygon(&points);
+        assert!(areas(&builder).iter().any(|&v| v < 0.0));
+
+        let mut builder = MeshBuilder::new();
+        builder.polygon_around(Vec2::ZERO, &points);
+        assert!(areas(&builder).iter().all(|&v| v > 0.0));
+        assert!((total_area(&builder) - area).abs() < 1e-5);
+    }
+
+    #[test]
+    fn areas_match() {
+        let area = |f: &dyn Fn(&mut MeshBuilder)| {
+            let mut builder = MeshBuilder::new().with_tolerance(0.0001);
+            f(&mut builder);
+            total_area(&builder)
+        };
+
+        let close = |a: f32, b: f32| (a - b).abs() < 0.01 * b;
+
+        assert!(close(
+            area(&|b| _ = b.rect(Vec2::ZERO, vec2(2.0, 3.0))),
+            6.0
+        ));
+        assert!(close(area(&|b| _ = b.circle(Vec2::ONE, 2.0)), PI * 4.0));
+        assert!(close(area(&|b| _ = b.ring(Vec2::ZERO, 1.0, 2.0)), PI * 3.0));
+        assert!(close(
+            area(&|b| _ = b.line(Vec2::ZERO, vec2(3.0, 4.0), 2.0)),
+            10.0
+        ));
+        assert!(close(
+            area(&|b| _ = b.capsule(Vec2::ZERO, vec2(4.0, 0.0), 1.0)),
+            8.0 + PI
+        ));
+    }
+
+    #[test]
+    fn tex_coords() {
+        let mut builder = MeshBuilder::new();
+        builder.rect(vec2(5.0, 5.0), Vec2::ONE);
+
+        let uvs = builder
+            .vertices()
+            .iter()
+            .map(|v| v.tex_coords)
+            .collect::<Vec<_>>();
+
+        // Matches `Mesh::square`
+        assert_eq!(
+            uvs,
+            [
+                vec2(0.0, 1.0),
+                vec2(1.0, 1.0),
+                vec2(1.0, 0.0),
+                vec2(0.0, 0.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn segments() {
+        let builder = MeshBuilder::new().with_tolerance(0.01);
+
+        assert_eq!(builder.segments(0.001, TAU), 8);
+        assert!(builder.segments(1.0, TAU) > builder.segments(0.5, TAU));
+        assert_eq!(builder.segments(1e9, TAU), 1024);
+    }
+}
@@ -0,0 +1,128 @@
+use wgpu::{TextureFormat, TextureViewDescriptor, TextureViewDimension};
+
+use super::{
+    BindGroupBuilder, BindGroupLayout, BlendMode, ComposedShader, Gpu, Shader, ShaderDefines,
+    ShaderDesc, ShaderLibrary, Texture,
+};
+
+/// Fills the mip chain of textures by repeatedly blitting each level into the next, smaller one
+pub(crate) struct MipGenerator {
+    source: ComposedShader,
+    layout: BindGroupLayout,
+}
+
+impl MipGenerator {
+    pub fn new(gpu: &Gpu) -> Self {
+        let library = ShaderLibrary::new()
+            .with_module(
+                "fullscreen",
+                "assets/shaders/fullscreen.wgsl",
+                include_str!("../../../assets/shaders/fullscreen.wgsl"),
+            )
+            .with_module(
+                "blit",
+                "assets/shaders/blit.wgsl",
+                include_str!("../../../assets/shaders/blit.wgsl"),
+            );
+
+        let source = library
+            .compose("blit", &ShaderDefines::new())
+            .expect("Blit shader is valid");
+
+        let layout = source
+            .reflect()
+            .expect("Blit shader is valid")
+            .bind_group_layout("blit_bind_group_layout", 0)
+            .build(gpu);
+
+        Self { source, layout }
+    }
+
+    /// Generates all mip levels after the first for every layer of `texture`
+    pub fn generate(&self, gpu: &Gpu, texture: &Texture) {
+        let format = texture.format();
+        assert!(
+            format
+                .guaranteed_format_features(gpu.device.features())
+                .allowed_usages
+                .contains(wgpu::TextureUsages::RENDER_ATTACHMENT),
+            "Can not generate mips for {format:?}, which is not renderable"
+        );
+
+        // The pipeline cache shares the pipeline between textures of the same format
+        let shader = self.shader(gpu, format);
+        let pipeline = shader
+            .pipeline()
+            .expect("Pipeline is compiled synchronously");
+
+        let level_view = |layer, level| {
+            texture.create_view(&TextureViewDescriptor {
+                label: Some("MipGenerator.level"),
+                dimension: Some(TextureViewDimension::D2),
+                base_mip_level: level,
+                mip_level_count: Some(1),
+                base_array_layer: layer,
+                array_layer_count: Some(1),
+                ..Default::default()
+            })
+        };
+
+        // The GL backend binds the whole texture rather than the view of a single level, so the
+        // source level is also selected through the lod clamp
+        let samplers = (1..texture.mip_level_count())
+            .map(|level| {
+                gpu.device.create_sampler(&wgpu::SamplerDescriptor {
+                    label: Some("MipGenerator.sampler"),
+                    mag_filter: wgpu::FilterMode::Linear,
+                    min_filter: wgpu::FilterMode::Linear,
+                    lod_min_clamp: (level - 1) as f32,
+                    lod_max_clamp: (level - 1) as f32,
+                    ..Default::default()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        gpu.submit("MipGenerator", |encoder| {
+            for layer in 0..texture.layer_count() {
+                for level in 1..texture.mip_level_count() {
+                    let source = level_view(layer, level - 1);
+                    let target = level_view(layer, level);
+
+                    let bind_group = BindGroupBuilder::new("blit_bind_group")
+                        .bind_texture(&source)
+                        .bind_sampler(&samplers[level as usize - 1])
+                        .build(gpu, &self.layout);
+
+                    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("MipGenerator.blit"),
+                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                            view: &target,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                                store: true,
+                            },
+                        })],
+                        depth_stencil_attachment: None,
+                    });
+
+                    render_pass.set_pipeline(pipeline);
+                    render_pass.set_bind_group(0, &bind_group, &[]);
+                    render_pass.draw(0..3, 0..1);
+                }
+            }
+        });
+    }
+
+    fn shader(&self, gpu: &Gpu, format: TextureFormat) -> Shader {
+        Shader::new(
+            gpu,
+            ShaderDesc::new("blit", &self.source)
+                .with_target(format, BlendMode::Opaque)
+                .with_depth_stencil(None)
+                .with_sample_count(1)
+                .with_layouts(&[&self.layout]),
+        )
+        .expect("Blit shader matches its layout")
+    }
+}
@@ -1,13 +1,43 @@
+mod atlas;
 mod bind_group;
 mod buffer;
+mod compressed;
+mod compute;
+mod config;
 mod gpu;
 mod mesh;
+mod mesh_builder;
+mod mipmap;
+mod model;
+mod pipeline_cache;
+mod post_process;
+mod preprocessor;
+mod reflection;
+mod render_graph;
+mod sampler;
 mod shader;
+mod sprite_batch;
+mod svg;
 mod texture;
 
+pub use atlas::*;
 pub use bind_group::*;
 pub use buffer::*;
+pub use compressed::*;
+pub use compute::*;
+pub use config::*;
 pub use gpu::*;
 pub use mesh::*;
+pub use mesh_builder::*;
+pub(crate) use mipmap::MipGenerator;
+pub use model::*;
+pub use pipeline_cache::*;
+pub use post_process::*;
+pub use preprocessor::*;
+pub use reflection::*;
+pub use render_graph::*;
+pub use sampler::*;
 pub use shader::*;
+pub use sprite_batch::*;
+pub use svg::*;
 pub use texture::*;
@@ -0,0 +1,863 @@
+//! Import of 3D models from glTF 2.0 and Wavefront OBJ files.
+//!
+//! Importing happens in two steps. The file is first parsed into a [`ModelData`] on the cpu, with
+//! any files it refers to, such as separate glTF buffers or OBJ material libraries, loaded through
+//! a resolver given by the caller, either directly or as an asset through a [`ModelKey`]. The data
+//! is then uploaded into a [`Model`].
+//!
+//! Textures are not loaded, but referenced by the materials, so they can be shared between
+//! models and loaded in whatever format suits the platform.
+use std::{io::BufReader, sync::Arc};
+
+use async_trait::async_trait;
+use base64::Engine;
+use elements_asset_cache::{AssetCache, AsyncAssetKey};
+use glam::{Mat4, Vec2, Vec3, Vec4};
+
+use super::{Gpu, Mesh, NormalVertex};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ModelError {
+    #[error("Invalid glTF: {0}")]
+    Gltf(#[from] gltf::Error),
+    #[error("Invalid OBJ: {0}")]
+    Obj(#[from] tobj::LoadError),
+    #[error("Failed to load {uri:?}: {source}")]
+    Resolve { uri: String, source: anyhow::Error },
+    #[error("Invalid data URI: {0}")]
+    DataUri(String),
+    #[error("The binary chunk referenced by buffer {0} is missing")]
+    MissingBlob(usize),
+    #[error("Primitive {primitive} of mesh {mesh} has no positions")]
+    MissingPositions { mesh: usize, primitive: usize },
+    #[error("Buffer view {view} is out of bounds of its buffer of {length} bytes")]
+    BufferView { view: usize, length: usize },
+    #[error("Accessor {accessor} is out of bounds of buffer view {view}")]
+    Accessor { accessor: usize, view: usize },
+    #[error("Unknown model format of {0:?}")]
+    Format(String),
+    #[error(
+        "Primitive {primitive} of mesh {mesh} uses vertex {index}, but has {vertex_count} vertices"
+    )]
+    IndexOutOfBounds {
+        mesh: usize,
+        primitive: usize,
+        index: u32,
+        vertex_count: usize,
+    },
+}
+
+/// Where the encoded image of a texture is stored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextureRef {
+    /// A file, relative to the model
+    Uri(String),
+    /// Image data stored within the model
+    Embedded {
+        mime_type: Option<String>,
+        data: Arc<[u8]>,
+    },
+}
+
+/// The metallic-roughness material of glTF. OBJ materials are converted to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaterialDesc {
+    pub name: Option<String>,
+    /// Linear RGBA, multiplied with the base color texture
+    pub base_color: Vec4,
+    pub base_color_texture: Option<TextureRef>,
+    pub normal_texture: Option<TextureRef>,
+    pub metallic: f32,
+    pub roughness: f32,
+}
+
+impl Default for MaterialDesc {
+    fn default() -> Self {
+        Self {
+            name: None,
+            base_color: Vec4::ONE,
+            base_color_texture: None,
+            normal_texture: None,
+            metallic: 0.0,
+            roughness: 1.0,
+        }
+    }
+}
+
+/// Triangles sharing a material
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrimitiveData {
+    pub vertices: Vec<NormalVertex>,
+    pub indices: Vec<u32>,
+    /// Index into [`ModelData::materials`], or the default material if `None`
+    pub material: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeshData {
+    pub name: Option<String>,
+    pub primitives: Vec<PrimitiveData>,
+}
+
+/// A placement of a mesh in the model
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshInstance {
+    /// Index into [`ModelData::meshes`]
+    pub mesh: usize,
+    /// The transform of the node relative to the model, including those of its parents
+    pub transform: Mat4,
+}
+
+/// A model parsed on the cpu
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ModelData {
+    pub meshes: Vec<MeshData>,
+    pub materials: Vec<MaterialDesc>,
+    pub instances: Vec<MeshInstance>,
+}
+
+impl ModelData {
+    /// Parses a glTF 2.0 model, either as JSON (`.gltf`) or binary (`.glb`).
+    ///
+    /// Buffers which are neither embedded as data URIs nor stored in the binary chunk are loaded
+    /// through `resolve`, which is given the URI of the file relative to the model.
+    ///
+    /// The meshes of the nodes in the default scene are instanced with their global transform.
+    /// Primitives which are not triangle lists are skipped.
+    pub fn from_gltf(
+        data: &[u8],
+        resolve: impl Fn(&str) -> anyhow::Result<Vec<u8>>,
+    ) -> Result<Self, ModelError> {
+        let gltf::Gltf { document, mut blob } = gltf::Gltf::from_slice(data)?;
+
+        let buffers = document
+            .buffers()
+            .map(|buffer| match buffer.source() {
+                gltf::buffer::Source::Bin => {
+                    blob.take().ok_or(ModelError::MissingBlob(buffer.index()))
+                }
+                gltf::buffer::Source::Uri(uri) => load_uri(uri, &resolve),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let texture_ref = |texture: gltf::Texture| match texture.source().source() {
+            gltf::image::Source::Uri { uri, mime_type } => match decode_data_uri(uri) {
+                Some(data) => data.map(|data| TextureRef::Embedded {
+                    mime_type: mime_type.map(Into::into),
+                    data: data.into(),
+                }),
+                None => Ok(TextureRef::Uri(uri.into())),
+            },
+            gltf::image::Source::View { view, mime_type } => {
+                let buffer = &buffers[view.buffer().index()];
+                let data = buffer
+                    .get(view.offset()..view.offset() + view.length())
+                    .ok_or(ModelError::BufferView {
+                        view: view.index(),
+                        length: buffer.len(),
+                    })?;
+
+                Ok(TextureRef::Embedded {
+                    mime_type: Some(mime_type.into()),
+                    data: data.into(),
+                })
+            }
+        };
+
+        let materials = document
+            .materials()
+            .map(|material| {
+                let pbr = material.pbr_metallic_roughness();
+
+                Ok(MaterialDesc {
+                    name: material.name().map(Into::into),
+                    base_color: pbr.base_color_factor().into(),
+                    base_color_texture: pbr
+                        .base_color_texture()
+                        .map(|v| texture_ref(v.texture()))
+                        .transpose()?,
+                    normal_texture: material
+                        .normal_texture()
+                        .map(|v| texture_ref(v.texture()))
+                        .transpose()?,
+                    metallic: pbr.metallic_factor(),
+                    roughness: pbr.roughness_factor(),
+                })
+            })
+            .collect::<Result<Vec<_>, ModelError>>()?;
+
+        let meshes = document
+            .meshes()
+            .map(|mesh| {
+                let primitives = mesh
+                    .primitives()
+                    .filter(|primitive| {
+                        let triangles = primitive.mode() == gltf::mesh::Mode::Triangles;
+                        if !triangles {
+                            tracing::warn!(mode = ?primitive.mode(), "Skipping primitive");
+                        }
+                        triangles
+                    })
+                    .map(|primitive| {
+                        // The reader treats out of bounds data as missing
+                        for (_, accessor) in primitive.attributes() {
+                            check_accessor(&accessor, &buffers)?;
+                        }
+                        if let Some(accessor) = primitive.indices() {
+                            check_accessor(&accessor, &buffers)?;
+                        }
+
+                        let reader = primitive
+                            .reader(|buffer| buffers.get(buffer.index()).map(|v| v.as_slice()));
+
+                        let positions = reader
+                            .read_positions()
+                            .ok_or(ModelError::MissingPositions {
+                                mesh: mesh.index(),
+                                primitive: primitive.index(),
+                            })?
+                            .map(Vec3::from);
+
+                        let mut vertices = positions
+                            .map(|pos| NormalVertex::new(pos, Vec3::ZERO, Vec2::ZERO))
+                            .collect::<Vec<_>>();
+
+                        if let Some(tex_coords) = reader.read_tex_coords(0) {
+                            for (v, uv) in vertices.iter_mut().zip(tex_coords.into_f32()) {
+                                v.tex_coords = uv.into();
+                            }
+                        }
+
+                        let indices: Vec<u32> = match reader.read_indices() {
+                            Some(indices) => indices.into_u32().collect(),
+                            None => (0..vertices.len() as u32).collect(),
+                        };
+                        check_indices(&indices, vertices.len(), mesh.index(), primitive.index())?;
+
+                        match reader.read_normals() {
+                            Some(normals) => {
+                                for (v, normal) in vertices.iter_mut().zip(normals) {
+                                    v.normal = normal.into();
+                                }
+                            }
+                            None => compute_normals(&mut vertices, &indices),
+                        }
+
+                        Ok(PrimitiveData {
+                            vertices,
+                            indices,
+                            material: primitive.material().index(),
+                        })
+                    })
+                    .collect::<Result<Vec<_>, ModelError>>()?;
+
+                Ok(MeshData {
+                    name: mesh.name().map(Into::into),
+                    primitives,
+                })
+            })
+            .collect::<Result<Vec<_>, ModelError>>()?;
+
+        let mut instances = Vec::new();
+        let scene = document
+            .default_scene()
+            .or_else(|| document.scenes().next());
+        for node in scene.iter().flat_map(|v| v.nodes()) {
+            collect_instances(&node, Mat4::IDENTITY, &mut instances);
+        }
+
+        Ok(Self {
+            meshes,
+            materials,
+            instances,
+        })
+    }
+
+    /// Parses a Wavefront OBJ model, loading material libraries through `resolve`.
+    ///
+    /// Each object becomes a mesh with a single primitive, instanced once without a transform.
+    /// Missing material libraries are skipped with a warning.
+    pub fn from_obj(
+        data: &[u8],
+        resolve: impl Fn(&str) -> anyhow::Result<Vec<u8>>,
+    ) -> Result<Self, ModelError> {
+        let options = tobj::LoadOptions {
+            single_index: true,
+            triangulate: true,
+            ignore_points: true,
+            ignore_lines: true,
+        };
+
+        let (models, materials) =
+            tobj::load_obj_buf(&mut BufReader::new(data), &options, |path| {
+                let uri = path.to_string_lossy();
+                let data = resolve(&uri).map_err(|err| {
+                    tracing::warn!(%uri, "Failed to load material library: {err:?}");
+                    tobj::LoadError::OpenFileFailed
+                })?;
+
+                tobj::load_mtl_buf(&mut BufReader::new(data.as_slice()))
+            })?;
+
+        let materials = materials
+            .unwrap_or_else(|err| {
+                tracing::warn!("Failed to load materials: {err}");
+                Vec::new()
+            })
+            .into_iter()
+            .map(|material| MaterialDesc {
+                name: Some(material.name),
+                base_color: Vec3::from(material.diffuse.unwrap_or([1.0; 3]))
+                    .extend(material.dissolve.unwrap_or(1.0)),
+                base_color_texture: material.diffuse_texture.map(TextureRef::Uri),
+                normal_texture: material.normal_texture.map(TextureRef::Uri),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+
+        let meshes = models
+            .into_iter()
+            .enumerate()
+            .map(|(i, model)| {
+                let mesh = model.mesh;
+
+                let mut vertices = mesh
+                    .positions
+                    .chunks_exact(3)
+                    .enumerate()
+                    .map(|(i, pos)| {
+                        let normal = mesh.normals.get(i * 3..i * 3 + 3);
+                        // OBJ has the origin of texture coordinates in the bottom left corner
+                        let uv = mesh.texcoords.get(i * 2..i * 2 + 2);
+
+                        NormalVertex::new(
+                            Vec3::from_slice(pos),
+                            normal.map_or(Vec3::ZERO, Vec3::from_slice),
+                            uv.map_or(Vec2::ZERO, |v| Vec2::new(v[0], 1.0 - v[1])),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                check_indices(&mesh.indices, vertices.len(), i, 0)?;
+
+                if mesh.normals.is_empty() {
+                    compute_normals(&mut vertices, &mesh.indices);
+                }
+
+                Ok(MeshData {
+                    name: Some(model.name),
+                    primitives: vec![PrimitiveData {
+                        vertices,
+                        indices: mesh.indices,
+                        material: mesh.material_id.filter(|&v| v < materials.len()),
+                    }],
+                })
+            })
+            .collect::<Result<Vec<_>, ModelError>>()?;
+
+        let instances = (0..meshes.len())
+            .map(|mesh| MeshInstance {
+                mesh,
+                transform: Mat4::IDENTITY,
+            })
+            .collect();
+
+        Ok(Self {
+            meshes,
+            materials,
+            instances,
+        })
+    }
+
+    /// Uploads the meshes
+    pub fn upload(&self, gpu: &Gpu) -> Model {
+        let meshes = self
+            .meshes
+            .iter()
+            .map(|mesh| {
+                mesh.primitives
+                    .iter()
+                    .map(|primitive| ModelPrimitive {
+                        mesh: Mesh::new(gpu, &primitive.vertices, &primitive.indices),
+                        material: primitive.material,
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Model {
+            meshes,
+            materials: self.materials.clone(),
+            instances: self.instances.clone(),
+        }
+    }
+}
+
+/// Loads a buffer from a data URI, or through `resolve`
+fn load_uri(
+    uri: &str,
+    resolve: impl Fn(&str) -> anyhow::Result<Vec<u8>>,
+) -> Result<Vec<u8>, ModelError> {
+    decode_data_uri(uri).unwrap_or_else(|| {
+        resolve(uri).map_err(|source| ModelError::Resolve {
+            uri: uri.into(),
+            source,
+        })
+    })
+}
+
+/// Decodes a base64 `data:` URI, or returns `None` if `uri` is not a data URI
+fn decode_data_uri(uri: &str) -> Option<Result<Vec<u8>, ModelError>> {
+    let rest = uri.strip_prefix("data:")?;
+
+    let decoded = rest
+        .split_once(";base64,")
+        .and_then(|(_, data)| base64::engine::general_purpose::STANDARD.decode(data).ok())
+        .ok_or_else(|| ModelError::DataUri(uri.chars().take(64).collect()));
+
+    Some(decoded)
+}
+
+fn collect_instances(node: &gltf::Node, parent: Mat4, instances: &mut Vec<MeshInstance>) {
+    let transform = parent * Mat4::from_cols_array_2d(&node.transform().matrix());
+
+    if let Some(mesh) = node.mesh() {
+        instances.push(MeshInstance {
+            mesh: mesh.index(),
+            transform,
+        });
+    }
+
+    for child in node.children() {
+        collect_instances(&child, transform, instances);
+    }
+}
+
+/// Returns an error if the elements of `accessor` extend past its buffer view, or the view past
+/// its buffer
+fn check_accessor(accessor: &gltf::Accessor, buffers: &[Vec<u8>]) -> Result<(), ModelError> {
+    // Accessors without a view are zeroed
+    let Some(view) = accessor.view() else {
+        return Ok(());
+    };
+
+    let length = buffers[view.buffer().index()].len();
+    if view.offset() + view.length() > length {
+        return Err(ModelError::BufferView {
+            view: view.index(),
+            length,
+        });
+    }
+
+    let stride = view.stride().unwrap_or(accessor.size());
+    let end = match accessor.count() {
+        0 => 0,
+        count => accessor.offset() + stride * (count - 1) + accessor.size(),
+    };
+
+    if end > view.length() {
+        return Err(ModelError::Accessor {
+            accessor: accessor.index(),
+            view: view.index(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Returns an error if an index refers to a vertex past `vertex_count`
+fn check_indices(
+    indices: &[u32],
+    vertex_count: usize,
+    mesh: usize,
+    primitive: usize,
+) -> Result<(), ModelError> {
+    match indices.iter().find(|&&v| v as usize >= vertex_count) {
+        Some(&index) => Err(ModelError::IndexOutOfBounds {
+            mesh,
+            primitive,
+            index,
+            vertex_count,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Sets the normals to the area weighted average of the adjacent triangles
+fn compute_normals(vertices: &mut [NormalVertex], indices: &[u32]) {
+    let mut normals = vec![Vec3::ZERO; vertices.len()];
+
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize);
+        let normal = (vertices[b].pos - vertices[a].pos).cross(vertices[c].pos - vertices[a].pos);
+
+        for i in [a, b, c] {
+            normals[i] += normal;
+        }
+    }
+
+    for (v, normal) in vertices.iter_mut().zip(normals) {
+        v.normal = normal.normalize_or_zero();
+    }
+}
+
+pub struct ModelPrimitive {
+    pub mesh: Mesh,
+    pub material: Option<usize>,
+}
+
+/// A model uploaded to the gpu. The meshes use the [`NormalVertex`] format.
+pub struct Model {
+    meshes: Vec<Vec<ModelPrimitive>>,
+    materials: Vec<MaterialDesc>,
+    instances: Vec<MeshInstance>,
+}
+
+impl Model {
+    /// The primitives of each mesh
+    pub fn meshes(&self) -> &[Vec<ModelPrimitive>] {
+        &self.meshes
+    }
+
+    pub fn materials(&self) -> &[MaterialDesc] {
+        &self.materials
+    }
+
+    pub fn instances(&self) -> &[MeshInstance] {
+        &self.instances
+    }
+
+    /// Returns each primitive to draw with its transform relative to the model
+    pub fn primitives(&self) -> impl Iterator<Item = (&ModelPrimitive, Mat4)> {
+        self.instances.iter().flat_map(|instance| {
+            self.meshes[instance.mesh]
+                .iter()
+                .map(|primitive| (primitive, instance.transform))
+        })
+    }
+}
+
+/// Reads a file given its path
+pub type ModelResolver = Arc<dyn Fn(&str) -> anyhow::Result<Vec<u8>> + Send + Sync>;
+
+/// Imports a model as an asset, which is shared while it is alive.
+///
+/// The format is chosen by the extension of the path, which is one of `gltf`, `glb` or `obj`. The
+/// files the model refers to are resolved relative to it.
+///
+/// The path identifies the model in the cache, so all keys of a path need to resolve the same
+/// files. Uploading is left to the caller, as gpu resources can not be sent between threads on
+/// the web.
+#[derive(Clone)]
+pub struct ModelKey {
+    path: String,
+    resolve: ModelResolver,
+}
+
+impl ModelKey {
+    pub fn new(path: impl Into<String>, resolve: ModelResolver) -> Self {
+        Self {
+            path: path.into(),
+            resolve,
+        }
+    }
+
+    /// Parses the model on the cpu
+    pub fn load_data(&self) -> Result<ModelData, ModelError> {
+        let extension = self
+            .path
+            .rsplit_once('.')
+            .map(|(_, v)| v.to_ascii_lowercase());
+        let gltf = match extension.as_deref() {
+            Some("gltf" | "glb") => true,
+            Some("obj") => false,
+            _ => return Err(ModelError::Format(self.path.clone())),
+        };
+
+        let data = (self.resolve)(&self.path).map_err(|source| ModelError::Resolve {
+            uri: self.path.clone(),
+            source,
+        })?;
+
+        let resolve = |uri: &str| (self.resolve)(&relative_path(&self.path, uri));
+        if gltf {
+            ModelData::from_gltf(&data, resolve)
+        } else {
+            ModelData::from_obj(&data, resolve)
+        }
+    }
+}
+
+impl std::fmt::Debug for ModelKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ModelKey").field(&self.path).finish()
+    }
+}
+
+#[async_trait]
+impl AsyncAssetKey<Arc<ModelData>> for ModelKey {
+    async fn try_load(self, _: AssetCache) -> anyhow::Result<Arc<ModelData>> {
+        Ok(Arc::new(self.load_data()?))
+    }
+}
+
+/// Returns `uri` relative to the directory of `path`
+fn relative_path(path: &str, uri: &str) -> String {
+    match path.rsplit_once('/') {
+        Some((dir, _)) => format!("{dir}/{uri}"),
+        None => uri.into(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::vec3;
+
+    use super::*;
+
+    fn no_files(uri: &str) -> anyhow::Result<Vec<u8>> {
+        anyhow::bail!("Unexpected file {uri}")
+    }
+
+    /// A triangle as positions followed by u16 indices, padded to 4 bytes
+    fn triangle_buffer() -> Vec<u8> {
+        let positions: [f32; 9] = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        let indices: [u16; 4] = [0, 1, 2, 0];
+
+        [
+            bytemuck::cast_slice::<_, u8>(&positions),
+            bytemuck::cast_slice(&indices),
+        ]
+        .concat()
+    }
+
+    fn gltf_json(uri: &str) -> String {
+        format!(
+            r#"{{
+                "asset": {{ "version": "2.0" }},
+                "scene": 0,
+                "scenes": [{{ "nodes": [0] }}],
+                "nodes": [
+                    {{ "translation": [10.0, 0.0, 0.0], "children": [1], "mesh": 0 }},
+                    {{ "scale": [2.0, 2.0, 2.0], "mesh": 0 }}
+                ],
+                "meshes": [{{
+                    "name": "hull",
+                    "primitives": [
+                        {{ "attributes": {{ "POSITION": 0 }}, "indices": 1, "material": 0 }},
+                        {{ "attributes": {{ "POSITION": 0 }} }}
+                    ]
+                }}],
+                "materials": [{{
+                    "name": "paint",
+                    "pbrMetallicRoughness": {{
+                        "baseColorFactor": [1.0, 0.5, 0.25, 1.0],
+                        "baseColorTexture": {{ "index": 0 }},
+                        "metallicFactor": 0.5
+                    }}
+                }}],
+                "textures": [{{ "source": 0 }}],
+                "images": [{{ "uri": "hull.png" }}],
+                "buffers": [{{ "byteLength": 44, "uri": "{uri}" }}],
+                "bufferViews": [
+                    {{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }},
+                    {{ "buffer": 0, "byteOffset": 36, "byteLength": 6 }}
+                ],
+                "accessors": [
+                    {{
+                        "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                        "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0]
+                    }},
+                    {{ "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }}
+                ]
+            }}"#
+        )
+    }
+
+    #[test]
+    fn gltf() {
+        let data_uri = format!(
+            "data:application/octet-stream;base64,{}",
+            base64::engine::general_purpose::STANDARD.encode(triangle_buffer())
+        );
+
+        let model = ModelData::from_gltf(gltf_json(&data_uri).as_bytes(), no_files).unwrap();
+
+        assert_eq!(model.meshes.len(), 1);
+        let mesh = &model.meshes[0];
+        assert_eq!(mesh.name.as_deref(), Some("hull"));
+        assert_eq!(mesh.primitives.len(), 2);
+
+        let primitive = &mesh.primitives[0];
+        assert_eq!(primitive.indices, [0, 1, 2]);
+        assert_eq!(primitive.material, Some(0));
+        assert_eq!(primitive.vertices[1].pos, vec3(1.0, 0.0, 0.0));
+        // Computed from the counter-clockwise winding
+        assert_eq!(primitive.vertices[0].normal, Vec3::Z);
+
+        // Non-indexed primitives use the vertices in order
+        assert_eq!(mesh.primitives[1].indices, [0, 1, 2]);
+        assert_eq!(mesh.primitives[1].material, None);
+
+        let material = &model.materials[0];
+        assert_eq!(material.name.as_deref(), Some("paint"));
+        assert_eq!(material.base_color, Vec4::new(1.0, 0.5, 0.25, 1.0));
+        assert_eq!(material.metallic, 0.5);
+        assert_eq!(
+            material.base_color_texture,
+            Some(TextureRef::Uri("hull.png".into()))
+        );
+
+        // The child is scaled within its translated parent
+        assert_eq!(model.instances.len(), 2);
+        assert_eq!(
+            model.instances[0].transform,
+            Mat4::from_translation(vec3(10.0, 0.0, 0.0))
+        );
+        assert_eq!(
+            model.instances[1]
+                .transform
+                .transform_point3(vec3(1.0, 0.0, 0.0)),
+            vec3(12.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn gltf_separate_buffer() {
+        let json = gltf_json("hull.bin");
+
+        let model = ModelData::from_gltf(json.as_bytes(), |uri| {
+            assert_eq!(uri, "hull.bin");
+            Ok(triangle_buffer())
+        })
+        .unwrap();
+        assert_eq!(model.meshes[0].primitives[0].vertices.len(), 3);
+
+        let err = ModelData::from_gltf(json.as_bytes(), no_files).unwrap_err();
+        assert!(matches!(err, ModelError::Resolve { uri, .. } if uri == "hull.bin"));
+    }
+
+    #[test]
+    fn obj() {
+        let obj = "mtllib ship.mtl
+o wing
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+usemtl metal
+f 1/1 2/2 3/3 4/4
+";
+        let mtl = "newmtl metal
+Kd 0.5 0.5 0.5
+map_Kd metal.png
+";
+
+        let model = ModelData::from_obj(obj.as_bytes(), |uri| {
+            assert_eq!(uri, "ship.mtl");
+            Ok(mtl.as_bytes().to_vec())
+        })
+        .unwrap();
+
+        assert_eq!(model.meshes.len(), 1);
+        assert_eq!(model.meshes[0].name.as_deref(), Some("wing"));
+
+        // The quad is triangulated
+        let primitive = &model.meshes[0].primitives[0];
+        assert_eq!(primitive.indices.len(), 6);
+        assert_eq!(primitive.material, Some(0));
+        assert!(primitive.vertices.iter().all(|v| v.normal == Vec3::Z));
+        assert_eq!(primitive.vertices[0].tex_coords, Vec2::new(0.0, 1.0));
+
+        let material = &model.materials[0];
+        assert_eq!(material.base_color, Vec4::new(0.5, 0.5, 0.5, 1.0));
+        assert_eq!(
+            material.base_color_texture,
+            Some(TextureRef::Uri("metal.png".into()))
+        );
+
+        assert_eq!(model.instances[0].transform, Mat4::IDENTITY);
+
+        // Missing material libraries are skipped
+        let model = ModelData::from_obj(obj.as_bytes(), no_files).unwrap();
+        assert!(model.materials.is_empty());
+        assert_eq!(model.meshes[0].primitives[0].material, None);
+    }
+
+    #[test]
+    fn malformed() {
+        // An embedded image past the end of the 44 byte buffer
+        let json = gltf_json("hull.bin")
+            .replace(
+                r#"{ "uri": "hull.png" }"#,
+                r#"{ "bufferView": 2, "mimeType": "image/png" }"#,
+            )
+            .replace(
+                r#"{ "buffer": 0, "byteOffset": 36, "byteLength": 6 }"#,
+                r#"{ "buffer": 0, "byteOffset": 36, "byteLength": 6 },
+                { "buffer": 0, "byteOffset": 40, "byteLength": 16 }"#,
+            );
+        let err = ModelData::from_gltf(json.as_bytes(), |_| Ok(triangle_buffer())).unwrap_err();
+        assert!(matches!(
+            err,
+            ModelError::BufferView {
+                view: 2,
+                length: 44
+            }
+        ));
+
+        let mut buffer = triangle_buffer();
+        buffer[40..42].copy_from_slice(bytemuck::bytes_of(&5u16));
+        let err = ModelData::from_gltf(gltf_json("hull.bin").as_bytes(), |_| Ok(buffer.clone()))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ModelError::IndexOutOfBounds {
+                mesh: 0,
+                primitive: 0,
+                index: 5,
+                vertex_count: 3
+            }
+        ));
+
+        // More positions than fit the buffer view
+        let json = gltf_json("hull.bin").replace(
+            r#""count": 3, "type": "VEC3""#,
+            r#""count": 4, "type": "VEC3""#,
+        );
+        let err = ModelData::from_gltf(json.as_bytes(), |_| Ok(triangle_buffer())).unwrap_err();
+        assert!(matches!(
+            err,
+            ModelError::Accessor {
+                accessor: 0,
+                view: 0
+            }
+        ));
+
+        // A truncated buffer cuts off the indices, rather than leaving them out
+        let err = ModelData::from_gltf(gltf_json("hull.bin").as_bytes(), |_| {
+            Ok(triangle_buffer()[..40].to_vec())
+        })
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ModelError::BufferView {
+                view: 1,
+                length: 40
+            }
+        ));
+
+        // tobj rejects out of bounds faces itself
+        let obj = "o wing
+v 0 0 0
+v 1 0 0
+v 1 1 0
+f 1 2 9
+";
+        let err = ModelData::from_obj(obj.as_bytes(), no_files).unwrap_err();
+        assert!(matches!(err, ModelError::Obj(_)));
+    }
+}
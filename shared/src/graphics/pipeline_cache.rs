@@ -0,0 +1,487 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::{mpsc, OnceLock};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    sync::Arc,
+};
+
+use futures::FutureExt;
+use parking_lot::Mutex;
+use wgpu::{
+    BindGroupLayoutEntry, DepthStencilState, MultisampleState, PipelineLayoutDescriptor,
+    PrimitiveState, RenderPipeline, ShaderStages, VertexBufferLayout,
+};
+
+use super::{ColorTarget, ComposedShader, Gpu, ShaderDesc, ShaderError, ShaderLibrary};
+
+/// An owned copy of a [`ShaderDesc`], kept to rebuild the pipeline when its source changes
+#[derive(Debug, Clone)]
+struct PipelineState {
+    label: String,
+    source: ComposedShader,
+    vertex_entry_point: String,
+    fragment_entry_point: Option<String>,
+    targets: Vec<ColorTarget>,
+    vertex_layouts: Vec<VertexBufferLayout<'static>>,
+    layout_entries: Vec<Vec<BindGroupLayoutEntry>>,
+    primitive: PrimitiveState,
+    depth_stencil: Option<DepthStencilState>,
+    multisample: MultisampleState,
+}
+
+impl PipelineState {
+    fn new(desc: &ShaderDesc) -> Self {
+        Self {
+            label: desc.label.into(),
+            source: desc.source.clone(),
+            vertex_entry_point: desc.vertex_entry_point.into(),
+            fragment_entry_point: desc.fragment_entry_point.map(Into::into),
+            targets: desc.targets.clone(),
+            vertex_layouts: desc.vertex_layouts.to_vec(),
+            layout_entries: desc.layouts.iter().map(|v| v.entries().to_vec()).collect(),
+            primitive: desc.primitive,
+            depth_stencil: desc.depth_stencil.clone(),
+            multisample: desc.multisample.unwrap_or_default(),
+        }
+    }
+
+    fn key(&self) -> PipelineKey {
+        PipelineKey {
+            source: self.source.source().into(),
+            vertex_entry_point: self.vertex_entry_point.clone(),
+            fragment_entry_point: self.fragment_entry_point.clone(),
+            targets: self.targets.clone(),
+            vertex_layouts: self.vertex_layouts.clone(),
+            layout_entries: self.layout_entries.clone(),
+            primitive: self.primitive,
+            depth_stencil: self.depth_stencil.clone(),
+            multisample: self.multisample,
+        }
+    }
+
+    /// Checks the layouts against the shader's reflection
+    fn check(&self) -> Result<(), ShaderError> {
+        let reflection = self.source.reflect()?;
+
+        reflection.check_entry_point(&self.vertex_entry_point, ShaderStages::VERTEX)?;
+        if let Some(entry_point) = &self.fragment_entry_point {
+            reflection.check_entry_point(entry_point, ShaderStages::FRAGMENT)?;
+        }
+
+        if self.layout_entries.len() < reflection.group_count() as usize {
+            return Err(ShaderError::MissingBindGroups {
+                expected: reflection.group_count(),
+                provided: self.layout_entries.len(),
+            });
+        }
+
+        for (group, entries) in self.layout_entries.iter().enumerate() {
+            reflection.check_bind_group_layout(group as u32, entries)?;
+        }
+
+        reflection.check_vertex_layouts(&self.vertex_layouts)
+    }
+
+    /// Returns `true` if `err` was raised by creating this pipeline rather than by other use of
+    /// the device
+    fn raised(&self, err: &wgpu::Error) -> bool {
+        let description = err.to_string();
+        let label = format!("label = `{}`", self.label);
+
+        description.contains(&label)
+            && [
+                "Device::create_shader_module",
+                "Device::create_render_pipeline",
+            ]
+            .iter()
+            .any(|v| description.contains(v))
+    }
+
+    fn create(&self, device: &wgpu::Device, layout: &wgpu::PipelineLayout) -> RenderPipeline {
+        tracing::debug!(label = self.label, "Compiling pipeline");
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&self.label),
+            source: wgpu::ShaderSource::Wgsl(self.source.source().into()),
+        });
+
+        let targets = self
+            .targets
+            .iter()
+            .map(|&v| Some(v.into()))
+            .collect::<Vec<_>>();
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&self.label),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: &self.vertex_entry_point,
+                buffers: &self.vertex_layouts,
+            },
+            fragment: self
+                .fragment_entry_point
+                .as_ref()
+                .map(|entry_point| wgpu::FragmentState {
+                    module: &shader,
+                    entry_point,
+                    targets: &targets,
+                }),
+            primitive: self.primitive,
+            depth_stencil: self.depth_stencil.clone(),
+            multisample: self.multisample,
+            multiview: None,
+        })
+    }
+}
+
+/// Identifies pipelines which would be identical. The label is not part of the key.
+///
+/// Bind group layouts are compared by their entries, as layouts with equal entries are
+/// interchangeable.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct PipelineKey {
+    source: String,
+    vertex_entry_point: String,
+    fragment_entry_point: Option<String>,
+    targets: Vec<ColorTarget>,
+    vertex_layouts: Vec<VertexBufferLayout<'static>>,
+    layout_entries: Vec<Vec<BindGroupLayoutEntry>>,
+    primitive: PrimitiveState,
+    depth_stencil: Option<DepthStencilState>,
+    multisample: MultisampleState,
+}
+
+struct PipelineSlot {
+    state: Mutex<PipelineState>,
+    layout: wgpu::PipelineLayout,
+    /// `None` while compiling asynchronously, or if that failed
+    pipeline: Mutex<Option<Arc<RenderPipeline>>>,
+    /// The validation error of a failed compilation
+    error: Mutex<Option<String>>,
+    /// Set once a reload made this pipeline equal to another one, which is used from then on
+    merged: Mutex<Option<CachedPipeline>>,
+}
+
+/// A pipeline owned by a [`PipelineCache`].
+///
+/// The pipeline is replaced when a module it depends on is reloaded.
+#[derive(Clone)]
+pub struct CachedPipeline {
+    slot: Arc<PipelineSlot>,
+}
+
+impl CachedPipeline {
+    /// Returns the current pipeline, or `None` if it is still compiling
+    pub fn get(&self) -> Option<Arc<RenderPipeline>> {
+        self.slot().pipeline.lock().clone()
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.slot().pipeline.lock().is_some()
+    }
+
+    /// Returns the validation error if compiling in the background failed, in which case the
+    /// pipeline never becomes ready
+    pub fn error(&self) -> Option<String> {
+        self.slot().error.lock().clone()
+    }
+
+    /// Returns the slot this pipeline was merged into, or its own
+    fn slot(&self) -> Arc<PipelineSlot> {
+        let mut slot = self.slot.clone();
+        loop {
+            let merged = slot.merged.lock().as_ref().map(|v| v.slot.clone());
+            match merged {
+                Some(merged) => slot = merged,
+                None => return slot,
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for CachedPipeline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CachedPipeline")
+            .field("label", &self.slot().state.lock().label)
+            .field("ready", &self.is_ready())
+            .finish()
+    }
+}
+
+/// A pipeline to compile on the background thread of the cache
+#[cfg(not(target_arch = "wasm32"))]
+struct CompileJob {
+    gpu: Arc<Gpu>,
+    slot: Arc<PipelineSlot>,
+}
+
+/// Deduplicates render pipelines by their source and pipeline state
+#[derive(Default)]
+pub struct PipelineCache {
+    pipelines: Mutex<HashMap<PipelineKey, CachedPipeline>>,
+    /// Held while the cache has an error scope pushed. Error scopes are shared by the whole
+    /// device, so this keeps two compilations from catching each other's errors.
+    scope: Mutex<()>,
+    #[cfg(not(target_arch = "wasm32"))]
+    worker: OnceLock<mpsc::Sender<CompileJob>>,
+}
+
+impl PipelineCache {
+    /// Returns the pipeline for `desc`, compiling it if it is not already cached
+    pub fn get(
+        &self,
+        device: &wgpu::Device,
+        desc: &ShaderDesc,
+    ) -> Result<CachedPipeline, ShaderError> {
+        let (pipeline, compile) = self.get_or_insert(device, desc)?;
+        if compile {
+            let slot = pipeline.slot();
+            let state = slot.state.lock().clone();
+
+            match self.create(device, &state, &slot.layout) {
+                Ok(compiled) => *slot.pipeline.lock() = Some(Arc::new(compiled)),
+                Err(err) => {
+                    *slot.error.lock() = Some(err.clone());
+                    self.pipelines.lock().remove(&state.key());
+                    return Err(ShaderError::Pipeline(err));
+                }
+            }
+        }
+
+        Ok(pipeline)
+    }
+
+    /// Returns the pipeline for `desc`, compiling it in the background if it is not already
+    /// cached.
+    ///
+    /// The returned pipeline is not ready until compilation finishes. The layouts are checked
+    /// immediately. Pipelines are compiled one at a time on a single background thread, or
+    /// immediately on the web, where threads are not available.
+    pub fn get_async(gpu: &Arc<Gpu>, desc: &ShaderDesc) -> Result<CachedPipeline, ShaderError> {
+        let cache = gpu.pipeline_cache();
+        #[cfg(target_arch = "wasm32")]
+        {
+            cache.get(&gpu.device, desc)
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let (pipeline, compile) = cache.get_or_insert(&gpu.device, desc)?;
+            if compile {
+                let job = CompileJob {
+                    gpu: gpu.clone(),
+                    slot: pipeline.slot(),
+                };
+
+                cache
+                    .worker()
+                    .send(job)
+                    .expect("Pipeline compilation thread exited");
+            }
+
+            Ok(pipeline)
+        }
+    }
+
+    /// Returns the channel to the compilation thread, starting it if necessary
+    #[cfg(not(target_arch = "wasm32"))]
+    fn worker(&self) -> &mpsc::Sender<CompileJob> {
+        self.worker.get_or_init(|| {
+            let (tx, rx) = mpsc::channel::<CompileJob>();
+
+            std::thread::Builder::new()
+                .name("pipeline_compiler".into())
+                .spawn(move || {
+                    // Exits once the cache is dropped
+                    for CompileJob { gpu, slot } in rx {
+                        let state = slot.state.lock().clone();
+
+                        match gpu
+                            .pipeline_cache()
+                            .create(&gpu.device, &state, &slot.layout)
+                        {
+                            Ok(compiled) => {
+                                // A reload may have replaced it in the meantime
+                                slot.pipeline.lock().get_or_insert(Arc::new(compiled));
+                            }
+                            Err(err) => {
+                                tracing::error!(
+                                    label = state.label,
+                                    "Failed to compile pipeline:\n{err}"
+                                );
+                                *slot.error.lock() = Some(err);
+                            }
+                        }
+                    }
+                })
+                .expect("Failed to start the pipeline compilation thread");
+
+            tx
+        })
+    }
+
+    /// Creates the pipeline, returning the validation errors raised by doing so.
+    ///
+    /// Errors raised by other use of the device while the scope is pushed are logged instead,
+    /// as they do not belong to this pipeline.
+    fn create(
+        &self,
+        device: &wgpu::Device,
+        state: &PipelineState,
+        layout: &wgpu::PipelineLayout,
+    ) -> Result<RenderPipeline, String> {
+        let _scope = self.scope.lock();
+
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let pipeline = state.create(device, layout);
+
+        // Resolves immediately on native and WebGL. Polled rather than blocked on, as this may be
+        // called from within an executor.
+        let error = device
+            .pop_error_scope()
+            .now_or_never()
+            .expect("Error scopes resolve immediately on native and WebGL");
+
+        match error {
+            Some(err) if state.raised(&err) => Err(err.to_string()),
+            Some(err) => {
+                tracing::error!(
+                    label = state.label,
+                    "Validation error while compiling pipeline:\n{err}"
+                );
+                Ok(pipeline)
+            }
+            None => Ok(pipeline),
+        }
+    }
+
+    /// Returns the cached pipeline, or inserts an uncompiled slot and returns `true`
+    fn get_or_insert(
+        &self,
+        device: &wgpu::Device,
+        desc: &ShaderDesc,
+    ) -> Result<(CachedPipeline, bool), ShaderError> {
+        let state = PipelineState::new(desc);
+        let key = state.key();
+
+        if let Some(pipeline) = self.pipelines.lock().get(&key) {
+            return Ok((pipeline.clone(), false));
+        }
+
+        state.check()?;
+
+        let layouts = desc.layouts.iter().map(|v| &***v).collect::<Vec<_>>();
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some(desc.label),
+            bind_group_layouts: &layouts,
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = CachedPipeline {
+            slot: Arc::new(PipelineSlot {
+                state: Mutex::new(state),
+                layout,
+                pipeline: Mutex::new(None),
+                error: Mutex::new(None),
+                merged: Mutex::new(None),
+            }),
+        };
+
+        // Another thread may have inserted the same pipeline in the meantime
+        let mut pipelines = self.pipelines.lock();
+        if let Some(existing) = pipelines.get(&key) {
+            return Ok((existing.clone(), false));
+        }
+
+        pipelines.insert(key, pipeline.clone());
+        Ok((pipeline, true))
+    }
+
+    /// Rebuilds all pipelines which depend on `module`, using its current source in `library`.
+    ///
+    /// Pipelines which fail to rebuild keep their previous version, and the error is logged.
+    /// Pipelines which become equal to another cached pipeline are merged into it. Returns the
+    /// number of rebuilt pipelines.
+    pub fn reload(&self, device: &wgpu::Device, library: &ShaderLibrary, module: &str) -> usize {
+        let mut pipelines = self.pipelines.lock();
+
+        // Removed up front, as the key depends on the source. Otherwise a rebuilt pipeline could
+        // be merged into one which is yet to be rebuilt.
+        let affected = pipelines
+            .iter()
+            .filter(|(_, v)| v.slot.state.lock().source.modules().any(|v| v == module))
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+
+        let affected = affected
+            .into_iter()
+            .filter_map(|key| {
+                let pipeline = pipelines.remove(&key)?;
+                Some((key, pipeline))
+            })
+            .collect::<Vec<_>>();
+
+        let mut rebuilt = 0;
+        for (key, pipeline) in affected {
+            let slot = pipeline.slot();
+            let mut state = slot.state.lock();
+
+            let result = state.source.recompose(library).and_then(|source| {
+                let new_state = PipelineState {
+                    source,
+                    ..state.clone()
+                };
+
+                new_state.check()?;
+                Ok(new_state)
+            });
+
+            let key = match result {
+                // Merged below, without compiling it again
+                Ok(new_state) if pipelines.contains_key(&new_state.key()) => {
+                    *state = new_state;
+                    rebuilt += 1;
+                    state.key()
+                }
+                Ok(new_state) => match self.create(device, &new_state, &slot.layout) {
+                    Ok(compiled) => {
+                        *slot.pipeline.lock() = Some(Arc::new(compiled));
+                        *slot.error.lock() = None;
+                        *state = new_state;
+                        rebuilt += 1;
+                        state.key()
+                    }
+                    Err(err) => {
+                        tracing::error!(label = state.label, "Failed to reload pipeline:\n{err}");
+                        key
+                    }
+                },
+                Err(err) => {
+                    tracing::error!(label = state.label, "Failed to reload pipeline:\n{err}");
+                    key
+                }
+            };
+
+            drop(state);
+            match pipelines.entry(key) {
+                Entry::Occupied(existing) => *slot.merged.lock() = Some(existing.get().clone()),
+                Entry::Vacant(entry) => {
+                    entry.insert(pipeline);
+                }
+            }
+        }
+
+        tracing::info!(module, rebuilt, "Reloaded shader module");
+        rebuilt
+    }
+
+    pub fn len(&self) -> usize {
+        self.pipelines.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pipelines.lock().is_empty()
+    }
+}
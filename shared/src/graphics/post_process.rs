@@ -0,0 +1,360 @@
+//! Bloom, vignette and color grading, applied by fullscreen passes of a [`RenderGraph`].
+use std::sync::Arc;
+
+use bytemuck::{Pod, Zeroable};
+use glam::{vec4, Vec3, Vec4};
+use wgpu::{BindGroup, BufferUsages, TextureFormat};
+
+use super::{
+    AttachmentDesc, BindGroupBuilder, BindGroupLayout, BlendMode, ComposedShader, Gpu, GraphPass,
+    PassContext, PassDesc, RenderGraph, SamplerDesc, Shader, ShaderDefines, ShaderDesc,
+    ShaderError, ShaderLibrary, TypedBuffer,
+};
+
+/// Adds the glow of bright parts of the image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BloomSettings {
+    /// The brightness above which colors bloom, where colors above 1 are emissive
+    pub threshold: f32,
+    /// The width of the soft transition around the threshold
+    pub knee: f32,
+    /// The strength of the bloom added to the image, where 0 disables it
+    pub intensity: f32,
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        Self {
+            threshold: 1.0,
+            knee: 0.5,
+            intensity: 0.3,
+        }
+    }
+}
+
+/// Darkens the edges of the image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VignetteSettings {
+    /// How much the corners are darkened, where 0 disables the vignette
+    pub intensity: f32,
+    /// The distance from the center where the darkening starts, where 1 is a corner
+    pub radius: f32,
+    /// The distance over which the darkening fades in
+    pub smoothness: f32,
+}
+
+impl Default for VignetteSettings {
+    fn default() -> Self {
+        Self {
+            intensity: 0.25,
+            radius: 0.6,
+            smoothness: 0.6,
+        }
+    }
+}
+
+/// Adjusts the colors of the image, which are left unchanged by the default
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorGrading {
+    /// Exposure in stops, where each stop doubles the brightness. Lowering it brings back the
+    /// detail of colors above 1.
+    pub exposure: f32,
+    /// Contrast around middle grey
+    pub contrast: f32,
+    /// Where 0 is greyscale
+    pub saturation: f32,
+    /// Multiplied with the color
+    pub tint: Vec3,
+}
+
+impl Default for ColorGrading {
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            contrast: 1.0,
+            saturation: 1.0,
+            tint: Vec3::ONE,
+        }
+    }
+}
+
+/// Compresses the HDR colors of the scene into the range of the display
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Tonemapper {
+    /// Clamps colors to 1, which loses the detail of bright parts
+    None,
+    Reinhard,
+    /// The filmic look of the Academy Color Encoding System, with saturated highlights
+    #[default]
+    Aces,
+    /// Desaturates bright colors towards white, keeping their hue
+    AgX,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct PostProcessSettings {
+    pub bloom: BloomSettings,
+    pub vignette: VignetteSettings,
+    pub grading: ColorGrading,
+    pub tonemapper: Tonemapper,
+}
+
+/// Matches `PostProcessParams` in post_process.wgsl
+#[repr(C)]
+#[derive(Pod, Zeroable, Copy, Clone, Debug, PartialEq)]
+struct PostProcessParams {
+    bloom: Vec4,
+    vignette: Vec4,
+    grading: Vec4,
+    tint: Vec4,
+    tonemapper: u32,
+    _padding: [u32; 3],
+}
+
+impl From<&PostProcessSettings> for PostProcessParams {
+    fn from(settings: &PostProcessSettings) -> Self {
+        let PostProcessSettings {
+            bloom,
+            vignette,
+            grading,
+            tonemapper,
+        } = settings;
+
+        Self {
+            bloom: vec4(bloom.threshold, bloom.knee, bloom.intensity, 0.0),
+            vignette: vec4(
+                vignette.intensity,
+                vignette.radius,
+                vignette.smoothness,
+                0.0,
+            ),
+            grading: vec4(
+                grading.exposure.exp2(),
+                grading.contrast,
+                grading.saturation,
+                0.0,
+            ),
+            tint: grading.tint.extend(1.0),
+            tonemapper: *tonemapper as u32,
+            _padding: [0; 3],
+        }
+    }
+}
+
+/// Returns the WGSL modules used by the post-processing passes
+pub fn post_process_library() -> ShaderLibrary {
+    ShaderLibrary::new()
+        .with_module(
+            "fullscreen",
+            "assets/shaders/fullscreen.wgsl",
+            include_str!("../../../assets/shaders/fullscreen.wgsl"),
+        )
+        .with_module(
+            "post_process",
+            "assets/shaders/post_process.wgsl",
+            include_str!("../../../assets/shaders/post_process.wgsl"),
+        )
+        .with_module(
+            "tonemap",
+            "assets/shaders/tonemap.wgsl",
+            include_str!("../../../assets/shaders/tonemap.wgsl"),
+        )
+        .with_module(
+            "bloom",
+            "assets/shaders/bloom.wgsl",
+            include_str!("../../../assets/shaders/bloom.wgsl"),
+        )
+        .with_module(
+            "composite",
+            "assets/shaders/composite.wgsl",
+            include_str!("../../../assets/shaders/composite.wgsl"),
+        )
+}
+
+/// Draws a fullscreen triangle into `target`, sampling the `inputs`.
+///
+/// The shader binds the inputs in order in group 0, followed by a linear sampler and the post
+/// processing parameters.
+pub struct FullscreenPass {
+    label: String,
+    shader: Shader,
+    layout: BindGroupLayout,
+    inputs: Vec<String>,
+    target: String,
+    params: Arc<TypedBuffer<PostProcessParams>>,
+    sampler: Arc<wgpu::Sampler>,
+    /// The bind group, and the generation of the attachments it refers to
+    bind_group: Option<(u64, BindGroup)>,
+}
+
+impl FullscreenPass {
+    /// Returns the description of the pass for the graph
+    pub fn desc(&self) -> PassDesc {
+        self.inputs
+            .iter()
+            .fold(PassDesc::new(&self.label), |desc, v| desc.with_read(v))
+            .with_write(&self.target)
+    }
+}
+
+impl<T> GraphPass<T> for FullscreenPass {
+    fn run(&mut self, ctx: &mut PassContext, _: &mut T) {
+        let Some(pipeline) = self.shader.pipeline() else {
+            return;
+        };
+
+        let generation = ctx.generation();
+        if !matches!(&self.bind_group, Some((v, _)) if *v == generation) {
+            let mut builder = BindGroupBuilder::new(&self.label);
+            for input in &self.inputs {
+                builder.bind_texture(ctx.view(input));
+            }
+
+            let bind_group = builder
+                .bind_sampler(&self.sampler)
+                .bind_buffer(&self.params)
+                .build(ctx.gpu, &self.layout);
+
+            self.bind_group = Some((generation, bind_group));
+        }
+
+        let (_, bind_group) = self.bind_group.as_ref().unwrap();
+        ctx.draw_fullscreen(&self.label, &self.target, pipeline, bind_group);
+    }
+}
+
+/// The post-processing chain, turning the rendered scene into the final image
+pub struct PostProcess {
+    pub settings: PostProcessSettings,
+    library: ShaderLibrary,
+    params: Arc<TypedBuffer<PostProcessParams>>,
+    sampler: Arc<wgpu::Sampler>,
+}
+
+impl PostProcess {
+    pub fn new(gpu: &Gpu, settings: PostProcessSettings) -> Self {
+        let params = TypedBuffer::new(
+            gpu,
+            "post_process_params",
+            BufferUsages::UNIFORM,
+            &[PostProcessParams::from(&settings)],
+        );
+
+        Self {
+            settings,
+            library: post_process_library(),
+            params: Arc::new(params),
+            sampler: gpu.sampler(&SamplerDesc::linear()),
+        }
+    }
+
+    /// Adds the passes and attachments applying the effects to the `source` attachment and
+    /// writing the result to `target`, which has the given format.
+    ///
+    /// Colors are encoded to sRGB by the shader when the target format does not do so itself.
+    pub fn add_to_graph<T>(
+        &self,
+        gpu: &Gpu,
+        graph: &mut RenderGraph<T>,
+        source: &str,
+        (target, format): (&str, TextureFormat),
+    ) -> Result<(), ShaderError> {
+        let hdr_format = gpu.hdr_format();
+
+        // The blur is done at half resolution, which also widens it
+        for name in ["bloom_bright", "bloom_blur_x", "bloom"] {
+            graph.add_attachment(gpu, name, AttachmentDesc::new(hdr_format).with_scale(0.5));
+        }
+
+        let bloom = self.library.compose("bloom", &ShaderDefines::new())?;
+
+        let mut defines = ShaderDefines::new();
+        if !format.is_srgb() {
+            defines = defines.with_flag("ENCODE_SRGB");
+        }
+        let composite = self.library.compose("composite", &defines)?;
+
+        let passes = [
+            self.pass(
+                gpu,
+                "bloom_threshold",
+                &bloom,
+                "fs_threshold",
+                &[source],
+                ("bloom_bright", hdr_format),
+            )?,
+            self.pass(
+                gpu,
+                "bloom_blur_x",
+                &bloom,
+                "fs_blur_x",
+                &["bloom_bright"],
+                ("bloom_blur_x", hdr_format),
+            )?,
+            self.pass(
+                gpu,
+                "bloom_blur_y",
+                &bloom,
+                "fs_blur_y",
+                &["bloom_blur_x"],
+                ("bloom", hdr_format),
+            )?,
+            self.pass(
+                gpu,
+                "composite",
+                &composite,
+                "fs_main",
+                &[source, "bloom"],
+                (target, format),
+            )?,
+        ];
+
+        for pass in passes {
+            graph.add_pass(pass.desc(), pass);
+        }
+
+        Ok(())
+    }
+
+    fn pass(
+        &self,
+        gpu: &Gpu,
+        label: &str,
+        source: &ComposedShader,
+        entry_point: &str,
+        inputs: &[&str],
+        (target, format): (&str, TextureFormat),
+    ) -> Result<FullscreenPass, ShaderError> {
+        let layout = source
+            .reflect()?
+            .bind_group_layout(format!("{label}_bind_group_layout"), 0)
+            .build(gpu);
+
+        let shader = Shader::new(
+            gpu,
+            ShaderDesc::new(label, source)
+                .with_entry_points("vs_main", Some(entry_point))
+                .with_target(format, BlendMode::Opaque)
+                .with_depth_stencil(None)
+                .with_sample_count(1)
+                .with_layouts(&[&layout]),
+        )?;
+
+        Ok(FullscreenPass {
+            label: label.into(),
+            shader,
+            layout,
+            inputs: inputs.iter().map(|v| v.to_string()).collect(),
+            target: target.into(),
+            params: self.params.clone(),
+            sampler: self.sampler.clone(),
+            bind_group: None,
+        })
+    }
+
+    /// Uploads the current settings, to be called before the graph is executed
+    pub fn write(&self, gpu: &Gpu) {
+        self.params
+            .write(&gpu.queue, &[PostProcessParams::from(&self.settings)]);
+    }
+}
@@ -0,0 +1,607 @@
+//! Composition of WGSL shaders from named modules.
+//!
+//! Modules are registered in a [`ShaderLibrary`] and may contain the following directives, each
+//! on a line of its own:
+//!
+//! - `#import name` includes the module `name`. Each module is included at most once.
+//! - `#define NAME [value]` defines `NAME`. Occurrences of `NAME` in the following source are
+//!   replaced by `value`, if given.
+//! - `#undef NAME`
+//! - `#ifdef NAME`, `#ifndef NAME`, `#else` and `#endif` conditionally include source.
+//!
+//! The composed source keeps track of where each line originated, so that errors are reported
+//! against the original module and line.
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+};
+
+use naga::valid::{Capabilities, ModuleInfo, ValidationFlags, Validator};
+use wgpu::ShaderStages;
+
+use super::ShaderReflection;
+
+#[derive(Debug, Clone)]
+struct ModuleSource {
+    path: String,
+    source: String,
+}
+
+/// A registry of WGSL modules which can be imported by name
+#[derive(Default, Debug, Clone)]
+pub struct ShaderLibrary {
+    modules: HashMap<String, ModuleSource>,
+}
+
+impl ShaderLibrary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a module, replacing any previous module of the same name.
+    ///
+    /// `path` is only used for error reporting.
+    pub fn add_module(
+        &mut self,
+        name: impl Into<String>,
+        path: impl Into<String>,
+        source: impl Into<String>,
+    ) -> &mut Self {
+        self.modules.insert(
+            name.into(),
+            ModuleSource {
+                path: path.into(),
+                source: source.into(),
+            },
+        );
+        self
+    }
+
+    pub fn with_module(
+        mut self,
+        name: impl Into<String>,
+        path: impl Into<String>,
+        source: impl Into<String>,
+    ) -> Self {
+        self.add_module(name, path, source);
+        self
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.modules.contains_key(name)
+    }
+
+    /// Replaces the source of an existing module.
+    ///
+    /// Returns false if the module does not exist or the source is unchanged.
+    pub fn update_module(&mut self, name: &str, source: impl Into<String>) -> bool {
+        let Some(module) = self.modules.get_mut(name) else {
+            return false;
+        };
+
+        let source = source.into();
+        if module.source == source {
+            return false;
+        }
+
+        module.source = source;
+        true
+    }
+
+    /// Re-reads all modules from their paths, and returns the names of the modules which changed.
+    ///
+    /// Modules whose path can not be read, such as when running outside of the workspace, are
+    /// skipped.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn reload_modified(&mut self) -> Vec<String> {
+        let modified = self
+            .modules
+            .iter()
+            .filter_map(|(name, module)| {
+                let source = std::fs::read_to_string(&module.path).ok()?;
+                (source != module.source).then(|| (name.clone(), source))
+            })
+            .collect::<Vec<_>>();
+
+        modified
+            .into_iter()
+            .map(|(name, source)| {
+                tracing::info!(name, "Shader module changed on disk");
+                self.update_module(&name, source);
+                name
+            })
+            .collect()
+    }
+
+    /// Resolves the imports and conditionals of the module `name`
+    pub fn compose(
+        &self,
+        name: &str,
+        defines: &ShaderDefines,
+    ) -> Result<ComposedShader, ShaderError> {
+        if !self.contains(name) {
+            return Err(ShaderError::UnknownModule(name.to_string()));
+        }
+
+        let mut composer = Composer {
+            library: self,
+            defines: defines.clone(),
+            stack: Vec::new(),
+            output: ComposedShader {
+                defines: defines.clone(),
+                ..Default::default()
+            },
+        };
+
+        composer.compose_module(name)?;
+
+        Ok(composer.output)
+    }
+}
+
+/// Defines used when composing a shader, such as to select between variants
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ShaderDefines {
+    defines: BTreeMap<String, String>,
+}
+
+impl ShaderDefines {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Defines `name` without a value, for use with `#ifdef`
+    pub fn with_flag(mut self, name: impl Into<String>) -> Self {
+        self.defines.insert(name.into(), String::new());
+        self
+    }
+
+    /// Defines `name` to be substituted by `value`
+    pub fn with_value(mut self, name: impl Into<String>, value: impl Display) -> Self {
+        self.defines.insert(name.into(), value.to_string());
+        self
+    }
+
+    pub fn is_defined(&self, name: &str) -> bool {
+        self.defines.contains_key(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.defines.get(name).map(|v| v.as_str())
+    }
+
+    /// Replaces all identifiers which have a non-empty value
+    fn substitute<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        if self.defines.values().all(|v| v.is_empty()) {
+            return line.into();
+        }
+
+        let mut result = String::with_capacity(line.len());
+        let mut rest = line;
+
+        while let Some(start) = rest.find(is_ident_start) {
+            let (before, ident) = rest.split_at(start);
+            let end = ident
+                .find(|c: char| !is_ident_continue(c))
+                .unwrap_or(ident.len());
+            let (ident, after) = ident.split_at(end);
+
+            result.push_str(before);
+            match self.defines.get(ident) {
+                Some(value) if !value.is_empty() => result.push_str(value),
+                _ => result.push_str(ident),
+            }
+
+            rest = after;
+        }
+
+        result.push_str(rest);
+        result.into()
+    }
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_ident_continue(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// A location in a module of a [`ShaderLibrary`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub module: String,
+    pub path: String,
+    /// 1-based line number
+    pub line: usize,
+}
+
+impl Display for SourceLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.path, self.line)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ShaderError {
+    #[error("Unknown shader module {0:?}")]
+    UnknownModule(String),
+    #[error("{location}: {message}")]
+    Source {
+        location: SourceLocation,
+        message: String,
+    },
+    #[error("Binding {binding} of group {group} ({name}): {message}")]
+    BindingMismatch {
+        group: u32,
+        binding: u32,
+        name: String,
+        message: String,
+    },
+    #[error("Missing {stage:?} entry point {name:?}")]
+    MissingEntryPoint { name: String, stage: ShaderStages },
+    #[error("The shader uses {expected} bind groups, but only {provided} layouts were provided")]
+    MissingBindGroups { expected: u32, provided: usize },
+    #[error("Vertex input {location} ({name}): {message}")]
+    VertexMismatch {
+        location: u32,
+        name: String,
+        message: String,
+    },
+    /// The shader passed the checks of naga, but the pipeline failed the validation of wgpu
+    #[error("Failed to create pipeline: {0}")]
+    Pipeline(String),
+}
+
+impl ShaderError {
+    pub fn location(&self) -> Option<&SourceLocation> {
+        match self {
+            ShaderError::Source { location, .. } => Some(location),
+            _ => None,
+        }
+    }
+}
+
+/// WGSL source composed from one or more modules
+#[derive(Default, Debug, Clone)]
+pub struct ComposedShader {
+    source: String,
+    /// The modules which were included, in order of inclusion
+    modules: Vec<(String, String)>,
+    /// Module index and line number for each line of the output
+    lines: Vec<(usize, usize)>,
+    defines: ShaderDefines,
+}
+
+impl ComposedShader {
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Returns the name of the module which was composed
+    pub fn name(&self) -> &str {
+        &self.modules[0].0
+    }
+
+    /// Returns the defines the shader was composed with
+    pub fn defines(&self) -> &ShaderDefines {
+        &self.defines
+    }
+
+    /// Composes the same module with the same defines again, such as after a module changed
+    pub fn recompose(&self, library: &ShaderLibrary) -> Result<ComposedShader, ShaderError> {
+        library.compose(self.name(), &self.defines)
+    }
+
+    /// Returns the names of all modules which the shader was composed from
+    pub fn modules(&self) -> impl Iterator<Item = &str> {
+        self.modules.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Maps a 1-based line of the composed source back to its original module and line
+    pub fn location(&self, line: usize) -> Option<SourceLocation> {
+        let &(module, line) = self.lines.get(line.checked_sub(1)?)?;
+        let (name, path) = &self.modules[module];
+
+        Some(SourceLocation {
+            module: name.clone(),
+            path: path.clone(),
+            line,
+        })
+    }
+
+    /// Parses and validates the composed source using naga
+    pub fn validate(&self) -> Result<(naga::Module, ModuleInfo), ShaderError> {
+        let module = naga::front::wgsl::parse_str(&self.source).map_err(|err| {
+            self.error(
+                err.location(&self.source).map(|v| v.line_number),
+                err.to_string(),
+            )
+        })?;
+
+        let info = Validator::new(ValidationFlags::all(), Capabilities::all())
+            .validate(&module)
+            .map_err(|err| {
+                self.error(
+                    err.location(&self.source).map(|v| v.line_number),
+                    err.to_string(),
+                )
+            })?;
+
+        Ok((module, info))
+    }
+
+    /// Validates the source and reflects its bindings and vertex inputs
+    pub fn reflect(&self) -> Result<ShaderReflection, ShaderError> {
+        let (module, info) = self.validate()?;
+        ShaderReflection::new(&module, &info)
+    }
+
+    fn error(&self, line: Option<u32>, message: String) -> ShaderError {
+        // Errors without a span are attributed to the root module
+        let location = line
+            .and_then(|line| self.location(line as usize))
+            .or_else(|| {
+                let (module, path) = self.modules.first()?;
+                Some(SourceLocation {
+                    module: module.clone(),
+                    path: path.clone(),
+                    line: 1,
+                })
+            })
+            .expect("Composed shader has no modules");
+
+        ShaderError::Source { location, message }
+    }
+}
+
+struct Condition {
+    /// Whether the enclosing block is active
+    parent_active: bool,
+    taken: bool,
+    has_else: bool,
+    line: usize,
+}
+
+struct Composer<'a> {
+    library: &'a ShaderLibrary,
+    defines: ShaderDefines,
+    stack: Vec<&'a str>,
+    output: ComposedShader,
+}
+
+impl<'a> Composer<'a> {
+    fn compose_module(&mut self, name: &'a str) -> Result<(), ShaderError> {
+        let (name, module) = self
+            .library
+            .modules
+            .get_key_value(name)
+            .expect("Module existence is checked by the caller");
+
+        let index = self.output.modules.len();
+        self.output
+            .modules
+            .push((name.clone(), module.path.clone()));
+        self.stack.push(name);
+
+        let location = |line: usize| SourceLocation {
+            module: name.clone(),
+            path: module.path.clone(),
+            line,
+        };
+
+        let error = |line: usize, message: String| ShaderError::Source {
+            location: location(line),
+            message,
+        };
+
+        let mut conditions: Vec<Condition> = Vec::new();
+
+        for (i, text) in module.source.lines().enumerate() {
+            let line = i + 1;
+            let active = conditions
+                .last()
+                .is_none_or(|v| v.parent_active && v.taken != v.has_else);
+
+            let Some(directive) = text.trim_start().strip_prefix('#') else {
+                if active {
+                    self.output.source.push_str(&self.defines.substitute(text));
+                    self.output.source.push('\n');
+                    self.output.lines.push((index, line));
+                }
+                continue;
+            };
+
+            let mut args = directive.split_whitespace();
+            let directive = args.next().unwrap_or_default();
+            let mut arg = || {
+                args.next()
+                    .ok_or_else(|| error(line, format!("Missing argument to #{directive}")))
+            };
+
+            match directive {
+                "ifdef" | "ifndef" => {
+                    let defined = self.defines.is_defined(arg()?);
+                    conditions.push(Condition {
+                        parent_active: active,
+                        taken: defined == (directive == "ifdef"),
+                        has_else: false,
+                        line,
+                    });
+                }
+                "else" => {
+                    let condition = conditions
+                        .last_mut()
+                        .ok_or_else(|| error(line, "#else without #ifdef".into()))?;
+
+                    if condition.has_else {
+                        return Err(error(
+                            line,
+                            format!("Duplicate #else for #ifdef on line {}", condition.line),
+                        ));
+                    }
+
+                    condition.has_else = true;
+                }
+                "endif" => {
+                    conditions
+                        .pop()
+                        .ok_or_else(|| error(line, "#endif without #ifdef".into()))?;
+                }
+                _ if !active => {}
+                "define" => {
+                    let name = arg()?.to_string();
+                    let value = args.collect::<Vec<_>>().join(" ");
+                    self.defines.defines.insert(name, value);
+                }
+                "undef" => {
+                    self.defines.defines.remove(arg()?);
+                }
+                "import" => {
+                    let import = arg()?;
+                    let Some((import, _)) = self.library.modules.get_key_value(import) else {
+                        return Err(error(line, format!("Unknown module {import:?}")));
+                    };
+
+                    if self.stack.contains(&import.as_str()) {
+                        let cycle = self
+                            .stack
+                            .iter()
+                            .copied()
+                            .chain([import.as_str()])
+                            .collect::<Vec<_>>()
+                            .join(" -> ");
+
+                        return Err(error(line, format!("Import cycle: {cycle}")));
+                    }
+
+                    if !self.output.modules().any(|v| v == import) {
+                        self.compose_module(import)?;
+                    }
+                }
+                _ => return Err(error(line, format!("Unknown directive #{directive}"))),
+            }
+        }
+
+        if let Some(condition) = conditions.last() {
+            return Err(error(
+                condition.line,
+                "#ifdef without matching #endif".into(),
+            ));
+        }
+
+        self.stack.pop();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn library() -> ShaderLibrary {
+        ShaderLibrary::new()
+            .with_module(
+                "camera",
+                "camera.wgsl",
+                "struct Camera {\n    view: mat4x4<f32>,\n}\n",
+            )
+            .with_module(
+                "objects",
+                "objects.wgsl",
+                "#import camera\n#ifdef STORAGE\nvar<storage> objects: array<f32>;\n#else\nvar<uniform> objects: array<vec4<f32>, MAX_OBJECTS>;\n#endif\n",
+            )
+            .with_module(
+                "main",
+                "main.wgsl",
+                "#import camera\n#import objects\n\nfn main() {}\n",
+            )
+    }
+
+    #[test]
+    fn compose() {
+        let library = library();
+
+        let shader = library
+            .compose("main", &ShaderDefines::new().with_value("MAX_OBJECTS", 128))
+            .unwrap();
+
+        assert_eq!(
+            shader.source(),
+            "struct Camera {\n    view: mat4x4<f32>,\n}\nvar<uniform> objects: array<vec4<f32>, 128>;\n\nfn main() {}\n"
+        );
+        assert_eq!(
+            shader.modules().collect::<Vec<_>>(),
+            ["main", "camera", "objects"]
+        );
+
+        let shader = library
+            .compose("main", &ShaderDefines::new().with_flag("STORAGE"))
+            .unwrap();
+
+        assert_eq!(
+            shader.source(),
+            "struct Camera {\n    view: mat4x4<f32>,\n}\nvar<storage> objects: array<f32>;\n\nfn main() {}\n"
+        );
+
+        assert_eq!(
+            shader.location(4),
+            Some(SourceLocation {
+                module: "objects".into(),
+                path: "objects.wgsl".into(),
+                line: 3
+            })
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let library = library()
+            .with_module("a", "a.wgsl", "#import b\n")
+            .with_module("b", "b.wgsl", "\n#import a\n")
+            .with_module("unbalanced", "unbalanced.wgsl", "#ifdef A\n")
+            .with_module("missing", "missing.wgsl", "\n\n#import nothing\n");
+
+        let err = library.compose("a", &Default::default()).unwrap_err();
+        assert_eq!(err.to_string(), "b.wgsl:2: Import cycle: a -> b -> a");
+
+        let err = library
+            .compose("unbalanced", &Default::default())
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unbalanced.wgsl:1: #ifdef without matching #endif"
+        );
+
+        let err = library.compose("missing", &Default::default()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "missing.wgsl:3: Unknown module \"nothing\""
+        );
+    }
+
+    #[test]
+    fn validation_error() {
+        let library = ShaderLibrary::new()
+            .with_module("types", "types.wgsl", "struct Foo {\n    a: f32,\n}\n")
+            .with_module(
+                "main",
+                "main.wgsl",
+                "#import types\n\nfn main() -> f32 {\n    return undefined;\n}\n",
+            );
+
+        let shader = library.compose("main", &Default::default()).unwrap();
+        let err = shader.validate().unwrap_err();
+
+        assert_eq!(
+            err.location(),
+            Some(&SourceLocation {
+                module: "main".into(),
+                path: "main.wgsl".into(),
+                line: 4
+            })
+        );
+    }
+}
@@ -0,0 +1,673 @@
+//! Derives the resources a shader expects from its naga module.
+use std::{borrow::Cow, collections::BTreeMap, num::NonZeroU64};
+
+use naga::{
+    valid::{GlobalUse, ModuleInfo},
+    AddressSpace, Binding, ImageClass, ImageDimension, ScalarKind, StorageAccess, StorageFormat,
+    TypeInner,
+};
+use wgpu::{
+    BindGroupLayoutEntry, BindingType, BufferBindingType, SamplerBindingType, ShaderStages,
+    StorageTextureAccess, TextureFormat, TextureSampleType, TextureViewDimension,
+    VertexBufferLayout, VertexFormat,
+};
+
+use super::{BindGroupLayoutBuilder, ShaderError};
+
+/// A resource binding declared by a shader
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReflectedBinding {
+    pub name: String,
+    pub entry: BindGroupLayoutEntry,
+    /// Whether the shader writes to the resource
+    pub writes: bool,
+}
+
+/// An input of a vertex entry point
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VertexInput {
+    pub location: u32,
+    pub kind: ScalarKind,
+    pub components: u32,
+}
+
+/// The bindings and vertex inputs of a shader
+#[derive(Debug, Clone, Default)]
+pub struct ShaderReflection {
+    groups: BTreeMap<u32, Vec<ReflectedBinding>>,
+    vertex_inputs: BTreeMap<u32, (String, VertexInput)>,
+    /// The workgroup size is zero for stages other than compute
+    entry_points: Vec<(String, ShaderStages, [u32; 3])>,
+}
+
+impl ShaderReflection {
+    pub fn new(module: &naga::Module, info: &ModuleInfo) -> Result<Self, ShaderError> {
+        let mut groups: BTreeMap<u32, Vec<ReflectedBinding>> = BTreeMap::new();
+
+        for (handle, var) in module.global_variables.iter() {
+            let Some(binding) = &var.binding else {
+                continue;
+            };
+
+            let name = var.name.clone().unwrap_or_default();
+
+            // Only bindings which are used by an entry point are required in the layout
+            let mut visibility = ShaderStages::NONE;
+            let mut uses = GlobalUse::empty();
+            for (i, entry_point) in module.entry_points.iter().enumerate() {
+                let usage = info.get_entry_point(i)[handle];
+                if !usage.is_empty() {
+                    visibility |= stage(entry_point.stage);
+                    uses |= usage;
+                }
+            }
+
+            if visibility.is_empty() {
+                continue;
+            }
+
+            let mismatch = |message: String| ShaderError::BindingMismatch {
+                group: binding.group,
+                binding: binding.binding,
+                name: name.clone(),
+                message,
+            };
+
+            let (ty, count) = match module.types[var.ty].inner {
+                TypeInner::BindingArray { base, size } => (base, Some(size)),
+                _ => (var.ty, None),
+            };
+
+            let count = match count {
+                Some(naga::ArraySize::Constant(size)) => Some(size),
+                Some(naga::ArraySize::Dynamic) => {
+                    return Err(mismatch(
+                        "Runtime sized binding arrays are not supported".into(),
+                    ))
+                }
+                None => None,
+            };
+
+            let min_binding_size =
+                || NonZeroU64::new(module.types[ty].inner.size(module.to_ctx()) as u64);
+
+            let ty = match (var.space, &module.types[ty].inner) {
+                (AddressSpace::Uniform, _) => BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: min_binding_size(),
+                },
+                (AddressSpace::Storage { access }, _) => BindingType::Buffer {
+                    ty: BufferBindingType::Storage {
+                        read_only: !access.contains(StorageAccess::STORE),
+                    },
+                    has_dynamic_offset: false,
+                    min_binding_size: min_binding_size(),
+                },
+                (AddressSpace::Handle, &TypeInner::Sampler { comparison }) => {
+                    BindingType::Sampler(if comparison {
+                        SamplerBindingType::Comparison
+                    } else {
+                        SamplerBindingType::Filtering
+                    })
+                }
+                (
+                    AddressSpace::Handle,
+                    &TypeInner::Image {
+                        dim,
+                        arrayed,
+                        class,
+                    },
+                ) => {
+                    let view_dimension = view_dimension(dim, arrayed);
+                    match class {
+                        ImageClass::Sampled { kind, multi } => BindingType::Texture {
+                            sample_type: match kind {
+                                ScalarKind::Sint => TextureSampleType::Sint,
+                                ScalarKind::Uint => TextureSampleType::Uint,
+                                _ => TextureSampleType::Float { filterable: true },
+                            },
+                            view_dimension,
+                            multisampled: multi,
+                        },
+                        ImageClass::Depth { multi } => BindingType::Texture {
+                            sample_type: TextureSampleType::Depth,
+                            view_dimension,
+                            multisampled: multi,
+                        },
+                        ImageClass::Storage { format, access } => BindingType::StorageTexture {
+                            access: match (
+                                access.contains(StorageAccess::LOAD),
+                                access.contains(StorageAccess::STORE),
+                            ) {
+                                (true, true) => StorageTextureAccess::ReadWrite,
+                                (true, false) => StorageTextureAccess::ReadOnly,
+                                _ => StorageTextureAccess::WriteOnly,
+                            },
+                            format: texture_format(format),
+                            view_dimension,
+                        },
+                    }
+                }
+                (space, _) => {
+                    return Err(mismatch(format!(
+                        "Unsupported address space {space:?} for a resource binding"
+                    )))
+                }
+            };
+
+            groups
+                .entry(binding.group)
+                .or_default()
+                .push(ReflectedBinding {
+                    name,
+                    entry: BindGroupLayoutEntry {
+                        binding: binding.binding,
+                        visibility,
+                        ty,
+                        count,
+                    },
+                    writes: uses.contains(GlobalUse::WRITE),
+                });
+        }
+
+        for bindings in groups.values_mut() {
+            bindings.sort_by_key(|v| v.entry.binding);
+        }
+
+        let mut vertex_inputs = BTreeMap::new();
+        for entry_point in &module.entry_points {
+            if entry_point.stage != naga::ShaderStage::Vertex {
+                continue;
+            }
+
+            for arg in &entry_point.function.arguments {
+                let name = arg.name.clone().unwrap_or_default();
+                match &module.types[arg.ty].inner {
+                    TypeInner::Struct { members, .. } => {
+                        for member in members {
+                            let name = member.name.clone().unwrap_or_else(|| name.clone());
+                            if let Some(input) = vertex_input(
+                                member.binding.as_ref(),
+                                &module.types[member.ty].inner,
+                            ) {
+                                vertex_inputs.insert(input.location, (name, input));
+                            }
+                        }
+                    }
+                    ty => {
+                        if let Some(input) = vertex_input(arg.binding.as_ref(), ty) {
+                            vertex_inputs.insert(input.location, (name, input));
+                        }
+                    }
+                }
+            }
+        }
+
+        let entry_points = module
+            .entry_points
+            .iter()
+            .map(|v| (v.name.clone(), stage(v.stage), v.workgroup_size))
+            .collect();
+
+        Ok(Self {
+            groups,
+            vertex_inputs,
+            entry_points,
+        })
+    }
+
+    /// Returns the number of bind groups the pipeline layout needs
+    pub fn group_count(&self) -> u32 {
+        self.groups.keys().last().map_or(0, |v| v + 1)
+    }
+
+    /// Returns the bindings of `group` used by any entry point, ordered by binding
+    pub fn bindings(&self, group: u32) -> &[ReflectedBinding] {
+        self.groups.get(&group).map_or(&[], |v| v.as_slice())
+    }
+
+    /// Returns the layout entries expected by the shader for `group`
+    pub fn bind_group_layout_entries(&self, group: u32) -> Vec<BindGroupLayoutEntry> {
+        self.bindings(group).iter().map(|v| v.entry).collect()
+    }
+
+    /// Creates a layout builder matching `group`
+    pub fn bind_group_layout(
+        &self,
+        label: impl Into<Cow<'static, str>>,
+        group: u32,
+    ) -> BindGroupLayoutBuilder {
+        let mut builder = BindGroupLayoutBuilder::new(label);
+        for binding in self.bindings(group) {
+            builder.push(binding.entry);
+        }
+        builder
+    }
+
+    pub fn vertex_inputs(&self) -> impl Iterator<Item = &VertexInput> {
+        self.vertex_inputs.values().map(|(_, v)| v)
+    }
+
+    pub fn entry_points(&self) -> impl Iterator<Item = (&str, ShaderStages)> {
+        self.entry_points
+            .iter()
+            .map(|(name, stage, _)| (name.as_str(), *stage))
+    }
+
+    /// Returns the workgroup size of a compute entry point
+    pub fn workgroup_size(&self, entry_point: &str) -> Option<[u32; 3]> {
+        self.entry_points
+            .iter()
+            .find(|(name, stage, _)| name == entry_point && *stage == ShaderStages::COMPUTE)
+            .map(|(_, _, size)| *size)
+    }
+
+    pub fn check_entry_point(&self, name: &str, stage: ShaderStages) -> Result<(), ShaderError> {
+        if self.entry_points().any(|v| v == (name, stage)) {
+            Ok(())
+        } else {
+            Err(ShaderError::MissingEntryPoint {
+                name: name.into(),
+                stage,
+            })
+        }
+    }
+
+    /// Checks that a layout provides every binding used by the shader in `group`
+    pub fn check_bind_group_layout(
+        &self,
+        group: u32,
+        entries: &[BindGroupLayoutEntry],
+    ) -> Result<(), ShaderError> {
+        for expected in self.bindings(group) {
+            let mismatch = |message: String| ShaderError::BindingMismatch {
+                group,
+                binding: expected.entry.binding,
+                name: expected.name.clone(),
+                message,
+            };
+
+            let Some(entry) = entries.iter().find(|v| v.binding == expected.entry.binding) else {
+                return Err(mismatch("Missing from the bind group layout".into()));
+            };
+
+            if !entry.visibility.contains(expected.entry.visibility) {
+                return Err(mismatch(format!(
+                    "Used in {:?}, but only visible in {:?}",
+                    expected.entry.visibility, entry.visibility
+                )));
+            }
+
+            if entry.count != expected.entry.count {
+                return Err(mismatch(format!(
+                    "Expected an array of {:?}, but the layout has {:?}",
+                    expected.entry.count, entry.count
+                )));
+            }
+
+            if !binding_compatible(&expected.entry.ty, &entry.ty, expected.writes) {
+                return Err(mismatch(format!(
+                    "Expected {:?}, but the layout has {:?}",
+                    expected.entry.ty, entry.ty
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the vertex buffers provide every input of the vertex entry points
+    pub fn check_vertex_layouts(&self, layouts: &[VertexBufferLayout]) -> Result<(), ShaderError> {
+        for (name, input) in self.vertex_inputs.values() {
+            let mismatch = |message: String| ShaderError::VertexMismatch {
+                location: input.location,
+                name: name.clone(),
+                message,
+            };
+
+            let Some(attribute) = layouts
+                .iter()
+                .flat_map(|v| v.attributes)
+                .find(|v| v.shader_location == input.location)
+            else {
+                return Err(mismatch("Missing from the vertex buffer layouts".into()));
+            };
+
+            let (kind, components) = format_type(attribute.format);
+            if kind != input.kind || components != input.components {
+                return Err(mismatch(format!(
+                    "Expected {} {:?} components, but the vertex format is {:?}",
+                    input.components, input.kind, attribute.format
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns true if a layout entry of type `provided` can be used for a binding of type `expected`
+fn binding_compatible(expected: &BindingType, provided: &BindingType, writes: bool) -> bool {
+    match (expected, provided) {
+        (
+            BindingType::Buffer {
+                ty: expected_ty,
+                min_binding_size: expected_size,
+                ..
+            },
+            BindingType::Buffer {
+                ty: provided_ty,
+                min_binding_size: provided_size,
+                ..
+            },
+        ) => {
+            let ty = match (expected_ty, provided_ty) {
+                (BufferBindingType::Uniform, BufferBindingType::Uniform) => true,
+                (BufferBindingType::Storage { .. }, BufferBindingType::Storage { read_only }) => {
+                    !(writes && *read_only)
+                }
+                _ => false,
+            };
+
+            // A missing size is validated when binding instead
+            let size = match (expected_size, provided_size) {
+                (Some(expected), Some(provided)) => provided >= expected,
+                _ => true,
+            };
+
+            ty && size
+        }
+        (
+            BindingType::Texture {
+                sample_type: expected_sample,
+                view_dimension: expected_dimension,
+                multisampled: expected_multisampled,
+            },
+            BindingType::Texture {
+                sample_type: provided_sample,
+                view_dimension: provided_dimension,
+                multisampled: provided_multisampled,
+            },
+        ) => {
+            // Filterability and depth can not be inferred from the shader alone
+            let sample = matches!(
+                (expected_sample, provided_sample),
+                (
+                    TextureSampleType::Float { .. },
+                    TextureSampleType::Float { .. } | TextureSampleType::Depth
+                ) | (TextureSampleType::Depth, TextureSampleType::Depth)
+                    | (TextureSampleType::Sint, TextureSampleType::Sint)
+                    | (TextureSampleType::Uint, TextureSampleType::Uint)
+            );
+
+            sample
+                && expected_dimension == provided_dimension
+                && expected_multisampled == provided_multisampled
+        }
+        (BindingType::Sampler(expected), BindingType::Sampler(provided)) => {
+            (*expected == SamplerBindingType::Comparison)
+                == (*provided == SamplerBindingType::Comparison)
+        }
+        (expected @ BindingType::StorageTexture { .. }, provided) => expected == provided,
+        _ => false,
+    }
+}
+
+fn stage(stage: naga::ShaderStage) -> ShaderStages {
+    match stage {
+        naga::ShaderStage::Vertex => ShaderStages::VERTEX,
+        naga::ShaderStage::Fragment => ShaderStages::FRAGMENT,
+        naga::ShaderStage::Compute => ShaderStages::COMPUTE,
+    }
+}
+
+fn view_dimension(dim: ImageDimension, arrayed: bool) -> TextureViewDimension {
+    match (dim, arrayed) {
+        (ImageDimension::D1, _) => TextureViewDimension::D1,
+        (ImageDimension::D2, false) => TextureViewDimension::D2,
+        (ImageDimension::D2, true) => TextureViewDimension::D2Array,
+        (ImageDimension::D3, _) => TextureViewDimension::D3,
+        (ImageDimension::Cube, false) => TextureViewDimension::Cube,
+        (ImageDimension::Cube, true) => TextureViewDimension::CubeArray,
+    }
+}
+
+fn vertex_input(binding: Option<&Binding>, ty: &TypeInner) -> Option<VertexInput> {
+    let &Binding::Location { location, .. } = binding? else {
+        return None;
+    };
+
+    let (kind, components) = match *ty {
+        TypeInner::Scalar { kind, .. } => (kind, 1),
+        TypeInner::Vector { kind, size, .. } => (kind, size as u32),
+        _ => return None,
+    };
+
+    Some(VertexInput {
+        location,
+        kind,
+        components,
+    })
+}
+
+/// Returns the type a vertex format is read as in the shader
+/// Returns the scalar kind and component count a vertex format is read as in the shader
+fn format_type(format: VertexFormat) -> (ScalarKind, u32) {
+    use VertexFormat::*;
+    let kind = match format {
+        Uint8x2 | Uint8x4 | Uint16x2 | Uint16x4 | Uint32 | Uint32x2 | Uint32x3 | Uint32x4 => {
+            ScalarKind::Uint
+        }
+        Sint8x2 | Sint8x4 | Sint16x2 | Sint16x4 | Sint32 | Sint32x2 | Sint32x3 | Sint32x4 => {
+            ScalarKind::Sint
+        }
+        _ => ScalarKind::Float,
+    };
+
+    let components = match format {
+        Uint32 | Sint32 | Float32 | Float64 => 1,
+        Uint8x2 | Sint8x2 | Unorm8x2 | Snorm8x2 | Uint16x2 | Sint16x2 | Unorm16x2 | Snorm16x2
+        | Float16x2 | Uint32x2 | Sint32x2 | Float32x2 | Float64x2 => 2,
+        Uint32x3 | Sint32x3 | Float32x3 | Float64x3 => 3,
+        Uint8x4 | Sint8x4 | Unorm8x4 | Snorm8x4 | Uint16x4 | Sint16x4 | Unorm16x4 | Snorm16x4
+        | Float16x4 | Uint32x4 | Sint32x4 | Float32x4 | Float64x4 => 4,
+    };
+
+    (kind, components)
+}
+
+fn texture_format(format: StorageFormat) -> TextureFormat {
+    match format {
+        StorageFormat::R8Unorm => TextureFormat::R8Unorm,
+        StorageFormat::R8Snorm => TextureFormat::R8Snorm,
+        StorageFormat::R8Uint => TextureFormat::R8Uint,
+        StorageFormat::R8Sint => TextureFormat::R8Sint,
+        StorageFormat::R16Uint => TextureFormat::R16Uint,
+        StorageFormat::R16Sint => TextureFormat::R16Sint,
+        StorageFormat::R16Float => TextureFormat::R16Float,
+        StorageFormat::Rg8Unorm => TextureFormat::Rg8Unorm,
+        StorageFormat::Rg8Snorm => TextureFormat::Rg8Snorm,
+        StorageFormat::Rg8Uint => TextureFormat::Rg8Uint,
+        StorageFormat::Rg8Sint => TextureFormat::Rg8Sint,
+        StorageFormat::R32Uint => TextureFormat::R32Uint,
+        StorageFormat::R32Sint => TextureFormat::R32Sint,
+        StorageFormat::R32Float => TextureFormat::R32Float,
+        StorageFormat::Rg16Uint => TextureFormat::Rg16Uint,
+        StorageFormat::Rg16Sint => TextureFormat::Rg16Sint,
+        StorageFormat::Rg16Float => TextureFormat::Rg16Float,
+        StorageFormat::Rgba8Unorm => TextureFormat::Rgba8Unorm,
+        StorageFormat::Rgba8Snorm => TextureFormat::Rgba8Snorm,
+        StorageFormat::Rgba8Uint => TextureFormat::Rgba8Uint,
+        StorageFormat::Rgba8Sint => TextureFormat::Rgba8Sint,
+        StorageFormat::Rgb10a2Unorm => TextureFormat::Rgb10a2Unorm,
+        StorageFormat::Rg11b10Float => TextureFormat::Rg11b10Float,
+        StorageFormat::Rg32Uint => TextureFormat::Rg32Uint,
+        StorageFormat::Rg32Sint => TextureFormat::Rg32Sint,
+        StorageFormat::Rg32Float => TextureFormat::Rg32Float,
+        StorageFormat::Rgba16Uint => TextureFormat::Rgba16Uint,
+        StorageFormat::Rgba16Sint => TextureFormat::Rgba16Sint,
+        StorageFormat::Rgba16Float => TextureFormat::Rgba16Float,
+        StorageFormat::Rgba32Uint => TextureFormat::Rgba32Uint,
+        StorageFormat::Rgba32Sint => TextureFormat::Rgba32Sint,
+        StorageFormat::Rgba32Float => TextureFormat::Rgba32Float,
+        StorageFormat::R16Unorm => TextureFormat::R16Unorm,
+        StorageFormat::R16Snorm => TextureFormat::R16Snorm,
+        StorageFormat::Rg16Unorm => TextureFormat::Rg16Unorm,
+        StorageFormat::Rg16Snorm => TextureFormat::Rg16Snorm,
+        StorageFormat::Rgba16Unorm => TextureFormat::Rgba16Unorm,
+        StorageFormat::Rgba16Snorm => TextureFormat::Rgba16Snorm,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use wgpu::vertex_attr_array;
+
+    use super::*;
+    use crate::graphics::{ShaderDefines, ShaderLibrary};
+
+    const SOURCE: &str = "
+struct VertexInput {
+    @location(0) pos: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> transform: mat4x4<f32>;
+@group(0) @binding(1)
+var diffuse: texture_2d<f32>;
+@group(0) @binding(2)
+var diffuse_sampler: sampler;
+@group(1) @binding(0)
+var<storage, read_write> counter: array<u32>;
+
+@vertex
+fn vs_main(in: VertexInput) -> @builtin(position) vec4<f32> {
+    return transform * vec4(in.pos, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    counter[0] = 1u;
+    return textureSample(diffuse, diffuse_sampler, vec2(0.0));
+}
+";
+
+    fn reflect() -> ShaderReflection {
+        ShaderLibrary::new()
+            .with_module("main", "main.wgsl", SOURCE)
+            .compose("main", &ShaderDefines::new())
+            .unwrap()
+            .reflect()
+            .unwrap()
+    }
+
+    #[test]
+    fn bindings() {
+        let reflection = reflect();
+
+        assert_eq!(reflection.group_count(), 2);
+        assert_eq!(
+            reflection.bind_group_layout_entries(0),
+            [
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(64),
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ]
+        );
+
+        let mut layout = BindGroupLayoutBuilder::new("layout");
+        layout
+            .bind_uniform_buffer(ShaderStages::VERTEX)
+            .bind_texture(ShaderStages::FRAGMENT)
+            .bind_sampler(ShaderStages::FRAGMENT);
+        reflection
+            .check_bind_group_layout(0, layout.entries())
+            .unwrap();
+
+        let mut layout = BindGroupLayoutBuilder::new("layout");
+        layout
+            .bind_uniform_buffer(ShaderStages::FRAGMENT)
+            .bind_texture(ShaderStages::FRAGMENT);
+        let err = reflection
+            .check_bind_group_layout(0, layout.entries())
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Binding 0 of group 0 (transform): Used in ShaderStages(VERTEX), but only visible in ShaderStages(FRAGMENT)"
+        );
+
+        // The shader writes to the storage buffer
+        let mut layout = BindGroupLayoutBuilder::new("layout");
+        layout.bind_storage_buffer(ShaderStages::FRAGMENT);
+        assert!(matches!(
+            reflection.check_bind_group_layout(1, layout.entries()),
+            Err(ShaderError::BindingMismatch {
+                group: 1,
+                binding: 0,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn vertex_inputs() {
+        let reflection = reflect();
+
+        reflection
+            .check_vertex_layouts(&[VertexBufferLayout {
+                array_stride: 20,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &vertex_attr_array![0 => Float32x3, 1 => Float32x2],
+            }])
+            .unwrap();
+
+        let err = reflection
+            .check_vertex_layouts(&[VertexBufferLayout {
+                array_stride: 12,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &vertex_attr_array![0 => Float32x3],
+            }])
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Vertex input 1 (tex_coords): Missing from the vertex buffer layouts"
+        );
+
+        let err = reflection
+            .check_vertex_layouts(&[VertexBufferLayout {
+                array_stride: 16,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &vertex_attr_array![0 => Float32x2, 1 => Float32x2],
+            }])
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Vertex input 0 (pos): Expected 3 Float components, but the vertex format is Float32x2"
+        );
+    }
+}
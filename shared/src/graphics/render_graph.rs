@@ -0,0 +1,571 @@
+//! A graph of render passes which draw into named attachments.
+//!
+//! Passes declare which attachments they read and write, from which the graph derives the order
+//! they run in. Attachments are owned by the graph and recreated when the gpu is resized, except
+//! for [`RenderGraph::SURFACE`], which is the view being presented.
+use std::collections::HashMap;
+
+use wgpu::{
+    BindGroup, Color, CommandEncoder, LoadOp, Operations, RenderPipeline, TextureFormat,
+    TextureUsages, TextureView,
+};
+use winit::dpi::PhysicalSize;
+
+use super::{Gpu, Texture, TextureDesc};
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum RenderGraphError {
+    #[error("Pass {pass:?} uses the unknown attachment {attachment:?}")]
+    UnknownAttachment { pass: String, attachment: String },
+    #[error("Pass {pass:?} depends on the unknown pass {dependency:?}")]
+    UnknownPass { pass: String, dependency: String },
+    #[error("Pass {0:?} is added more than once")]
+    DuplicatePass(String),
+    #[error("The passes {0:?} depend on each other")]
+    Cycle(Vec<String>),
+}
+
+/// The size of an attachment
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AttachmentSize {
+    /// The size of the surface multiplied by a factor
+    Scaled(f32),
+    Fixed(u32, u32),
+}
+
+impl AttachmentSize {
+    fn resolve(self, surface: PhysicalSize<u32>) -> (u32, u32) {
+        match self {
+            AttachmentSize::Scaled(scale) => (
+                ((surface.width as f32 * scale) as u32).max(1),
+                ((surface.height as f32 * scale) as u32).max(1),
+            ),
+            AttachmentSize::Fixed(width, height) => (width, height),
+        }
+    }
+}
+
+/// Describes an attachment.
+///
+/// Defaults to the size of the surface, and can be rendered to and sampled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AttachmentDesc {
+    pub format: TextureFormat,
+    pub size: AttachmentSize,
+    pub usage: TextureUsages,
+    pub sample_count: u32,
+}
+
+impl AttachmentDesc {
+    pub fn new(format: TextureFormat) -> Self {
+        Self {
+            format,
+            size: AttachmentSize::Scaled(1.0),
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            sample_count: 1,
+        }
+    }
+
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.size = AttachmentSize::Scaled(scale);
+        self
+    }
+
+    pub fn with_size(mut self, width: u32, height: u32) -> Self {
+        self.size = AttachmentSize::Fixed(width, height);
+        self
+    }
+
+    pub fn with_usage(mut self, usage: TextureUsages) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    /// Multisampled attachments are resolved into another attachment to be sampled, so they are
+    /// only used as render attachments
+    pub fn with_sample_count(mut self, sample_count: u32) -> Self {
+        self.sample_count = sample_count;
+        if sample_count > 1 {
+            self.usage = TextureUsages::RENDER_ATTACHMENT;
+        }
+        self
+    }
+}
+
+struct Attachment {
+    desc: AttachmentDesc,
+    texture: Texture,
+    view: TextureView,
+}
+
+impl Attachment {
+    fn new(gpu: &Gpu, name: &str, desc: AttachmentDesc, surface: PhysicalSize<u32>) -> Self {
+        let (width, height) = desc.size.resolve(surface);
+
+        let texture = Texture::from_desc(
+            gpu,
+            &TextureDesc::new(width, height, desc.format, desc.usage)
+                .with_label(name)
+                .with_sample_count(desc.sample_count),
+        );
+        let view = texture.view();
+
+        Self {
+            desc,
+            texture,
+            view,
+        }
+    }
+}
+
+/// Describes the attachments a pass uses, and the passes it needs to run after
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PassDesc {
+    pub name: String,
+    pub reads: Vec<String>,
+    pub writes: Vec<String>,
+    /// Passes which need to run first, in addition to those writing the attachments read
+    pub dependencies: Vec<String>,
+}
+
+impl PassDesc {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            reads: Vec::new(),
+            writes: Vec::new(),
+            dependencies: Vec::new(),
+        }
+    }
+
+    pub fn with_read(mut self, attachment: impl Into<String>) -> Self {
+        self.reads.push(attachment.into());
+        self
+    }
+
+    pub fn with_write(mut self, attachment: impl Into<String>) -> Self {
+        self.writes.push(attachment.into());
+        self
+    }
+
+    pub fn with_dependency(mut self, pass: impl Into<String>) -> Self {
+        self.dependencies.push(pass.into());
+        self
+    }
+}
+
+/// A color attachment of a render pass begun with [`PassContext::begin_render_pass`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorAttachment<'a> {
+    pub name: &'a str,
+    /// The attachment a multisampled attachment is resolved into at the end of the pass
+    pub resolve_target: Option<&'a str>,
+    pub load: LoadOp<Color>,
+}
+
+impl<'a> ColorAttachment<'a> {
+    pub fn new(name: &'a str, load: LoadOp<Color>) -> Self {
+        Self {
+            name,
+            resolve_target: None,
+            load,
+        }
+    }
+
+    pub fn with_resolve_target(mut self, resolve_target: &'a str) -> Self {
+        self.resolve_target = Some(resolve_target);
+        self
+    }
+}
+
+/// What a pass has access to while recording
+pub struct PassContext<'a> {
+    pub gpu: &'a Gpu,
+    pub encoder: &'a mut CommandEncoder,
+    attachments: &'a HashMap<String, Attachment>,
+    surface: &'a TextureView,
+    generation: u64,
+}
+
+impl<'a> PassContext<'a> {
+    /// Returns the view of an attachment, or of the surface for [`RenderGraph::SURFACE`]
+    pub fn view(&self, name: &str) -> &'a TextureView {
+        if name == RenderGraph::<()>::SURFACE {
+            return self.surface;
+        }
+
+        match self.attachments.get(name) {
+            Some(attachment) => &attachment.view,
+            None => panic!("Unknown attachment {name:?}"),
+        }
+    }
+
+    pub fn texture(&self, name: &str) -> &'a Texture {
+        match self.attachments.get(name) {
+            Some(attachment) => &attachment.texture,
+            None => panic!("Unknown attachment {name:?}"),
+        }
+    }
+
+    /// Incremented whenever the attachments are recreated, which invalidates bind groups
+    /// referring to them
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Begins a render pass drawing to the `color` attachments and an optional `depth` attachment
+    pub fn begin_render_pass(
+        &mut self,
+        label: &str,
+        color: &[ColorAttachment],
+        depth: Option<(&str, LoadOp<f32>)>,
+    ) -> wgpu::RenderPass<'_> {
+        let color_attachments = color
+            .iter()
+            .map(|v| {
+                Some(wgpu::RenderPassColorAttachment {
+                    view: self.view(v.name),
+                    resolve_target: v.resolve_target.map(|name| self.view(name)),
+                    ops: Operations {
+                        load: v.load,
+                        store: true,
+                    },
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let depth_stencil_attachment =
+            depth.map(|(name, load)| wgpu::RenderPassDepthStencilAttachment {
+                view: self.view(name),
+                depth_ops: Some(Operations { load, store: true }),
+                stencil_ops: None,
+            });
+
+        self.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &color_attachments,
+            depth_stencil_attachment,
+        })
+    }
+
+    /// Draws a fullscreen triangle into `target`, such as for post-processing
+    pub fn draw_fullscreen(
+        &mut self,
+        label: &str,
+        target: &str,
+        pipeline: &RenderPipeline,
+        bind_group: &BindGroup,
+    ) {
+        let mut render_pass = self.begin_render_pass(
+            label,
+            &[ColorAttachment::new(target, LoadOp::Clear(Color::BLACK))],
+            None,
+        );
+
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+/// A pass of a [`RenderGraph`], which is given `T` each frame
+pub trait GraphPass<T> {
+    fn run(&mut self, ctx: &mut PassContext, world: &mut T);
+}
+
+impl<T, F> GraphPass<T> for F
+where
+    F: FnMut(&mut PassContext, &mut T),
+{
+    fn run(&mut self, ctx: &mut PassContext, world: &mut T) {
+        self(ctx, world)
+    }
+}
+
+struct PassNode<T> {
+    desc: PassDesc,
+    pass: Box<dyn GraphPass<T>>,
+}
+
+/// Runs passes in the order of their dependencies, passing each a `T` such as the game state
+pub struct RenderGraph<T> {
+    attachments: HashMap<String, Attachment>,
+    passes: Vec<PassNode<T>>,
+    /// The order of the passes, which is recomputed when passes are added
+    order: Option<Vec<usize>>,
+    size: PhysicalSize<u32>,
+    generation: u64,
+}
+
+impl<T> RenderGraph<T> {
+    /// The name of the view being presented, which is not owned by the graph
+    pub const SURFACE: &'static str = "surface";
+
+    pub fn new(gpu: &Gpu) -> Self {
+        Self {
+            attachments: HashMap::new(),
+            passes: Vec::new(),
+            order: None,
+            size: gpu.size(),
+            generation: 0,
+        }
+    }
+
+    /// Adds an attachment, replacing any previous attachment of the same name
+    pub fn add_attachment(
+        &mut self,
+        gpu: &Gpu,
+        name: impl Into<String>,
+        desc: AttachmentDesc,
+    ) -> &mut Self {
+        let name = name.into();
+        let attachment = Attachment::new(gpu, &name, desc, self.size);
+
+        self.attachments.insert(name, attachment);
+        self.generation += 1;
+        self
+    }
+
+    pub fn add_pass(&mut self, desc: PassDesc, pass: impl GraphPass<T> + 'static) -> &mut Self {
+        self.passes.push(PassNode {
+            desc,
+            pass: Box::new(pass),
+        });
+
+        self.order = None;
+        self
+    }
+
+    /// Validates the graph and computes the order of the passes
+    pub fn compile(&mut self) -> Result<(), RenderGraphError> {
+        let descs = self.passes.iter().map(|v| &v.desc).collect::<Vec<_>>();
+        let order = schedule(&descs, |name| {
+            name == Self::SURFACE || self.attachments.contains_key(name)
+        })?;
+
+        tracing::debug!(
+            order = ?order.iter().map(|&i| &descs[i].name).collect::<Vec<_>>(),
+            "Compiled render graph"
+        );
+
+        self.order = Some(order);
+        Ok(())
+    }
+
+    /// Returns the names of the passes in the order they run
+    pub fn order(&mut self) -> Result<Vec<&str>, RenderGraphError> {
+        if self.order.is_none() {
+            self.compile()?;
+        }
+
+        let order = self.order.as_ref().unwrap();
+        Ok(order
+            .iter()
+            .map(|&i| self.passes[i].desc.name.as_str())
+            .collect())
+    }
+
+    /// Recreates the attachments sized relative to the surface if the size of the gpu changed
+    pub fn resize(&mut self, gpu: &Gpu) {
+        let size = gpu.size();
+        if size == self.size {
+            return;
+        }
+
+        tracing::info!(?size, "Resizing render graph attachments");
+        self.size = size;
+
+        for (name, attachment) in &mut self.attachments {
+            if matches!(attachment.desc.size, AttachmentSize::Scaled(_)) {
+                *attachment = Attachment::new(gpu, name, attachment.desc, size);
+            }
+        }
+
+        self.generation += 1;
+    }
+
+    /// Records all passes, resizing the attachments first if needed.
+    ///
+    /// # Panics
+    ///
+    /// If the graph is invalid. Use [`Self::compile`] to check it up front.
+    pub fn execute(
+        &mut self,
+        gpu: &Gpu,
+        encoder: &mut CommandEncoder,
+        surface: &TextureView,
+        world: &mut T,
+    ) {
+        self.resize(gpu);
+
+        if self.order.is_none() {
+            if let Err(err) = self.compile() {
+                panic!("Invalid render graph: {err}");
+            }
+        }
+
+        let mut ctx = PassContext {
+            gpu,
+            encoder,
+            attachments: &self.attachments,
+            surface,
+            generation: self.generation,
+        };
+
+        for &i in self.order.as_ref().unwrap() {
+            let node = &mut self.passes[i];
+            node.pass.run(&mut ctx, world);
+        }
+    }
+}
+
+/// Orders the passes so that each runs after the passes it depends on.
+///
+/// A pass depends on the passes writing the attachments it reads, and on earlier passes writing
+/// the same attachments. Independent passes keep the order they were added in.
+fn schedule(
+    passes: &[&PassDesc],
+    is_attachment: impl Fn(&str) -> bool,
+) -> Result<Vec<usize>, RenderGraphError> {
+    let mut indices = HashMap::new();
+    for (i, pass) in passes.iter().enumerate() {
+        if indices.insert(pass.name.as_str(), i).is_some() {
+            return Err(RenderGraphError::DuplicatePass(pass.name.clone()));
+        }
+    }
+
+    let mut writers = HashMap::<&str, Vec<usize>>::new();
+    for (i, pass) in passes.iter().enumerate() {
+        for attachment in pass.reads.iter().chain(&pass.writes) {
+            if !is_attachment(attachment) {
+                return Err(RenderGraphError::UnknownAttachment {
+                    pass: pass.name.clone(),
+                    attachment: attachment.clone(),
+                });
+            }
+        }
+
+        for attachment in &pass.writes {
+            writers.entry(attachment).or_default().push(i);
+        }
+    }
+
+    let mut dependencies = vec![Vec::new(); passes.len()];
+    for (i, pass) in passes.iter().enumerate() {
+        for attachment in &pass.reads {
+            let writers = writers.get(attachment.as_str()).into_iter().flatten();
+            dependencies[i].extend(writers.filter(|&&v| v != i));
+        }
+
+        for attachment in &pass.writes {
+            let writers = writers[attachment.as_str()].iter();
+            dependencies[i].extend(writers.take_while(|&&v| v != i));
+        }
+
+        for dependency in &pass.dependencies {
+            match indices.get(dependency.as_str()) {
+                Some(&v) => dependencies[i].push(v),
+                None => {
+                    return Err(RenderGraphError::UnknownPass {
+                        pass: pass.name.clone(),
+                        dependency: dependency.clone(),
+                    })
+                }
+            }
+        }
+    }
+
+    // Repeatedly picks the first pass whose dependencies have all run
+    let mut done = vec![false; passes.len()];
+    let mut order = Vec::with_capacity(passes.len());
+
+    while order.len() < passes.len() {
+        let next =
+            (0..passes.len()).find(|&i| !done[i] && dependencies[i].iter().all(|&v| done[v]));
+
+        match next {
+            Some(i) => {
+                done[i] = true;
+                order.push(i);
+            }
+            None => {
+                let remaining = (0..passes.len())
+                    .filter(|&i| !done[i])
+                    .map(|i| passes[i].name.clone())
+                    .collect();
+
+                return Err(RenderGraphError::Cycle(remaining));
+            }
+        }
+    }
+
+    Ok(order)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn order(passes: &[PassDesc]) -> Result<Vec<&str>, RenderGraphError> {
+        let descs = passes.iter().collect::<Vec<_>>();
+        let order = schedule(&descs, |name| name != "missing")?;
+        Ok(order.iter().map(|&i| passes[i].name.as_str()).collect())
+    }
+
+    #[test]
+    fn dependencies() {
+        let passes = [
+            PassDesc::new("composite")
+                .with_read("scene")
+                .with_read("bloom")
+                .with_write("surface"),
+            PassDesc::new("bloom")
+                .with_read("scene")
+                .with_write("bloom"),
+            PassDesc::new("scene").with_write("scene"),
+            PassDesc::new("overlay")
+                .with_write("scene")
+                .with_dependency("ui"),
+            PassDesc::new("ui"),
+        ];
+
+        assert_eq!(
+            order(&passes).unwrap(),
+            ["scene", "ui", "overlay", "bloom", "composite"]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            order(&[PassDesc::new("a").with_read("missing")]),
+            Err(RenderGraphError::UnknownAttachment {
+                pass: "a".into(),
+                attachment: "missing".into()
+            })
+        );
+
+        assert_eq!(
+            order(&[PassDesc::new("a").with_dependency("b")]),
+            Err(RenderGraphError::UnknownPass {
+                pass: "a".into(),
+                dependency: "b".into()
+            })
+        );
+
+        assert_eq!(
+            order(&[PassDesc::new("a"), PassDesc::new("a")]),
+            Err(RenderGraphError::DuplicatePass("a".into()))
+        );
+
+        let cycle = [
+            PassDesc::new("a").with_read("y").with_write("x"),
+            PassDesc::new("b").with_read("x").with_write("y"),
+            PassDesc::new("c"),
+        ];
+        assert_eq!(
+            order(&cycle),
+            Err(RenderGraphError::Cycle(vec!["a".into(), "b".into()]))
+        );
+    }
+}
@@ -0,0 +1,66 @@
+use wgpu::{AddressMode, FilterMode};
+
+/// Describes a sampler, used to share samplers through [`Gpu::sampler`](super::Gpu::sampler)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SamplerDesc {
+    pub address_mode: AddressMode,
+    pub mag_filter: FilterMode,
+    pub min_filter: FilterMode,
+    pub mipmap_filter: FilterMode,
+    /// Requires all filters to be linear when greater than 1
+    pub anisotropy_clamp: u16,
+}
+
+impl SamplerDesc {
+    /// Trilinear filtering, for textures with mips
+    pub fn linear() -> Self {
+        Self {
+            address_mode: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            anisotropy_clamp: 1,
+        }
+    }
+
+    /// Point sampling, for pixel art and lookup tables
+    pub fn nearest() -> Self {
+        Self {
+            address_mode: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Nearest,
+            min_filter: FilterMode::Nearest,
+            mipmap_filter: FilterMode::Nearest,
+            anisotropy_clamp: 1,
+        }
+    }
+
+    pub fn with_address_mode(mut self, address_mode: AddressMode) -> Self {
+        self.address_mode = address_mode;
+        self
+    }
+
+    pub fn with_anisotropy(mut self, anisotropy_clamp: u16) -> Self {
+        self.anisotropy_clamp = anisotropy_clamp;
+        self
+    }
+
+    pub(crate) fn create(&self, device: &wgpu::Device) -> wgpu::Sampler {
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            label: None,
+            address_mode_u: self.address_mode,
+            address_mode_v: self.address_mode,
+            address_mode_w: self.address_mode,
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_filter: self.mipmap_filter,
+            anisotropy_clamp: self.anisotropy_clamp,
+            ..Default::default()
+        })
+    }
+}
+
+impl Default for SamplerDesc {
+    fn default() -> Self {
+        Self::linear()
+    }
+}
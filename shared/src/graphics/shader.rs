@@ -1,94 +1,232 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, sync::Arc};
 
 use wgpu::{
-    BindGroupLayout, DepthStencilState, PipelineLayoutDescriptor, RenderPipeline, StencilState,
-    TextureFormat, VertexBufferLayout,
+    BlendState, ColorTargetState, ColorWrites, DepthStencilState, MultisampleState, PrimitiveState,
+    PrimitiveTopology, RenderPipeline, StencilState, TextureFormat, VertexBufferLayout,
 };
 
-use super::Gpu;
+use super::{BindGroupLayout, CachedPipeline, ComposedShader, Gpu, PipelineCache, ShaderError};
+
+/// How the output of a fragment shader is combined with the target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    /// Overwrites the target
+    Opaque,
+    /// Regular alpha blending of non-premultiplied colors
+    Alpha,
+    PremultipliedAlpha,
+    /// Adds the color weighted by alpha, for particles and glows
+    Additive,
+    Custom(BlendState),
+}
+
+impl BlendMode {
+    pub fn state(&self) -> Option<BlendState> {
+        match *self {
+            BlendMode::Opaque => None,
+            BlendMode::Alpha => Some(BlendState::ALPHA_BLENDING),
+            BlendMode::PremultipliedAlpha => Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+            BlendMode::Additive => Some(BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent::OVER,
+            }),
+            BlendMode::Custom(state) => Some(state),
+        }
+    }
+}
+
+/// A color attachment written by the fragment shader
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ColorTarget {
+    pub format: TextureFormat,
+    pub blend: BlendMode,
+    pub write_mask: ColorWrites,
+}
+
+impl ColorTarget {
+    pub fn new(format: TextureFormat, blend: BlendMode) -> Self {
+        Self {
+            format,
+            blend,
+            write_mask: ColorWrites::ALL,
+        }
+    }
+}
+
+impl From<ColorTarget> for ColorTargetState {
+    fn from(value: ColorTarget) -> Self {
+        Self {
+            format: value.format,
+            blend: value.blend.state(),
+            write_mask: value.write_mask,
+        }
+    }
+}
+
+/// Describes a render pipeline.
+///
+/// Defaults to `vs_main` and `fs_main`, triangle lists, depth testing against a
+/// [`TextureFormat::Depth32Float`] attachment and the sample count of the gpu, which matches the
+/// scene attachments of the [`Renderer`](crate::renderer::Renderer). Pipelines drawing into
+/// other targets, such as for post-processing, need to set their sample count.
 #[derive(Debug, Clone)]
 pub struct ShaderDesc<'a> {
     pub label: &'a str,
-    pub source: Cow<'a, str>,
-    pub format: TextureFormat,
+    pub source: &'a ComposedShader,
+    pub vertex_entry_point: &'a str,
+    /// Pipelines without a fragment stage only write depth
+    pub fragment_entry_point: Option<&'a str>,
+    pub targets: Vec<ColorTarget>,
     pub vertex_layouts: Cow<'a, [VertexBufferLayout<'static>]>,
     pub layouts: &'a [&'a BindGroupLayout],
+    pub primitive: PrimitiveState,
+    pub depth_stencil: Option<DepthStencilState>,
+    /// `None` uses the sample count of the gpu
+    pub multisample: Option<MultisampleState>,
 }
 
+impl<'a> ShaderDesc<'a> {
+    pub fn new(label: &'a str, source: &'a ComposedShader) -> Self {
+        Self {
+            label,
+            source,
+            vertex_entry_point: "vs_main",
+            fragment_entry_point: Some("fs_main"),
+            targets: Vec::new(),
+            vertex_layouts: Cow::Borrowed(&[]),
+            layouts: &[],
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Cw,
+                cull_mode: None,
+                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+                polygon_mode: wgpu::PolygonMode::Fill,
+                // Requires Features::DEPTH_CLIP_CONTROL
+                unclipped_depth: false,
+                // Requires Features::CONSERVATIVE_RASTERIZATION
+                conservative: false,
+            },
+            depth_stencil: Some(Self::depth(TextureFormat::Depth32Float, true)),
+            multisample: None,
+        }
+    }
+
+    /// Depth testing with the `Less` comparison
+    pub fn depth(format: TextureFormat, write: bool) -> DepthStencilState {
+        DepthStencilState {
+            format,
+            depth_write_enabled: write,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: StencilState::default(),
+            bias: Default::default(),
+        }
+    }
+
+    pub fn with_entry_points(mut self, vertex: &'a str, fragment: Option<&'a str>) -> Self {
+        self.vertex_entry_point = vertex;
+        self.fragment_entry_point = fragment;
+        self
+    }
+
+    /// Adds a color target at the next location
+    pub fn with_target(mut self, format: TextureFormat, blend: BlendMode) -> Self {
+        self.targets.push(ColorTarget::new(format, blend));
+        self
+    }
+
+    pub fn with_vertex_layouts(
+        mut self,
+        vertex_layouts: impl Into<Cow<'a, [VertexBufferLayout<'static>]>>,
+    ) -> Self {
+        self.vertex_layouts = vertex_layouts.into();
+        self
+    }
+
+    pub fn with_layouts(mut self, layouts: &'a [&'a BindGroupLayout]) -> Self {
+        self.layouts = layouts;
+        self
+    }
+
+    pub fn with_topology(mut self, topology: PrimitiveTopology) -> Self {
+        self.primitive.topology = topology;
+        self
+    }
+
+    pub fn with_cull_mode(mut self, cull_mode: Option<wgpu::Face>) -> Self {
+        self.primitive.cull_mode = cull_mode;
+        self
+    }
+
+    /// Use `None` for passes without a depth attachment, such as post-processing
+    pub fn with_depth_stencil(mut self, depth_stencil: Option<DepthStencilState>) -> Self {
+        self.depth_stencil = depth_stencil;
+        self
+    }
+
+    pub fn with_sample_count(mut self, count: u32) -> Self {
+        self.multisample = Some(MultisampleState {
+            count,
+            ..Default::default()
+        });
+        self
+    }
+
+    /// Fills in the sample count of the gpu where none was set
+    fn resolve(&self, gpu: &Gpu) -> Self {
+        let mut desc = self.clone();
+        if desc.multisample.is_none() {
+            desc = desc.with_sample_count(gpu.sample_count());
+        }
+
+        desc
+    }
+}
+
+/// A render pipeline created through the [`PipelineCache`] of the gpu
 pub struct Shader {
-    pipeline: RenderPipeline,
+    cached: CachedPipeline,
+    pipeline: Option<Arc<RenderPipeline>>,
 }
 
 impl Shader {
-    pub fn new(gpu: &Gpu, desc: ShaderDesc) -> Self {
-        let shader = gpu
-            .device
-            .create_shader_module(wgpu::ShaderModuleDescriptor {
-                label: Some("Shader"),
-                source: wgpu::ShaderSource::Wgsl(desc.source),
-            });
-
-        let layout = gpu
-            .device
-            .create_pipeline_layout(&PipelineLayoutDescriptor {
-                label: Some(desc.label),
-                bind_group_layouts: desc.layouts,
-                push_constant_ranges: &[],
-            });
-
-        let pipeline = gpu
-            .device
-            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some(desc.label),
-                layout: Some(&layout),
-                vertex: wgpu::VertexState {
-                    module: &shader,
-                    entry_point: "vs_main",        // 1.
-                    buffers: &desc.vertex_layouts, // 2.
-                },
-                fragment: Some(wgpu::FragmentState {
-                    // 3.
-                    module: &shader,
-                    entry_point: "fs_main",
-                    targets: &[Some(wgpu::ColorTargetState {
-                        // 4.
-                        format: desc.format,
-                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                        write_mask: wgpu::ColorWrites::ALL,
-                    })],
-                }),
-                primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::TriangleList, // 1.
-                    strip_index_format: None,
-                    front_face: wgpu::FrontFace::Cw, // 2.
-                    // cull_mode: Some(wgpu::Face::Back),
-                    cull_mode: None,
-                    // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
-                    polygon_mode: wgpu::PolygonMode::Fill,
-                    // Requires Features::DEPTH_CLIP_CONTROL
-                    unclipped_depth: false,
-                    // Requires Features::CONSERVATIVE_RASTERIZATION
-                    conservative: false,
-                },
-                depth_stencil: Some(DepthStencilState {
-                    format: TextureFormat::Depth32Float,
-                    depth_write_enabled: true,
-                    depth_compare: wgpu::CompareFunction::Less,
-                    stencil: StencilState::default(),
-                    bias: Default::default(),
-                }),
-                multisample: wgpu::MultisampleState {
-                    count: 1,                         // 2.
-                    mask: !0,                         // 3.
-                    alpha_to_coverage_enabled: false, // 4.
-                },
-                multiview: None, // 5.
-            });
+    /// Creates or reuses the pipeline after checking the layouts against the shader's reflection
+    pub fn new(gpu: &Gpu, desc: ShaderDesc) -> Result<Self, ShaderError> {
+        let cached = gpu.pipeline_cache().get(&gpu.device, &desc.resolve(gpu))?;
+        Ok(Self::from_cached(cached))
+    }
+
+    /// Like [`Self::new`], but compiles the pipeline in the background.
+    ///
+    /// [`Self::pipeline`] returns `None` until the pipeline is ready. Use [`Self::pipeline_or`]
+    /// to draw with a placeholder in the meantime.
+    pub fn new_async(gpu: &Arc<Gpu>, desc: ShaderDesc) -> Result<Self, ShaderError> {
+        let cached = PipelineCache::get_async(gpu, &desc.resolve(gpu))?;
+        Ok(Self::from_cached(cached))
+    }
+
+    pub fn from_cached(cached: CachedPipeline) -> Self {
+        let pipeline = cached.get();
+        Self { cached, pipeline }
+    }
+
+    /// Picks up the pipeline once it has finished compiling or was rebuilt by a reload
+    pub fn update(&mut self) {
+        if let Some(pipeline) = self.cached.get() {
+            self.pipeline = Some(pipeline);
+        }
+    }
 
-        Self { pipeline }
+    pub fn pipeline(&self) -> Option<&RenderPipeline> {
+        self.pipeline.as_deref()
     }
 
-    pub fn pipeline(&self) -> &RenderPipeline {
-        &self.pipeline
+    /// Returns the pipeline of `placeholder` while this one is not ready
+    pub fn pipeline_or<'a>(&'a self, placeholder: &'a Shader) -> Option<&'a RenderPipeline> {
+        self.pipeline().or_else(|| placeholder.pipeline())
     }
 }
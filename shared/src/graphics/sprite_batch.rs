@@ -0,0 +1,359 @@
+//! Draws many 2D sprites with as few instanced draw calls as possible.
+//!
+//! Sprites are sorted by layer and then by texture, and each run of sprites sharing a texture
+//! is drawn with one call.
+//!
+//! The game does not draw anything through a batch yet. Its asteroids are flat-shaded meshes
+//! with gpu culling, and keep their own path in [`Game`](crate::game::Game).
+use std::{ops::Range, sync::Arc};
+
+use bytemuck::{Pod, Zeroable};
+use glam::{Vec2, Vec4};
+use wgpu::{
+    vertex_attr_array, BindGroup, Buffer, BufferUsages, CompareFunction, DepthStencilState,
+    RenderPass, TextureFormat, TextureView, VertexAttribute, VertexBufferLayout,
+};
+
+use super::{
+    AtlasSprite, BindGroupBuilder, BindGroupLayout, BlendMode, Gpu, Mesh, SamplerDesc, Shader,
+    ShaderDefines, ShaderDesc, ShaderError, ShaderLibrary, TypedBuffer, UvRect, Vertex,
+    VertexFormat,
+};
+
+/// Identifies a texture added to a [`SpriteBatch`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SpriteTexture(u32);
+
+/// A textured quad, centered on its position
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprite {
+    pub position: Vec2,
+    /// Counter-clockwise, in radians
+    pub rotation: f32,
+    /// The size in world units
+    pub scale: Vec2,
+    /// Multiplied with the texture, in linear space
+    pub color: Vec4,
+    pub uv: UvRect,
+    /// The array layer of the texture
+    pub page: u32,
+    /// Sprites on higher layers are drawn on top
+    pub layer: i32,
+    pub texture: SpriteTexture,
+}
+
+impl Sprite {
+    /// A white sprite of size 1 showing an atlas sprite, where `texture` is the atlas
+    pub fn from_atlas(texture: SpriteTexture, sprite: &AtlasSprite) -> Self {
+        Self {
+            position: Vec2::ZERO,
+            rotation: 0.0,
+            scale: Vec2::ONE,
+            color: Vec4::ONE,
+            uv: sprite.uv,
+            page: sprite.page,
+            layer: 0,
+            texture,
+        }
+    }
+
+    pub fn with_position(mut self, position: Vec2) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: Vec2) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_color(mut self, color: Vec4) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_layer(mut self, layer: i32) -> Self {
+        self.layer = layer;
+        self
+    }
+}
+
+/// Matches `InstanceInput` in sprite.wgsl
+#[repr(C)]
+#[derive(Pod, Zeroable, Copy, Clone, Debug, PartialEq)]
+struct SpriteInstance {
+    position: Vec2,
+    scale: Vec2,
+    rotation: f32,
+    page: u32,
+    uv_min: Vec2,
+    uv_max: Vec2,
+    /// Stored as an array, as the alignment of `Vec4` would require padding
+    color: [f32; 4],
+}
+
+impl From<&Sprite> for SpriteInstance {
+    fn from(sprite: &Sprite) -> Self {
+        Self {
+            position: sprite.position,
+            scale: sprite.scale,
+            rotation: sprite.rotation,
+            page: sprite.page,
+            uv_min: sprite.uv.min,
+            uv_max: sprite.uv.max,
+            color: sprite.color.to_array(),
+        }
+    }
+}
+
+impl VertexFormat for SpriteInstance {
+    /// Follows the attributes of the quad [`Vertex`]
+    const ATTRIBUTES: &'static [VertexAttribute] = &vertex_attr_array![
+        2 => Float32x2,
+        3 => Float32x2,
+        4 => Float32,
+        5 => Uint32,
+        6 => Float32x2,
+        7 => Float32x2,
+        8 => Float32x4,
+    ];
+
+    fn layout() -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// A draw call of the instances in `range`, which share a texture
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SpriteDraw {
+    texture: SpriteTexture,
+    range: Range<u32>,
+}
+
+/// Splits sorted sprites into runs sharing a texture
+fn draws(sprites: &[Sprite]) -> Vec<SpriteDraw> {
+    let mut draws: Vec<SpriteDraw> = Vec::new();
+
+    for (i, sprite) in sprites.iter().enumerate() {
+        let i = i as u32;
+        match draws.last_mut() {
+            Some(draw) if draw.texture == sprite.texture => draw.range.end = i + 1,
+            _ => draws.push(SpriteDraw {
+                texture: sprite.texture,
+                range: i..i + 1,
+            }),
+        }
+    }
+
+    draws
+}
+
+/// Returns the WGSL modules used by [`SpriteBatch`]
+pub fn sprite_library() -> ShaderLibrary {
+    ShaderLibrary::new()
+        .with_module(
+            "camera",
+            "assets/shaders/camera.wgsl",
+            include_str!("../../../assets/shaders/camera.wgsl"),
+        )
+        .with_module(
+            "sprite",
+            "assets/shaders/sprite.wgsl",
+            include_str!("../../../assets/shaders/sprite.wgsl"),
+        )
+}
+
+/// Collects the sprites of a frame and draws them sorted by layer.
+///
+/// Sprites are pushed each frame and uploaded by [`SpriteBatch::prepare`], after which
+/// [`SpriteBatch::render`] draws them in the scene pass. They are drawn over everything rendered
+/// before them in the pass, whatever its depth. Sprites on the same layer keep the order they were
+/// pushed in, except that those sharing a texture are drawn together.
+///
+/// Textures are array textures such as the view of a [`TextureAtlas`](super::TextureAtlas).
+pub struct SpriteBatch {
+    shader: Shader,
+    quad: Mesh,
+    texture_layout: BindGroupLayout,
+    camera_bind_group: BindGroup,
+    textures: Vec<BindGroup>,
+    sampler: Arc<wgpu::Sampler>,
+    sprites: Vec<Sprite>,
+    instances: TypedBuffer<SpriteInstance>,
+    draws: Vec<SpriteDraw>,
+}
+
+impl SpriteBatch {
+    /// Creates a batch drawing into a target of `format`, with the depth attachment and sample
+    /// count of the scene.
+    ///
+    /// `camera` holds the [`Camera`](crate::camera::Camera) the sprites are seen through.
+    pub fn new(gpu: &Gpu, camera: &Buffer, format: TextureFormat) -> Result<Self, ShaderError> {
+        let source = sprite_library().compose("sprite", &ShaderDefines::new())?;
+        let reflection = source.reflect()?;
+
+        let camera_layout = reflection
+            .bind_group_layout("sprite_camera_bind_group_layout", 0)
+            .build(gpu);
+        let texture_layout = reflection
+            .bind_group_layout("sprite_texture_bind_group_layout", 1)
+            .build(gpu);
+
+        // Sprites are transparent and ordered by layer instead of depth, so they are drawn over
+        // the scene without testing or writing depth
+        let depth = DepthStencilState {
+            depth_compare: CompareFunction::Always,
+            ..ShaderDesc::depth(TextureFormat::Depth32Float, false)
+        };
+        let shader = Shader::new(
+            gpu,
+            ShaderDesc::new("sprite", &source)
+                .with_target(format, BlendMode::Alpha)
+                .with_vertex_layouts(vec![Vertex::layout(), SpriteInstance::layout()])
+                .with_depth_stencil(Some(depth))
+                .with_layouts(&[&camera_layout, &texture_layout]),
+        )?;
+
+        let camera_bind_group = BindGroupBuilder::new("sprite_camera_bind_group")
+            .bind_buffer(camera)
+            .build(gpu, &camera_layout);
+
+        Ok(Self {
+            shader,
+            quad: Mesh::square(gpu),
+            texture_layout,
+            camera_bind_group,
+            textures: Vec::new(),
+            sampler: gpu.sampler(&SamplerDesc::linear()),
+            sprites: Vec::new(),
+            instances: TypedBuffer::new_uninit(gpu, "sprite_instances", BufferUsages::VERTEX, 256),
+            draws: Vec::new(),
+        })
+    }
+
+    /// Adds a texture which sprites can be drawn with, where `view` is a 2D array
+    pub fn add_texture(&mut self, gpu: &Gpu, view: &TextureView) -> SpriteTexture {
+        let bind_group = BindGroupBuilder::new("sprite_texture_bind_group")
+            .bind_texture(view)
+            .bind_sampler(&self.sampler)
+            .build(gpu, &self.texture_layout);
+
+        self.textures.push(bind_group);
+        SpriteTexture(self.textures.len() as u32 - 1)
+    }
+
+    pub fn push(&mut self, sprite: Sprite) {
+        self.sprites.push(sprite);
+    }
+
+    /// Removes the sprites pushed since the last call to [`SpriteBatch::prepare`]
+    pub fn clear(&mut self) {
+        self.sprites.clear();
+    }
+
+    /// Sorts and uploads the pushed sprites, replacing those drawn previously.
+    ///
+    /// The pushed sprites are cleared for the next frame.
+    pub fn prepare(&mut self, gpu: &Gpu) {
+        self.shader.update();
+
+        // Stable, so that sprites on the same layer and texture keep their order
+        self.sprites.sort_by_key(|v| (v.layer, v.texture));
+        self.draws = draws(&self.sprites);
+
+        if !self.sprites.is_empty() {
+            let instances = self
+                .sprites
+                .iter()
+                .map(SpriteInstance::from)
+                .collect::<Vec<_>>();
+
+            self.instances.write_growing(gpu, 0, &instances);
+        }
+
+        self.sprites.clear();
+    }
+
+    /// The number of draw calls made by [`SpriteBatch::render`]
+    pub fn draw_count(&self) -> usize {
+        self.draws.len()
+    }
+
+    /// Draws the prepared sprites, into a pass with a [`TextureFormat::Depth32Float`] depth
+    /// attachment
+    pub fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>) {
+        let Some(pipeline) = self.shader.pipeline() else {
+            return;
+        };
+
+        if self.draws.is_empty() {
+            return;
+        }
+
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+        self.quad.bind(render_pass);
+        render_pass.set_vertex_buffer(1, self.instances.slice(..));
+
+        let index_count = self.quad.index_count();
+        for draw in &self.draws {
+            render_pass.set_bind_group(1, &self.textures[draw.texture.0 as usize], &[]);
+            render_pass.draw_indexed(0..index_count, 0, draw.range.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::vec2;
+
+    use super::*;
+
+    #[test]
+    fn batching() {
+        let uv = UvRect {
+            min: Vec2::ZERO,
+            max: Vec2::ONE,
+        };
+        let sprite = |texture, layer, x| Sprite {
+            position: vec2(x, 0.0),
+            rotation: 0.0,
+            scale: Vec2::ONE,
+            color: Vec4::ONE,
+            uv,
+            page: 0,
+            layer,
+            texture: SpriteTexture(texture),
+        };
+
+        let mut sprites = vec![
+            sprite(1, 0, 0.0),
+            sprite(0, 1, 1.0),
+            sprite(0, 0, 2.0),
+            sprite(1, 0, 3.0),
+            sprite(1, 1, 4.0),
+            sprite(0, 0, 5.0),
+        ];
+        sprites.sort_by_key(|v| (v.layer, v.texture));
+
+        let positions = sprites.iter().map(|v| v.position.x).collect::<Vec<_>>();
+        assert_eq!(positions, [2.0, 5.0, 0.0, 3.0, 1.0, 4.0]);
+
+        let draws = draws(&sprites)
+            .into_iter()
+            .map(|v| (v.texture.0, v.range))
+            .collect::<Vec<_>>();
+        assert_eq!(draws, [(0, 0..2), (1, 2..4), (0, 4..5), (1, 5..6)]);
+
+        assert!(super::draws(&[]).is_empty());
+    }
+}
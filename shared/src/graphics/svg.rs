@@ -0,0 +1,139 @@
+use anyhow::Context;
+use glam::{vec2, Vec2};
+use image::{DynamicImage, RgbaImage};
+use resvg::{tiny_skia, usvg};
+use wgpu::TextureView;
+
+use super::{Gpu, ImageOptions, Texture};
+
+/// How much the required resolution may grow before re-rasterizing. Growing is visible as blur,
+/// while shrinking only wastes memory, so it is allowed to shrink further.
+const GROW_THRESHOLD: f32 = 1.25;
+const SHRINK_THRESHOLD: f32 = 0.5;
+
+/// Parses an SVG document
+pub fn parse_svg(data: &[u8]) -> anyhow::Result<usvg::Tree> {
+    usvg::Tree::from_data(data, &usvg::Options::default()).context("Failed to parse SVG")
+}
+
+/// Rasterizes `tree` to an image of `width` by `height` pixels, stretching it to fit
+pub fn rasterize_svg(tree: &usvg::Tree, width: u32, height: u32) -> DynamicImage {
+    let mut pixmap = tiny_skia::Pixmap::new(width.max(1), height.max(1)).unwrap();
+
+    let size = tree.size();
+    let transform = tiny_skia::Transform::from_scale(
+        pixmap.width() as f32 / size.width(),
+        pixmap.height() as f32 / size.height(),
+    );
+
+    resvg::render(tree, transform, &mut pixmap.as_mut());
+
+    // The pixmap is premultiplied, while textures are blended as straight alpha
+    let pixels = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|v| {
+            let v = v.demultiply();
+            [v.red(), v.green(), v.blue(), v.alpha()]
+        })
+        .collect();
+
+    RgbaImage::from_raw(pixmap.width(), pixmap.height(), pixels)
+        .unwrap()
+        .into()
+}
+
+/// A vector image which is rasterized at the resolution it is displayed at.
+///
+/// Call [`Self::update`] when the on-screen size or scale factor changes, which re-rasterizes
+/// when the required resolution changed significantly.
+pub struct SvgTexture {
+    tree: usvg::Tree,
+    options: ImageOptions,
+    texture: Texture,
+    view: TextureView,
+    generation: u64,
+}
+
+impl SvgTexture {
+    /// Parses and rasterizes `data` for an on-screen size of `size` logical pixels on a display
+    /// with `scale_factor` physical pixels per logical pixel.
+    ///
+    /// Mips are generated by default, which keeps it smooth when shrunk between rasterizations.
+    pub fn new(
+        gpu: &Gpu,
+        data: &[u8],
+        options: ImageOptions,
+        size: Vec2,
+        scale_factor: f32,
+    ) -> anyhow::Result<Self> {
+        let tree = parse_svg(data)?;
+        let [width, height] = Self::resolution(gpu, &tree, size, scale_factor);
+
+        let texture = Texture::from_image_with(gpu, &rasterize_svg(&tree, width, height), options);
+        let view = texture.view();
+
+        Ok(Self {
+            tree,
+            options,
+            texture,
+            view,
+            generation: 0,
+        })
+    }
+
+    /// Returns the pixel size needed to display the image at `size` logical pixels, keeping the
+    /// aspect ratio of the document
+    fn resolution(gpu: &Gpu, tree: &usvg::Tree, size: Vec2, scale_factor: f32) -> [u32; 2] {
+        let document = vec2(tree.size().width(), tree.size().height());
+
+        // Fit the document within the on-screen size
+        let scale = (size * scale_factor / document).min_element();
+        let pixels = (document * scale).ceil();
+
+        let max = gpu.device.limits().max_texture_dimension_2d as f32;
+        let pixels = pixels * (max / pixels.max_element()).min(1.0);
+
+        [pixels.x.max(1.0) as u32, pixels.y.max(1.0) as u32]
+    }
+
+    /// Re-rasterizes if the resolution needed for the new on-screen size differs significantly
+    /// from the current one.
+    ///
+    /// Returns true if the texture was replaced, in which case dependent bind groups need to be
+    /// rebuilt.
+    pub fn update(&mut self, gpu: &Gpu, size: Vec2, scale_factor: f32) -> bool {
+        let [width, height] = Self::resolution(gpu, &self.tree, size, scale_factor);
+
+        let ratio = width as f32 / self.texture.size().width as f32;
+        if (SHRINK_THRESHOLD..=GROW_THRESHOLD).contains(&ratio) {
+            return false;
+        }
+
+        tracing::debug!(
+            old_width = self.texture.size().width,
+            width,
+            height,
+            "Re-rasterizing SVG"
+        );
+
+        self.texture =
+            Texture::from_image_with(gpu, &rasterize_svg(&self.tree, width, height), self.options);
+        self.view = self.texture.view();
+        self.generation += 1;
+        true
+    }
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    pub fn view(&self) -> &TextureView {
+        &self.view
+    }
+
+    /// Incremented each time the image is re-rasterized
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+}
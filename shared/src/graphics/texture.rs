@@ -1,28 +1,226 @@
 use image::DynamicImage;
 use wgpu::{
     Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, TextureView,
-    TextureViewDescriptor,
+    TextureViewDescriptor, TextureViewDimension,
 };
 
 use super::Gpu;
 
+/// How the color channels of an image are encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorSpace {
+    /// Colors, which are decoded to linear when sampled
+    #[default]
+    Srgb,
+    /// Data such as normal maps and masks, which is sampled as is
+    Linear,
+}
+
+impl ColorSpace {
+    pub fn rgba8(self) -> TextureFormat {
+        match self {
+            ColorSpace::Srgb => TextureFormat::Rgba8UnormSrgb,
+            ColorSpace::Linear => TextureFormat::Rgba8Unorm,
+        }
+    }
+}
+
+/// Options for creating textures from images
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageOptions {
+    pub color_space: ColorSpace,
+    /// Generates a full mip chain, which avoids aliasing when the texture is minified
+    pub mips: bool,
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        Self {
+            color_space: ColorSpace::Srgb,
+            mips: true,
+        }
+    }
+}
+
+/// Describes a texture.
+///
+/// Defaults to a single 2D layer without mips or multisampling.
+#[derive(Debug, Clone)]
+pub struct TextureDesc<'a> {
+    pub label: Option<&'a str>,
+    pub size: Extent3d,
+    /// The dimension of views created by [`Texture::view`]
+    pub dimension: TextureViewDimension,
+    pub mip_level_count: u32,
+    pub sample_count: u32,
+    pub format: TextureFormat,
+    pub usage: TextureUsages,
+}
+
+impl<'a> TextureDesc<'a> {
+    pub fn new(width: u32, height: u32, format: TextureFormat, usage: TextureUsages) -> Self {
+        Self {
+            label: None,
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            dimension: TextureViewDimension::D2,
+            mip_level_count: 1,
+            sample_count: 1,
+            format,
+            usage,
+        }
+    }
+
+    pub fn with_label(mut self, label: &'a str) -> Self {
+        self.label = Some(label);
+        self
+    }
+
+    /// Makes this a 2D array texture
+    pub fn with_layers(mut self, layers: u32) -> Self {
+        self.size.depth_or_array_layers = layers;
+        self.dimension = TextureViewDimension::D2Array;
+        self
+    }
+
+    /// Makes this a cube map of 6 square faces, in the order +X, -X, +Y, -Y, +Z, -Z
+    pub fn cube(mut self) -> Self {
+        self.size.depth_or_array_layers = 6;
+        self.dimension = TextureViewDimension::Cube;
+        self
+    }
+
+    /// Allocates a full mip chain, down to 1x1
+    pub fn with_mips(mut self) -> Self {
+        self.mip_level_count = full_mip_level_count(self.size.width, self.size.height);
+        self
+    }
+
+    pub fn with_mip_level_count(mut self, mip_level_count: u32) -> Self {
+        self.mip_level_count = mip_level_count;
+        self
+    }
+
+    /// Makes this a multisampled render attachment, which needs to be resolved to be sampled
+    pub fn with_sample_count(mut self, sample_count: u32) -> Self {
+        self.sample_count = sample_count;
+        self
+    }
+}
+
+/// Returns the number of mip levels needed to halve the size down to 1x1
+pub fn full_mip_level_count(width: u32, height: u32) -> u32 {
+    u32::BITS - width.max(height).max(1).leading_zeros()
+}
+
 pub struct Texture {
     texture: wgpu::Texture,
     size: Extent3d,
+    dimension: TextureViewDimension,
+    mip_level_count: u32,
+    format: TextureFormat,
+}
+
+impl std::ops::Deref for Texture {
+    type Target = wgpu::Texture;
+
+    fn deref(&self) -> &Self::Target {
+        &self.texture
+    }
 }
 
 impl Texture {
+    /// Creates an sRGB texture with a full mip chain
     pub fn from_image(gpu: &Gpu, image: DynamicImage) -> Self {
-        Self::new(
+        Self::from_image_with(gpu, &image, ImageOptions::default())
+    }
+
+    pub fn from_image_with(gpu: &Gpu, image: &DynamicImage, options: ImageOptions) -> Self {
+        Self::from_layers(
             gpu,
-            image.width(),
-            image.height(),
-            TextureFormat::Rgba8UnormSrgb,
-            TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
-            &image.to_rgba8(),
+            std::slice::from_ref(image),
+            TextureViewDimension::D2,
+            options,
         )
     }
 
+    /// Creates an array texture or cube map with one layer per image.
+    ///
+    /// All images need to have the same size. Cube maps take 6 square images, in the order +X,
+    /// -X, +Y, -Y, +Z, -Z, and cube map arrays 6 images per cube.
+    ///
+    /// # Panics
+    ///
+    /// If the images don't fit the dimension.
+    pub fn from_layers(
+        gpu: &Gpu,
+        images: &[DynamicImage],
+        dimension: TextureViewDimension,
+        options: ImageOptions,
+    ) -> Self {
+        let Some(first) = images.first() else {
+            panic!("A texture needs at least one layer");
+        };
+        let (width, height) = (first.width(), first.height());
+
+        assert!(
+            images
+                .iter()
+                .all(|v| v.width() == width && v.height() == height),
+            "All layers need to have the same size"
+        );
+
+        let layers = images.len();
+        match dimension {
+            TextureViewDimension::D2 => {
+                assert_eq!(layers, 1, "2D textures take a single image")
+            }
+            TextureViewDimension::Cube => {
+                assert_eq!(layers, 6, "Cube maps take 6 images")
+            }
+            TextureViewDimension::CubeArray => assert!(
+                layers.is_multiple_of(6),
+                "Cube map arrays take 6 images per cube, got {layers}"
+            ),
+            _ => {}
+        }
+
+        if matches!(
+            dimension,
+            TextureViewDimension::Cube | TextureViewDimension::CubeArray
+        ) {
+            assert_eq!(width, height, "The faces of cube maps need to be square");
+        }
+
+        let mut usage = TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST;
+        if options.mips {
+            // The mips are rendered from the previous level
+            usage |= TextureUsages::RENDER_ATTACHMENT;
+        }
+
+        let mut desc = TextureDesc::new(width, height, options.color_space.rgba8(), usage);
+        desc.size.depth_or_array_layers = images.len() as u32;
+        desc.dimension = dimension;
+
+        if options.mips {
+            desc = desc.with_mips();
+        }
+
+        let texture = Self::from_desc(gpu, &desc);
+        for (layer, image) in images.iter().enumerate() {
+            texture.write_layer(gpu, layer as u32, &image.to_rgba8());
+        }
+
+        if options.mips {
+            texture.generate_mips(gpu);
+        }
+
+        texture
+    }
+
     pub fn new_uninit(
         gpu: &Gpu,
         width: u32,
@@ -30,25 +228,34 @@ impl Texture {
         format: TextureFormat,
         usage: TextureUsages,
     ) -> Self {
-        let size = Extent3d {
-            width,
-            height,
-            depth_or_array_layers: 1,
-        };
+        Self::from_desc(gpu, &TextureDesc::new(width, height, format, usage))
+    }
 
+    /// Creates an uninitialized texture with the usages of `desc`.
+    ///
+    /// Generating mips on the gpu requires [`TextureUsages::TEXTURE_BINDING`] and
+    /// [`TextureUsages::RENDER_ATTACHMENT`].
+    pub fn from_desc(gpu: &Gpu, desc: &TextureDesc) -> Self {
         let texture = gpu.device.create_texture(&TextureDescriptor {
-            label: None,
-            size,
-            mip_level_count: 1,
-            sample_count: 1,
+            label: desc.label,
+            size: desc.size,
+            mip_level_count: desc.mip_level_count,
+            sample_count: desc.sample_count,
             dimension: TextureDimension::D2,
-            format,
-            usage,
+            format: desc.format,
+            usage: desc.usage,
             view_formats: &[],
         });
 
-        Self { texture, size }
+        Self {
+            texture,
+            size: desc.size,
+            dimension: desc.dimension,
+            mip_level_count: desc.mip_level_count,
+            format: desc.format,
+        }
     }
+
     pub fn new(
         gpu: &Gpu,
         width: u32,
@@ -58,12 +265,46 @@ impl Texture {
         bytes: &[u8],
     ) -> Self {
         let texture = Self::new_uninit(gpu, width, height, format, usage);
+        texture.write_layer(gpu, 0, bytes);
+        texture
+    }
+
+    /// Writes the first mip level of `layer`
+    pub fn write_layer(&self, gpu: &Gpu, layer: u32, bytes: &[u8]) {
+        self.write_region(
+            gpu,
+            layer,
+            [0, 0],
+            [self.size.width, self.size.height],
+            bytes,
+        );
+    }
+
+    /// Writes a `size` region of the first mip level of `layer`, starting at `origin`
+    pub fn write_region(
+        &self,
+        gpu: &Gpu,
+        layer: u32,
+        origin: [u32; 2],
+        size: [u32; 2],
+        bytes: &[u8],
+    ) {
+        assert!(
+            layer < self.layer_count(),
+            "Layer {layer} is out of bounds for {} layers",
+            self.layer_count()
+        );
+
         gpu.queue.write_texture(
             // Tells wgpu where to copy the pixel data
             wgpu::ImageCopyTexture {
-                texture: &texture.texture,
+                texture: &self.texture,
                 mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
+                origin: wgpu::Origin3d {
+                    x: origin[0],
+                    y: origin[1],
+                    z: layer,
+                },
                 aspect: wgpu::TextureAspect::All,
             },
             // The actual pixel data
@@ -71,13 +312,83 @@ impl Texture {
             // The layout of the texture
             wgpu::ImageDataLayout {
                 offset: 0,
-                bytes_per_row: Some(format.block_size(None).unwrap() * width),
-                rows_per_image: Some(height),
+                bytes_per_row: Some(self.format.block_size(None).unwrap() * size[0]),
+                rows_per_image: Some(size[1]),
+            },
+            Extent3d {
+                width: size[0],
+                height: size[1],
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /// Writes all layers of mip `level`, such as the blocks of a compressed format
+    pub fn write_level(&self, gpu: &Gpu, level: u32, bytes: &[u8]) {
+        let size = self
+            .size
+            .mip_level_size(level, TextureDimension::D2)
+            .physical_size(self.format);
+
+        let (block_width, block_height) = self.format.block_dimensions();
+        let block_size = self.format.block_size(None).unwrap();
+
+        gpu.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: level,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            bytes,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(size.width / block_width * block_size),
+                rows_per_image: Some(size.height / block_height),
             },
-            texture.size,
+            size,
         );
+    }
 
-        texture
+    /// Fills all mip levels from the first one, using a blit pass per level.
+    ///
+    /// The texture needs to be created with [`TextureUsages::TEXTURE_BINDING`] and
+    /// [`TextureUsages::RENDER_ATTACHMENT`].
+    pub fn generate_mips(&self, gpu: &Gpu) {
+        if self.mip_level_count > 1 {
+            let usage = TextureUsages::TEXTURE_BINDING | TextureUsages::RENDER_ATTACHMENT;
+            assert!(
+                self.usage().contains(usage),
+                "Generating mips requires {usage:?}, but the texture has {:?}",
+                self.usage()
+            );
+
+            gpu.mip_generator().generate(gpu, self);
+        }
+    }
+
+    pub fn size(&self) -> Extent3d {
+        self.size
+    }
+
+    pub fn layer_count(&self) -> u32 {
+        self.size.depth_or_array_layers
+    }
+
+    pub fn mip_level_count(&self) -> u32 {
+        self.mip_level_count
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    /// Creates a view of all layers and mips, with the dimension the texture was created with
+    pub fn view(&self) -> TextureView {
+        self.create_view(&TextureViewDescriptor {
+            dimension: Some(self.dimension),
+            ..Default::default()
+        })
     }
 
     pub fn create_view(&self, desc: &TextureViewDescriptor) -> TextureView {
@@ -2,3 +2,5 @@ pub mod camera;
 pub mod game;
 pub mod graphics;
 pub mod renderer;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod snapshot;
@@ -1,76 +1,107 @@
-use wgpu::{CommandEncoder, LoadOp, Operations, TextureUsages, TextureView};
+use std::sync::Arc;
+
+use wgpu::{Color, CommandEncoder, LoadOp, TextureFormat, TextureUsages, TextureView};
 
 use crate::{
     game::Game,
-    graphics::{Gpu, Texture},
+    graphics::{
+        AttachmentDesc, ColorAttachment, Gpu, PassContext, PassDesc, PostProcess,
+        PostProcessSettings, RenderGraph,
+    },
 };
 
+/// Renders the game into an HDR framebuffer, which is post-processed and tonemapped onto the
+/// surface
 pub struct Renderer {
-    framebuffer: TextureView,
-    depth: TextureView,
+    gpu: Arc<Gpu>,
+    graph: RenderGraph<Game>,
+    post_process: PostProcess,
 }
 
 impl Renderer {
-    pub fn new(gpu: &Gpu) -> Self {
-        let size = gpu.size();
-        let framebuffer = Texture::new_uninit(
-            gpu,
-            size.width,
-            size.height,
-            wgpu::TextureFormat::Rgba8UnormSrgb,
-            TextureUsages::COPY_SRC | TextureUsages::RENDER_ATTACHMENT,
-        );
-        let framebuffer = framebuffer.create_view(&Default::default());
-
-        let depth = gpu.device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Renderer.shadow_texture"),
-            size: wgpu::Extent3d {
-                width: size.width,
-                height: size.height,
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Depth32Float,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            view_formats: &[],
-        });
+    const CLEAR_COLOR: Color = Color {
+        r: 0.1,
+        g: 0.0,
+        b: 0.2,
+        a: 1.0,
+    };
+
+    /// Creates the render graph, multisampling the scene with the sample count of the gpu
+    pub fn new(gpu: &Arc<Gpu>) -> anyhow::Result<Self> {
+        let sample_count = gpu.sample_count();
+
+        let mut graph = RenderGraph::new(gpu);
+        graph
+            .add_attachment(gpu, "framebuffer", AttachmentDesc::new(gpu.hdr_format()))
+            .add_attachment(
+                gpu,
+                "depth",
+                AttachmentDesc::new(TextureFormat::Depth32Float)
+                    .with_usage(TextureUsages::RENDER_ATTACHMENT)
+                    .with_sample_count(sample_count),
+            );
+
+        // The scene is drawn into a multisampled attachment, which is resolved into the
+        // framebuffer for post-processing
+        let mut scene = PassDesc::new("scene")
+            .with_write("framebuffer")
+            .with_write("depth");
 
-        let depth = depth.create_view(&wgpu::TextureViewDescriptor {
-            aspect: wgpu::TextureAspect::DepthOnly,
-            ..Default::default()
+        let target = if sample_count > 1 {
+            graph.add_attachment(
+                gpu,
+                "framebuffer_multisampled",
+                AttachmentDesc::new(gpu.hdr_format()).with_sample_count(sample_count),
+            );
+            scene = scene.with_write("framebuffer_multisampled");
+
+            ColorAttachment::new("framebuffer_multisampled", LoadOp::Clear(Self::CLEAR_COLOR))
+                .with_resolve_target("framebuffer")
+        } else {
+            ColorAttachment::new("framebuffer", LoadOp::Clear(Self::CLEAR_COLOR))
+        };
+
+        graph.add_pass(scene, move |ctx: &mut PassContext, game: &mut Game| {
+            game.prepare(ctx.encoder);
+
+            let mut render_pass =
+                ctx.begin_render_pass("scene", &[target], Some(("depth", LoadOp::Clear(1.0))));
+
+            game.render(&mut render_pass)
         });
 
-        Self { framebuffer, depth }
+        let post_process = PostProcess::new(gpu, PostProcessSettings::default());
+        post_process.add_to_graph(
+            gpu,
+            &mut graph,
+            "framebuffer",
+            (RenderGraph::<Game>::SURFACE, gpu.surface_format()),
+        )?;
+
+        graph.compile()?;
+
+        Ok(Self {
+            gpu: gpu.clone(),
+            graph,
+            post_process,
+        })
     }
 
-    pub fn render(&mut self, encoder: &mut CommandEncoder, view: &TextureView, game: &mut Game) {
-        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("Render Pass"),
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color {
-                        r: 0.1,
-                        g: 0.0,
-                        b: 0.2,
-                        a: 1.0,
-                    }),
-                    store: true,
-                },
-            })],
-            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                view: &self.depth,
-                depth_ops: Some(Operations {
-                    load: LoadOp::Clear(1.0),
-                    store: true,
-                }),
-                stencil_ops: None,
-            }),
-        });
+    pub fn post_process(&self) -> &PostProcessSettings {
+        &self.post_process.settings
+    }
 
-        game.render(&mut render_pass)
+    pub fn post_process_mut(&mut self) -> &mut PostProcessSettings {
+        &mut self.post_process.settings
+    }
+
+    /// Recreates the render targets to match the current size of the gpu
+    pub fn resize(&mut self) {
+        self.graph.resize(&self.gpu);
+    }
+
+    pub fn render(&mut self, encoder: &mut CommandEncoder, view: &TextureView, game: &mut Game) {
+        self.post_process.write(&self.gpu);
+        self.graph.execute(&self.gpu, encoder, view, game);
     }
 }
@@ -0,0 +1,222 @@
+//! Golden-image testing of rendered frames.
+//!
+//! Frames rendered by a headless [`Gpu`] are read back to the CPU and compared against
+//! checked-in PNG references.
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use futures::channel::oneshot;
+use image::{Rgba, RgbaImage};
+use wgpu::{BufferUsages, TextureFormat, COPY_BYTES_PER_ROW_ALIGNMENT};
+
+use crate::graphics::Gpu;
+
+/// Reads back the texture of a headless gpu.
+///
+/// The texture format must have 4 bytes per pixel, and is returned as is. Rendering into an sRGB
+/// format therefore yields the same values as would be presented.
+pub async fn read_offscreen(gpu: &Gpu) -> anyhow::Result<RgbaImage> {
+    let texture = gpu
+        .offscreen_texture()
+        .context("Only headless gpus can be read back")?;
+
+    let format = texture.format();
+    if !matches!(
+        format,
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb
+    ) {
+        bail!("Unsupported texture format for readback: {format:?}");
+    }
+
+    let (width, height) = (texture.width(), texture.height());
+
+    // Rows of the copy must be aligned
+    let row_size = width * 4;
+    let padded_row_size =
+        row_size.div_ceil(COPY_BYTES_PER_ROW_ALIGNMENT) * COPY_BYTES_PER_ROW_ALIGNMENT;
+
+    let buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("readback_buffer"),
+        size: (padded_row_size * height) as u64,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = gpu
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Readback Encoder"),
+        });
+
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row_size),
+                rows_per_image: Some(height),
+            },
+        },
+        texture.size(),
+    );
+
+    gpu.queue.submit([encoder.finish()]);
+
+    let slice = buffer.slice(..);
+    let (tx, rx) = oneshot::channel();
+    slice.map_async(wgpu::MapMode::Read, move |res| {
+        tx.send(res).ok();
+    });
+
+    gpu.device.poll(wgpu::Maintain::Wait);
+    rx.await.context("Readback was cancelled")??;
+
+    let data = slice.get_mapped_range();
+    let pixels = data
+        .chunks(padded_row_size as usize)
+        .flat_map(|row| &row[..row_size as usize])
+        .copied()
+        .collect();
+
+    drop(data);
+    buffer.unmap();
+
+    Ok(RgbaImage::from_raw(width, height, pixels).expect("Readback size mismatch"))
+}
+
+/// Compares images against PNG references.
+///
+/// Set `UPDATE_SNAPSHOTS=1` to write the references from the current output. Missing references
+/// fail the comparison otherwise.
+#[derive(Debug, Clone)]
+pub struct SnapshotTest {
+    reference_dir: PathBuf,
+    output_dir: PathBuf,
+    tolerance: u8,
+    update: bool,
+}
+
+impl SnapshotTest {
+    /// `output_dir` receives the actual and diff images of failing comparisons
+    pub fn new(reference_dir: impl Into<PathBuf>, output_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            reference_dir: reference_dir.into(),
+            output_dir: output_dir.into(),
+            tolerance: 2,
+            update: std::env::var_os("UPDATE_SNAPSHOTS").is_some(),
+        }
+    }
+
+    /// Set the maximum allowed difference of any channel of a pixel
+    pub fn with_tolerance(mut self, tolerance: u8) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Whether to overwrite the references instead of comparing against them, which defaults to
+    /// whether `UPDATE_SNAPSHOTS` is set
+    pub fn with_update(mut self, update: bool) -> Self {
+        self.update = update;
+        self
+    }
+
+    pub fn check(&self, name: &str, image: &RgbaImage) -> anyhow::Result<()> {
+        let reference_path = self.reference_dir.join(format!("{name}.png"));
+
+        if self.update {
+            save(image, &reference_path)?;
+            tracing::info!(?reference_path, "Wrote snapshot reference");
+            return Ok(());
+        }
+
+        if !reference_path.exists() {
+            let actual_path = self.output_dir.join(format!("{name}.actual.png"));
+            save(image, &actual_path)?;
+            bail!(
+                "Snapshot {name:?} has no reference at {reference_path:?}. Actual image written to {actual_path:?}, run with UPDATE_SNAPSHOTS=1 to accept it"
+            );
+        }
+
+        let reference = image::open(&reference_path)
+            .with_context(|| format!("Failed to read reference {reference_path:?}"))?
+            .to_rgba8();
+
+        if reference.dimensions() != image.dimensions() {
+            let actual_path = self.output_dir.join(format!("{name}.actual.png"));
+            save(image, &actual_path)?;
+            bail!(
+                "Snapshot {name:?} has size {:?}, but the reference has size {:?}. Actual image written to {actual_path:?}",
+                image.dimensions(),
+                reference.dimensions()
+            );
+        }
+
+        let (diff, mismatches) = diff_images(&reference, image, self.tolerance);
+
+        if mismatches > 0 {
+            let actual_path = self.output_dir.join(format!("{name}.actual.png"));
+            let diff_path = self.output_dir.join(format!("{name}.diff.png"));
+            save(image, &actual_path)?;
+            save(&diff, &diff_path)?;
+
+            bail!(
+                "Snapshot {name:?} differs from the reference in {mismatches} pixels. Actual image written to {actual_path:?} and diff to {diff_path:?}"
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns an image highlighting the pixels which differ by more than `tolerance`, and the number
+/// of such pixels.
+fn diff_images(reference: &RgbaImage, actual: &RgbaImage, tolerance: u8) -> (RgbaImage, usize) {
+    let mut mismatches = 0;
+    let diff = RgbaImage::from_fn(reference.width(), reference.height(), |x, y| {
+        let a = reference.get_pixel(x, y);
+        let b = actual.get_pixel(x, y);
+
+        let max_diff = a.0.iter().zip(b.0).map(|(a, b)| a.abs_diff(b)).max();
+
+        if max_diff > Some(tolerance) {
+            mismatches += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            // Faded version of the reference to provide context
+            let [r, g, b, _] = a.0;
+            Rgba([r / 4, g / 4, b / 4, 255])
+        }
+    });
+
+    (diff, mismatches)
+}
+
+fn save(image: &RgbaImage, path: &Path) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    image
+        .save(path)
+        .with_context(|| format!("Failed to write {path:?}"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn missing_reference() {
+        let dir = std::env::temp_dir().join("orion_snapshot_missing_reference");
+        let snapshots =
+            SnapshotTest::new(dir.join("references"), dir.join("output")).with_update(false);
+
+        let image = RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 255]));
+        let err = snapshots.check("missing", &image).unwrap_err().to_string();
+
+        assert!(err.contains("missing.png"), "{err}");
+        assert!(!dir.join("references/missing.png").exists());
+        assert!(dir.join("output/missing.actual.png").exists());
+    }
+}
@@ -0,0 +1,84 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+mod common;
+
+use image::{DynamicImage, Rgba, RgbaImage};
+use shared::graphics::{AtlasDesc, AtlasError, ColorSpace, TextureAtlas, TypedBuffer};
+use wgpu::BufferUsages;
+
+fn solid(width: u32, height: u32, color: [u8; 4]) -> DynamicImage {
+    RgbaImage::from_pixel(width, height, Rgba(color)).into()
+}
+
+#[test]
+fn add_sprites() {
+    futures::executor::block_on(async {
+        let Some(gpu) = common::headless_gpu(16, 16).await else {
+            return;
+        };
+
+        // Rows of 64 texels are 256 byte aligned for the readback
+        let mut atlas = TextureAtlas::new(
+            &gpu,
+            AtlasDesc {
+                page_size: 64,
+                max_pages: 2,
+                padding: 1,
+                color_space: ColorSpace::Linear,
+            },
+        );
+
+        let red = atlas.add(&gpu, &solid(30, 30, [255, 0, 0, 255])).unwrap();
+        let green = atlas.add(&gpu, &solid(30, 30, [0, 255, 0, 255])).unwrap();
+        let large = atlas.add(&gpu, &solid(40, 40, [0, 0, 255, 255])).unwrap();
+
+        assert_eq!(atlas.page_count(), 2);
+        assert_eq!(atlas.get(red).page, 0);
+        assert_eq!(atlas.get(green).page, 0);
+        assert_eq!(atlas.get(large).page, 1);
+
+        let sprite = atlas.get(green);
+        assert_eq!(sprite.origin, [33, 1]);
+        assert_eq!(sprite.uv.min, glam::vec2(33.0, 1.0) / 64.0);
+        assert_eq!(sprite.uv.size(), glam::vec2(30.0, 30.0) / 64.0);
+
+        assert!(matches!(
+            atlas.add(&gpu, &solid(64, 1, [0; 4])),
+            Err(AtlasError::TooLarge { .. })
+        ));
+        assert!(matches!(
+            atlas.add(&gpu, &solid(40, 40, [0; 4])),
+            Err(AtlasError::Full { pages: 2 })
+        ));
+
+        // Read back the first row of the first page
+        let buffer =
+            TypedBuffer::<[u8; 4]>::new_uninit(&gpu, "readback", BufferUsages::empty(), 64);
+        gpu.submit("readback", |encoder| {
+            encoder.copy_texture_to_buffer(
+                atlas.texture().as_image_copy(),
+                wgpu::ImageCopyBuffer {
+                    buffer: &buffer,
+                    layout: wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: Some(64 * 4),
+                        rows_per_image: Some(1),
+                    },
+                },
+                wgpu::Extent3d {
+                    width: 64,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+            )
+        });
+
+        let row = buffer.read_all(&gpu).await.unwrap();
+
+        // The padding repeats the edges of the sprites
+        assert_eq!(row[0], [255, 0, 0, 255]);
+        assert_eq!(row[31], [255, 0, 0, 255]);
+        assert_eq!(row[32], [0, 255, 0, 255]);
+        assert_eq!(row[63], [0, 255, 0, 255]);
+    });
+}
@@ -0,0 +1,35 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+mod common;
+
+use shared::graphics::TypedBuffer;
+use wgpu::BufferUsages;
+
+#[test]
+fn write_grow_and_read() {
+    futures::executor::block_on(async {
+        let Some(gpu) = common::headless_gpu(16, 16).await else {
+            return;
+        };
+
+        let mut buffer = TypedBuffer::new(&gpu, "buffer", BufferUsages::STORAGE, &[1u32, 2, 3, 4]);
+        assert_eq!(buffer.read_all(&gpu).await.unwrap(), [1, 2, 3, 4]);
+
+        buffer.write_at(&gpu.queue, 2, &[5, 6]);
+        assert_eq!(buffer.read(&gpu, 1..4).await.unwrap(), [2, 5, 6]);
+
+        // Growing keeps the contents
+        assert!(buffer.write_growing(&gpu, 4, &[7, 8, 9]));
+        assert_eq!(buffer.len(), 8);
+        assert_eq!(buffer.generation(), 1);
+        assert_eq!(
+            buffer.read(&gpu, 0..7).await.unwrap(),
+            [1, 2, 5, 6, 7, 8, 9]
+        );
+
+        assert!(!buffer.write_growing(&gpu, 7, &[10]));
+        assert_eq!(buffer.generation(), 1);
+
+        assert!(buffer.read(&gpu, 4..9).await.is_err());
+    });
+}
@@ -0,0 +1,27 @@
+use shared::graphics::{Gpu, GpuConfig};
+use wgpu::TextureFormat;
+use winit::dpi::PhysicalSize;
+
+/// Creates a gpu rendering into an offscreen texture, or `None` if no adapter is available in
+/// which case the test is skipped
+#[allow(dead_code)]
+pub async fn headless_gpu(width: u32, height: u32) -> Option<Gpu> {
+    headless_gpu_with(&GpuConfig::default(), width, height).await
+}
+
+#[allow(dead_code)]
+pub async fn headless_gpu_with(config: &GpuConfig, width: u32, height: u32) -> Option<Gpu> {
+    match Gpu::new_headless(
+        config,
+        PhysicalSize::new(width, height),
+        TextureFormat::Rgba8UnormSrgb,
+    )
+    .await
+    {
+        Ok(gpu) => Some(gpu),
+        Err(err) => {
+            eprintln!("Skipping test: {err:?}");
+            None
+        }
+    }
+}
@@ -0,0 +1,126 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+mod common;
+
+use shared::graphics::{GpuConfig, Texture};
+use wgpu::{Features, TextureFormat, TextureUsages};
+
+/// A KTX2 container with `levels` mip levels of a white 8x8 BC1 image, and no data format
+/// descriptor
+fn bc1_ktx2(levels: u32) -> Vec<u8> {
+    // BC1_RGBA_UNORM_BLOCK, 8x8 pixels, 1 face
+    let mut data = b"\xabKTX 20\xbb\r\n\x1a\n".to_vec();
+    for value in [133u32, 1, 8, 8, 0, 0, 1, levels, 0] {
+        data.extend(value.to_le_bytes());
+    }
+    data.extend([0; 32]);
+
+    // Every level is at least one block
+    let blocks = (0..levels).map(|level| (8u64 >> level).div_ceil(4).pow(2));
+
+    // Level index of offset, length and uncompressed length, with the levels in order after it
+    let mut offset = data.len() as u64 + 24 * levels as u64;
+    for length in blocks.clone().map(|v| v * 8) {
+        for value in [offset, length, length] {
+            data.extend(value.to_le_bytes());
+        }
+        offset += length;
+    }
+
+    for count in blocks {
+        data.extend([0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0].repeat(count as usize));
+    }
+
+    data
+}
+
+#[test]
+fn load_ktx2() {
+    futures::executor::block_on(async {
+        let Some(gpu) = common::headless_gpu(16, 16).await else {
+            return;
+        };
+
+        let data = bc1_ktx2(1);
+        let texture = Texture::from_ktx2(&gpu, &[&data]).unwrap();
+
+        let expected = if gpu
+            .device
+            .features()
+            .contains(Features::TEXTURE_COMPRESSION_BC)
+        {
+            TextureFormat::Bc1RgbaUnorm
+        } else {
+            TextureFormat::Rgba8Unorm
+        };
+
+        assert_eq!(texture.format(), expected);
+        assert_eq!((texture.size().width, texture.size().height), (8, 8));
+        assert_eq!(texture.mip_level_count(), 1);
+    });
+}
+
+#[test]
+fn decode_unsupported() {
+    futures::executor::block_on(async {
+        let config = GpuConfig::default().with_features(Features::empty());
+        let Some(gpu) = common::headless_gpu_with(&config, 16, 16).await else {
+            return;
+        };
+
+        let data = bc1_ktx2(1);
+        let texture = Texture::from_ktx2(&gpu, &[&data]).unwrap();
+        assert_eq!(texture.format(), TextureFormat::Rgba8Unorm);
+    });
+}
+
+#[test]
+fn decode_unaligned() {
+    futures::executor::block_on(async {
+        let Some(gpu) = common::headless_gpu(16, 16).await else {
+            return;
+        };
+
+        // BC1_RGBA_UNORM_BLOCK, 6x6 pixels of 2x2 blocks
+        let mut data = b"\xabKTX 20\xbb\r\n\x1a\n".to_vec();
+        for value in [133u32, 1, 6, 6, 0, 0, 1, 1, 0] {
+            data.extend(value.to_le_bytes());
+        }
+        data.extend([0; 32]);
+        for value in [data.len() as u64 + 24, 32, 32] {
+            data.extend(value.to_le_bytes());
+        }
+        data.extend([0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0].repeat(4));
+
+        // Uploading would fail even where BC is supported
+        let texture = Texture::from_ktx2(&gpu, &[&data]).unwrap();
+        assert_eq!(texture.format(), TextureFormat::Rgba8Unorm);
+        assert_eq!((texture.size().width, texture.size().height), (6, 6));
+    });
+}
+
+#[test]
+fn load_ktx2_mips() {
+    futures::executor::block_on(async {
+        let Some(gpu) = common::headless_gpu(16, 16).await else {
+            return;
+        };
+
+        if !gpu
+            .device
+            .features()
+            .contains(Features::TEXTURE_COMPRESSION_BC)
+        {
+            eprintln!("Skipping test: BC compression is not supported");
+            return;
+        }
+
+        // Uploaded mips must not require a render attachment, which compressed formats can not be
+        let data = bc1_ktx2(4);
+        let texture = Texture::from_ktx2(&gpu, &[&data]).unwrap();
+
+        assert_eq!(texture.format(), TextureFormat::Bc1RgbaUnorm);
+        assert_eq!(texture.mip_level_count(), 4);
+        assert!(!texture.usage().contains(TextureUsages::RENDER_ATTACHMENT));
+    });
+}
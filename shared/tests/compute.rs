@@ -0,0 +1,92 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+mod common;
+
+use shared::graphics::{
+    BindGroupBuilder, ComputeShader, ComputeShaderDesc, GpuConfig, ShaderDefines, ShaderLibrary,
+    TypedBuffer,
+};
+use wgpu::{BufferUsages, Limits};
+
+const DOUBLE: &str = "@group(0) @binding(0)
+var<storage, read_write> values: array<u32>;
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= arrayLength(&values) {
+        return;
+    }
+
+    values[id.x] = values[id.x] * 2u;
+}
+";
+
+#[test]
+fn dispatch_and_read_back() {
+    futures::executor::block_on(async {
+        // The default limits are those of WebGL2, which has no compute
+        let config = GpuConfig::default().with_limits(Limits::downlevel_defaults());
+        let Some(gpu) = common::headless_gpu_with(&config, 16, 16).await else {
+            return;
+        };
+
+        if !gpu.supports_compute() {
+            eprintln!("Skipping test: compute is not supported");
+            return;
+        }
+
+        let library = ShaderLibrary::new().with_module("double", "double.wgsl", DOUBLE);
+        let source = library.compose("double", &ShaderDefines::new()).unwrap();
+
+        let layout = source
+            .reflect()
+            .unwrap()
+            .bind_group_layout("double", 0)
+            .build(&gpu);
+
+        let shader = ComputeShader::new(
+            &gpu,
+            ComputeShaderDesc::new("double", &source).with_layouts(&[&layout]),
+        )
+        .unwrap();
+
+        assert_eq!(shader.workgroup_size(), [64, 1, 1]);
+        assert_eq!(shader.workgroup_count([1000, 1, 1]), [16, 1, 1]);
+
+        // Not a multiple of the workgroup size
+        let data = (0..1000).collect::<Vec<u32>>();
+        let values = TypedBuffer::new(&gpu, "values", BufferUsages::STORAGE, &data);
+
+        let bind_group = BindGroupBuilder::new("double")
+            .bind_buffer(&values)
+            .build(&gpu, &layout);
+
+        gpu.submit("double", |encoder| {
+            shader.record(encoder, &[&bind_group], data.len() as u32)
+        });
+
+        let result = values.read_all(&gpu).await.unwrap();
+        assert_eq!(result, data.iter().map(|v| v * 2).collect::<Vec<_>>());
+    });
+}
+
+#[test]
+fn missing_layout() {
+    futures::executor::block_on(async {
+        let Some(gpu) = common::headless_gpu(16, 16).await else {
+            return;
+        };
+
+        let library = ShaderLibrary::new().with_module("double", "double.wgsl", DOUBLE);
+        let source = library.compose("double", &ShaderDefines::new()).unwrap();
+
+        let result = ComputeShader::new(&gpu, ComputeShaderDesc::new("double", &source));
+        assert!(result.is_err());
+
+        let result = ComputeShader::new(
+            &gpu,
+            ComputeShaderDesc::new("double", &source).with_entry_point("main"),
+        );
+        assert!(result.is_err());
+    });
+}
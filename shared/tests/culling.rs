@@ -0,0 +1,126 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+mod common;
+
+use bytemuck::{Pod, Zeroable};
+use glam::{vec3, Mat4, Vec3, Vec4};
+use shared::{
+    camera::Camera,
+    game::{shader_library, DrawIndexedIndirect},
+    graphics::{
+        BindGroupBuilder, ComputeShader, ComputeShaderDesc, GpuConfig, ShaderDefines, TypedBuffer,
+    },
+};
+use wgpu::{BufferUsages, Limits};
+
+#[repr(C)]
+#[derive(Pod, Zeroable, Clone, Copy)]
+struct ObjectData {
+    model: Mat4,
+    color: Vec4,
+}
+
+/// Enough objects that the workgroup counts are scanned in several chunks
+const OBJECT_COUNT: usize = 10_000;
+
+#[test]
+fn many_objects_keep_order() {
+    futures::executor::block_on(async {
+        let config = GpuConfig::default().with_limits(Limits::downlevel_defaults());
+        let Some(gpu) = common::headless_gpu_with(&config, 16, 16).await else {
+            return;
+        };
+
+        if !gpu.supports_compute() {
+            eprintln!("Skipping test: compute is not supported");
+            return;
+        }
+
+        let source = shader_library()
+            .compose("cull", &ShaderDefines::new())
+            .unwrap();
+        let layout = source
+            .reflect()
+            .unwrap()
+            .bind_group_layout("cull", 0)
+            .build(&gpu);
+
+        let [cull, scan, compact] = ["cs_cull", "cs_scan", "cs_compact"].map(|entry_point| {
+            ComputeShader::new(
+                &gpu,
+                ComputeShaderDesc::new(entry_point, &source)
+                    .with_entry_point(entry_point)
+                    .with_layouts(&[&layout]),
+            )
+            .unwrap()
+        });
+
+        // The clip space is the view, and the objects are spread well beyond it along x, away
+        // from its edges
+        let xs = (0..OBJECT_COUNT)
+            .map(|i| ((i * 37) % 1000) as f32 * 0.01 - 5.0 + 0.003)
+            .collect::<Vec<_>>();
+        let objects = xs
+            .iter()
+            .map(|&x| ObjectData {
+                model: Mat4::from_translation(vec3(x, 0.0, 0.5))
+                    * Mat4::from_scale(Vec3::splat(0.01)),
+                color: Vec4::ONE,
+            })
+            .collect::<Vec<_>>();
+
+        let expected = (0..OBJECT_COUNT as u32)
+            .filter(|&i| xs[i as usize].abs() <= 1.01)
+            .collect::<Vec<_>>();
+
+        let group_count = OBJECT_COUNT.div_ceil(64);
+        let camera = TypedBuffer::new(
+            &gpu,
+            "camera",
+            BufferUsages::UNIFORM,
+            &[Camera::new(Mat4::IDENTITY, Mat4::IDENTITY)],
+        );
+        let object_data = TypedBuffer::new(&gpu, "objects", BufferUsages::STORAGE, &objects);
+        let params = TypedBuffer::new(
+            &gpu,
+            "params",
+            BufferUsages::UNIFORM,
+            &[[OBJECT_COUNT as u32, 0, 0, 0]],
+        );
+        let scratch = TypedBuffer::<u32>::new_uninit(
+            &gpu,
+            "scratch",
+            BufferUsages::STORAGE,
+            OBJECT_COUNT + group_count,
+        );
+        let visible =
+            TypedBuffer::<u32>::new_uninit(&gpu, "visible", BufferUsages::STORAGE, OBJECT_COUNT);
+        let draw = TypedBuffer::new(
+            &gpu,
+            "draw",
+            BufferUsages::STORAGE,
+            &[DrawIndexedIndirect::default()],
+        );
+
+        let bind_group = BindGroupBuilder::new("cull")
+            .bind_buffer(&camera)
+            .bind_buffer(&object_data)
+            .bind_buffer(&params)
+            .bind_buffer(&scratch)
+            .bind_buffer(&visible)
+            .bind_buffer(&draw)
+            .build(&gpu, &layout);
+
+        gpu.submit("cull", |encoder| {
+            cull.record(encoder, &[&bind_group], OBJECT_COUNT as u32);
+            scan.record(encoder, &[&bind_group], 1);
+            compact.record(encoder, &[&bind_group], OBJECT_COUNT as u32);
+        });
+
+        let draw = draw.read_all(&gpu).await.unwrap();
+        assert_eq!(draw[0].instance_count as usize, expected.len());
+
+        let visible = visible.read(&gpu, 0..expected.len()).await.unwrap();
+        assert_eq!(visible, expected);
+    });
+}
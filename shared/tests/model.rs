@@ -0,0 +1,67 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+mod common;
+
+use std::sync::Arc;
+
+use elements_asset_cache::{AssetCache, AssetErrorKind, AsyncAssetKeyExt};
+use shared::graphics::{ModelError, ModelKey, ModelResolver};
+
+fn files() -> ModelResolver {
+    Arc::new(|path| match path {
+        "models/panel.obj" => Ok(
+            b"mtllib panel.mtl\nv 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nusemtl metal\nf 1 2 3 4\n"
+                .to_vec(),
+        ),
+        "models/panel.mtl" => Ok(b"newmtl metal\nKd 0.5 0.5 0.5\n".to_vec()),
+        _ => anyhow::bail!("Unexpected file {path}"),
+    })
+}
+
+#[tokio::test]
+async fn load_obj() {
+    let Some(gpu) = common::headless_gpu(16, 16).await else {
+        return;
+    };
+
+    let assets = AssetCache::new(Arc::new(tokio::runtime::Handle::current()));
+    let key = ModelKey::new("models/panel.obj", files());
+
+    let data = key.try_get(&assets).await.unwrap();
+    assert!(Arc::ptr_eq(&data, &key.try_get(&assets).await.unwrap()));
+
+    // The material library is resolved relative to the model
+    assert_eq!(data.materials.len(), 1);
+
+    let model = data.upload(&gpu);
+    let primitives = model.primitives().collect::<Vec<_>>();
+
+    assert_eq!(primitives.len(), 1);
+    assert_eq!(primitives[0].0.mesh.vertex_count(), 4);
+    assert_eq!(primitives[0].0.mesh.index_count(), 6);
+}
+
+#[tokio::test]
+async fn load_failed() {
+    let assets = AssetCache::new(Arc::new(tokio::runtime::Handle::current()));
+
+    let model_error = |path: &str| {
+        let key = ModelKey::new(path, files());
+        let assets = assets.clone();
+        async move {
+            let err = key.try_get(&assets).await.unwrap_err();
+            let AssetErrorKind::LoadFailed(source) = err.kind() else {
+                panic!("Unexpected error {err:?}");
+            };
+            source.downcast_ref::<ModelError>().unwrap().to_string()
+        }
+    };
+
+    assert_eq!(
+        model_error("models/panel.fbx").await,
+        ModelError::Format("models/panel.fbx".into()).to_string()
+    );
+    assert!(model_error("models/hull.glb")
+        .await
+        .starts_with(r#"Failed to load "models/hull.glb""#));
+}
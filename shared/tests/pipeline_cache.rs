@@ -0,0 +1,284 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+mod common;
+
+use std::sync::Arc;
+
+use shared::graphics::{
+    BindGroupLayoutBuilder, BlendMode, Gpu, PipelineCache, Shader, ShaderDefines, ShaderDesc,
+    ShaderError, ShaderLibrary,
+};
+use wgpu::TextureFormat;
+
+const COLOR: &str = "fn color() -> vec4<f32> {\n    return vec4(1.0, 0.0, 0.0, 1.0);\n}\n";
+
+const MAIN: &str = "#import color
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    return vec4(f32(index), 0.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return color();
+}
+";
+
+fn shader(gpu: &Gpu, library: &ShaderLibrary, blend: BlendMode) -> Shader {
+    let source = library.compose("main", &ShaderDefines::new()).unwrap();
+
+    Shader::new(
+        gpu,
+        ShaderDesc::new("main", &source)
+            .with_target(gpu.surface_format(), blend)
+            .with_depth_stencil(None),
+    )
+    .unwrap()
+}
+
+#[test]
+fn deduplicate_and_reload() {
+    futures::executor::block_on(async {
+        let Some(gpu) = common::headless_gpu(16, 16).await else {
+            return;
+        };
+
+        let mut library = ShaderLibrary::new()
+            .with_module("color", "color.wgsl", COLOR)
+            .with_module("main", "main.wgsl", MAIN);
+
+        let mut a = shader(&gpu, &library, BlendMode::Alpha);
+        let b = shader(&gpu, &library, BlendMode::Alpha);
+        let _additive = shader(&gpu, &library, BlendMode::Additive);
+
+        assert_eq!(gpu.pipeline_cache().len(), 2);
+        assert!(std::ptr::eq(a.pipeline().unwrap(), b.pipeline().unwrap()));
+
+        let old = a.pipeline().unwrap() as *const _;
+
+        // Modules which are not imported do not cause a rebuild
+        library.add_module("unused", "unused.wgsl", "");
+        assert_eq!(
+            gpu.pipeline_cache().reload(&gpu.device, &library, "unused"),
+            0
+        );
+
+        assert!(library.update_module("color", COLOR.replace("1.0, 0.0, 0.0", "0.0, 1.0, 0.0")));
+        assert_eq!(
+            gpu.pipeline_cache().reload(&gpu.device, &library, "color"),
+            2
+        );
+
+        a.update();
+        assert!(!std::ptr::eq(a.pipeline().unwrap(), old));
+
+        // A broken module keeps the previous pipeline
+        let old = a.pipeline().unwrap() as *const _;
+        library.update_module("color", "fn color() -> vec4<f32> {\n    return 1;\n}\n");
+        assert_eq!(
+            gpu.pipeline_cache().reload(&gpu.device, &library, "color"),
+            0
+        );
+
+        a.update();
+        assert!(std::ptr::eq(a.pipeline().unwrap(), old));
+    });
+}
+
+#[test]
+fn reload_validation_error() {
+    futures::executor::block_on(async {
+        let Some(gpu) = common::headless_gpu(16, 16).await else {
+            return;
+        };
+
+        let mut library = ShaderLibrary::new()
+            .with_module("color", "color.wgsl", COLOR)
+            .with_module("main", "main.wgsl", MAIN);
+
+        let mut shader = shader(&gpu, &library, BlendMode::Opaque);
+        let old = shader.pipeline().unwrap() as *const _;
+
+        // Integer output to a float target passes naga, but not the validation of wgpu
+        library.update_module(
+            "main",
+            MAIN.replace(
+                "vec4<f32> {\n    return color();",
+                "vec4<u32> {\n    return vec4<u32>(color());",
+            ),
+        );
+        assert_eq!(
+            gpu.pipeline_cache().reload(&gpu.device, &library, "main"),
+            0
+        );
+
+        shader.update();
+        assert!(std::ptr::eq(shader.pipeline().unwrap(), old));
+    });
+}
+
+#[test]
+fn reload_merges_equal() {
+    futures::executor::block_on(async {
+        let Some(gpu) = common::headless_gpu(16, 16).await else {
+            return;
+        };
+
+        let green = COLOR.replace("1.0, 0.0, 0.0", "0.0, 1.0, 0.0");
+
+        let mut red_library = ShaderLibrary::new()
+            .with_module("color", "color.wgsl", COLOR)
+            .with_module("main", "main.wgsl", MAIN);
+        let green_library = ShaderLibrary::new()
+            .with_module("color", "color.wgsl", &green)
+            .with_module("main", "main.wgsl", MAIN);
+
+        let mut a = shader(&gpu, &red_library, BlendMode::Opaque);
+        let mut b = shader(&gpu, &green_library, BlendMode::Opaque);
+        assert_eq!(gpu.pipeline_cache().len(), 2);
+
+        // Both become green, and share a pipeline from then on
+        red_library.update_module("color", &green);
+        assert_eq!(
+            gpu.pipeline_cache()
+                .reload(&gpu.device, &red_library, "color"),
+            2
+        );
+        assert_eq!(gpu.pipeline_cache().len(), 1);
+
+        a.update();
+        b.update();
+        assert!(std::ptr::eq(a.pipeline().unwrap(), b.pipeline().unwrap()));
+
+        red_library.update_module("color", COLOR.replace("1.0, 0.0, 0.0", "0.0, 0.0, 1.0"));
+        assert_eq!(
+            gpu.pipeline_cache()
+                .reload(&gpu.device, &red_library, "color"),
+            1
+        );
+
+        let old = a.pipeline().unwrap() as *const _;
+        a.update();
+        b.update();
+        assert!(!std::ptr::eq(a.pipeline().unwrap(), old));
+        assert!(std::ptr::eq(a.pipeline().unwrap(), b.pipeline().unwrap()));
+    });
+}
+
+#[test]
+fn compile_error() {
+    futures::executor::block_on(async {
+        let Some(gpu) = common::headless_gpu(16, 16).await else {
+            return;
+        };
+
+        let library = ShaderLibrary::new()
+            .with_module("color", "color.wgsl", COLOR)
+            .with_module("main", "main.wgsl", MAIN);
+        let source = library.compose("main", &ShaderDefines::new()).unwrap();
+
+        let result = Shader::new(
+            &gpu,
+            ShaderDesc::new("main", &source)
+                .with_target(TextureFormat::R32Uint, BlendMode::Opaque)
+                .with_depth_stencil(None),
+        );
+
+        assert!(matches!(result, Err(ShaderError::Pipeline(_))));
+        assert!(gpu.pipeline_cache().is_empty());
+    });
+}
+
+#[test]
+fn equal_layouts() {
+    futures::executor::block_on(async {
+        let Some(gpu) = common::headless_gpu(16, 16).await else {
+            return;
+        };
+
+        let library = ShaderLibrary::new()
+            .with_module("color", "color.wgsl", COLOR)
+            .with_module("main", "main.wgsl", MAIN);
+        let source = library.compose("main", &ShaderDefines::new()).unwrap();
+
+        // Layouts created separately with the same entries share a pipeline
+        let first = BindGroupLayoutBuilder::new("first").build(&gpu);
+        let second = BindGroupLayoutBuilder::new("second").build(&gpu);
+
+        let shaders = [&first, &second].map(|layout| {
+            let layouts = [layout];
+            Shader::new(
+                &gpu,
+                ShaderDesc::new("main", &source)
+                    .with_target(gpu.surface_format(), BlendMode::Opaque)
+                    .with_depth_stencil(None)
+                    .with_layouts(&layouts),
+            )
+            .unwrap()
+        });
+
+        assert_eq!(gpu.pipeline_cache().len(), 1);
+        assert!(std::ptr::eq(
+            shaders[0].pipeline().unwrap(),
+            shaders[1].pipeline().unwrap()
+        ));
+    });
+}
+
+#[test]
+fn compile_async() {
+    futures::executor::block_on(async {
+        let Some(gpu) = common::headless_gpu(16, 16).await else {
+            return;
+        };
+        let gpu = Arc::new(gpu);
+
+        let library = ShaderLibrary::new()
+            .with_module("color", "color.wgsl", COLOR)
+            .with_module("main", "main.wgsl", MAIN);
+        let source = library.compose("main", &ShaderDefines::new()).unwrap();
+
+        let mut shader = Shader::new_async(
+            &gpu,
+            ShaderDesc::new("main", &source)
+                .with_target(gpu.surface_format(), BlendMode::Opaque)
+                .with_depth_stencil(None),
+        )
+        .unwrap();
+
+        while shader.pipeline().is_none() {
+            std::thread::yield_now();
+            shader.update();
+        }
+    });
+}
+
+#[test]
+fn compile_async_error() {
+    futures::executor::block_on(async {
+        let Some(gpu) = common::headless_gpu(16, 16).await else {
+            return;
+        };
+        let gpu = Arc::new(gpu);
+
+        let library = ShaderLibrary::new()
+            .with_module("color", "color.wgsl", COLOR)
+            .with_module("main", "main.wgsl", MAIN);
+        let source = library.compose("main", &ShaderDefines::new()).unwrap();
+
+        // The shader writes floats to an integer target, which only wgpu validates
+        let pipeline = PipelineCache::get_async(
+            &gpu,
+            &ShaderDesc::new("main", &source)
+                .with_target(TextureFormat::R32Uint, BlendMode::Opaque)
+                .with_depth_stencil(None),
+        )
+        .unwrap();
+
+        while pipeline.error().is_none() {
+            assert!(!pipeline.is_ready());
+            std::thread::yield_now();
+        }
+    });
+}
@@ -0,0 +1,133 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+mod common;
+
+use shared::{
+    graphics::{AttachmentDesc, ColorAttachment, GpuConfig, PassContext, PassDesc, RenderGraph},
+    snapshot::read_offscreen,
+};
+use wgpu::{Color, LoadOp, TextureFormat};
+use winit::dpi::PhysicalSize;
+
+/// What the passes saw, in the order they ran
+#[derive(Default)]
+struct Log {
+    passes: Vec<(&'static str, u32, u64)>,
+}
+
+#[test]
+fn execute_and_resize() {
+    futures::executor::block_on(async {
+        let Some(gpu) = common::headless_gpu(64, 32).await else {
+            return;
+        };
+
+        let mut graph = RenderGraph::<Log>::new(&gpu);
+        graph.add_attachment(
+            &gpu,
+            "half",
+            AttachmentDesc::new(TextureFormat::Rgba8Unorm).with_scale(0.5),
+        );
+
+        // Added out of order, which the dependencies on "half" correct
+        graph
+            .add_pass(
+                PassDesc::new("present")
+                    .with_read("half")
+                    .with_write(RenderGraph::<Log>::SURFACE),
+                |ctx: &mut PassContext, log: &mut Log| {
+                    ctx.begin_render_pass(
+                        "present",
+                        &[ColorAttachment::new(
+                            RenderGraph::<Log>::SURFACE,
+                            LoadOp::Clear(Color::BLUE),
+                        )],
+                        None,
+                    );
+
+                    let width = ctx.texture("half").size().width;
+                    log.passes.push(("present", width, ctx.generation()));
+                },
+            )
+            .add_pass(
+                PassDesc::new("draw").with_write("half"),
+                |ctx: &mut PassContext, log: &mut Log| {
+                    ctx.begin_render_pass(
+                        "draw",
+                        &[ColorAttachment::new("half", LoadOp::Clear(Color::RED))],
+                        None,
+                    );
+
+                    let width = ctx.texture("half").size().width;
+                    log.passes.push(("draw", width, ctx.generation()));
+                },
+            );
+
+        assert_eq!(graph.order().unwrap(), ["draw", "present"]);
+
+        let mut log = Log::default();
+        gpu.render(|encoder, view| graph.execute(&gpu, encoder, view, &mut log))
+            .unwrap();
+
+        gpu.resize(PhysicalSize::new(128, 32));
+        gpu.render(|encoder, view| graph.execute(&gpu, encoder, view, &mut log))
+            .unwrap();
+
+        let generation = log.passes[0].2;
+        assert_eq!(
+            log.passes,
+            [
+                ("draw", 32, generation),
+                ("present", 32, generation),
+                ("draw", 64, generation + 1),
+                ("present", 64, generation + 1),
+            ]
+        );
+    });
+}
+
+#[test]
+fn resolve_multisampled() {
+    futures::executor::block_on(async {
+        // Higher than any adapter supports, which falls back to a supported count
+        let config = GpuConfig::default().with_sample_count(64);
+        let Some(gpu) = common::headless_gpu_with(&config, 32, 32).await else {
+            return;
+        };
+
+        let sample_count = gpu.sample_count();
+        assert!(sample_count.is_power_of_two() && sample_count <= 16);
+
+        let mut graph = RenderGraph::<()>::new(&gpu);
+        graph.add_attachment(
+            &gpu,
+            "multisampled",
+            AttachmentDesc::new(gpu.surface_format()).with_sample_count(sample_count),
+        );
+
+        let target = ColorAttachment::new("multisampled", LoadOp::Clear(Color::RED));
+        let target = if sample_count > 1 {
+            target.with_resolve_target(RenderGraph::<()>::SURFACE)
+        } else {
+            ColorAttachment {
+                name: RenderGraph::<()>::SURFACE,
+                ..target
+            }
+        };
+
+        graph.add_pass(
+            PassDesc::new("draw")
+                .with_write("multisampled")
+                .with_write(RenderGraph::<()>::SURFACE),
+            move |ctx: &mut PassContext, _: &mut ()| {
+                ctx.begin_render_pass("draw", &[target], None);
+            },
+        );
+
+        gpu.render(|encoder, view| graph.execute(&gpu, encoder, view, &mut ()))
+            .unwrap();
+
+        let frame = read_offscreen(&gpu).await.unwrap();
+        assert!(frame.pixels().all(|v| v.0 == [255, 0, 0, 255]));
+    });
+}
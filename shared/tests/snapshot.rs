@@ -0,0 +1,209 @@
+//! Golden-image tests of the renderer.
+//!
+//! The references were rendered using llvmpipe. Run with `UPDATE_SNAPSHOTS=1` to regenerate them
+//! after an intentional change to the rendering.
+#![cfg(not(target_arch = "wasm32"))]
+
+mod common;
+
+use std::sync::Arc;
+
+use glam::vec2;
+use shared::{
+    game::Game,
+    graphics::{Gpu, GpuConfig},
+    renderer::Renderer,
+    snapshot::{read_offscreen, SnapshotTest},
+};
+use wgpu::{Limits, TextureFormat};
+use winit::dpi::PhysicalSize;
+
+const SEED: u64 = 42;
+
+fn snapshots() -> SnapshotTest {
+    SnapshotTest::new(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/snapshots"),
+        env!("CARGO_TARGET_TMPDIR"),
+    )
+}
+
+fn new_game(gpu: &Arc<Gpu>) -> Game {
+    let image = image::load_from_memory(include_bytes!("../../assets/asteroid.png")).unwrap();
+
+    let mut game = Game::from_image(gpu.clone(), image, SEED).unwrap();
+
+    // Advance the simulation to a fixed point in time
+    for _ in 0..100 {
+        game.update(1.0 / 50.0);
+    }
+
+    game
+}
+
+#[test]
+fn asteroids() {
+    futures::executor::block_on(async {
+        let Some(gpu) = common::headless_gpu(320, 240).await.map(Arc::new) else {
+            return;
+        };
+
+        let mut renderer = Renderer::new(&gpu).unwrap();
+        let mut game = new_game(&gpu);
+
+        gpu.render(|encoder, view| renderer.render(encoder, view, &mut game))
+            .unwrap();
+
+        let frame = read_offscreen(&gpu).await.unwrap();
+        snapshots().check("asteroids", &frame).unwrap();
+    })
+}
+
+/// Drawing all objects from a storage buffer, culled on the gpu where compute is supported, must
+/// match the chunked uniform buffers used above
+#[test]
+fn asteroids_storage() {
+    futures::executor::block_on(async {
+        let config = GpuConfig::default().with_limits(Limits::downlevel_defaults());
+        let Some(gpu) = common::headless_gpu_with(&config, 320, 240)
+            .await
+            .map(Arc::new)
+        else {
+            return;
+        };
+
+        if !gpu.supports_vertex_storage() {
+            eprintln!("Skipping test: vertex storage is not supported");
+            return;
+        }
+
+        let mut renderer = Renderer::new(&gpu).unwrap();
+        let mut game = new_game(&gpu);
+
+        gpu.render(|encoder, view| renderer.render(encoder, view, &mut game))
+            .unwrap();
+
+        let frame = read_offscreen(&gpu).await.unwrap();
+        snapshots().check("asteroids", &frame).unwrap();
+    })
+}
+
+/// Renders asteroids piled on top of each other, whose order decides which ones are visible
+async fn overlapping(config: &GpuConfig) -> Option<image::RgbaImage> {
+    let gpu = Arc::new(common::headless_gpu_with(config, 320, 240).await?);
+
+    let mut renderer = Renderer::new(&gpu).unwrap();
+    let mut game = new_game(&gpu);
+
+    for (i, asteroid) in game.asteroids_mut().iter_mut().take(48).enumerate() {
+        asteroid.pos = vec2((i % 8) as f32, (i / 8) as f32) * 0.4 - vec2(1.4, 1.0);
+        asteroid.radius = 1.0;
+    }
+
+    gpu.render(|encoder, view| renderer.render(encoder, view, &mut game))
+        .unwrap();
+
+    Some(read_offscreen(&gpu).await.unwrap())
+}
+
+#[test]
+fn asteroids_overlapping() {
+    futures::executor::block_on(async {
+        let Some(frame) = overlapping(&GpuConfig::default()).await else {
+            return;
+        };
+
+        snapshots().check("overlapping", &frame).unwrap();
+    })
+}
+
+/// Culling on the gpu must keep the order of the objects
+#[test]
+fn asteroids_overlapping_culled() {
+    futures::executor::block_on(async {
+        let config = GpuConfig::default().with_limits(Limits::downlevel_defaults());
+        let Some(frame) = overlapping(&config).await else {
+            return;
+        };
+
+        snapshots().check("overlapping", &frame).unwrap();
+    })
+}
+
+/// Rendering without multisampling, which uses the framebuffer as the scene attachment
+#[test]
+fn single_sample() {
+    futures::executor::block_on(async {
+        let config = GpuConfig::default().with_sample_count(1);
+        let Some(gpu) = common::headless_gpu_with(&config, 320, 240)
+            .await
+            .map(Arc::new)
+        else {
+            return;
+        };
+
+        let mut renderer = Renderer::new(&gpu).unwrap();
+        let mut game = new_game(&gpu);
+
+        gpu.render(|encoder, view| renderer.render(encoder, view, &mut game))
+            .unwrap();
+
+        let frame = read_offscreen(&gpu).await.unwrap();
+        snapshots().check("single_sample", &frame).unwrap();
+    })
+}
+
+/// Surfaces which are not sRGB are encoded by the shader, which must match the hardware encoding
+#[test]
+fn linear_surface() {
+    futures::executor::block_on(async {
+        let gpu = match Gpu::new_headless(
+            &GpuConfig::default(),
+            PhysicalSize::new(320, 240),
+            TextureFormat::Rgba8Unorm,
+        )
+        .await
+        {
+            Ok(gpu) => Arc::new(gpu),
+            Err(err) => {
+                eprintln!("Skipping test: {err:?}");
+                return;
+            }
+        };
+
+        let mut renderer = Renderer::new(&gpu).unwrap();
+        let mut game = new_game(&gpu);
+
+        gpu.render(|encoder, view| renderer.render(encoder, view, &mut game))
+            .unwrap();
+
+        let frame = read_offscreen(&gpu).await.unwrap();
+        snapshots().check("asteroids", &frame).unwrap();
+    })
+}
+
+#[test]
+fn resized() {
+    futures::executor::block_on(async {
+        let Some(gpu) = common::headless_gpu(320, 240).await.map(Arc::new) else {
+            return;
+        };
+
+        let mut renderer = Renderer::new(&gpu).unwrap();
+        let mut game = new_game(&gpu);
+
+        gpu.render(|encoder, view| renderer.render(encoder, view, &mut game))
+            .unwrap();
+
+        // Change the aspect ratio, which must not stretch the image
+        gpu.resize(PhysicalSize::new(160, 240));
+        renderer.resize();
+        game.resize();
+
+        gpu.render(|encoder, view| renderer.render(encoder, view, &mut game))
+            .unwrap();
+
+        let frame = read_offscreen(&gpu).await.unwrap();
+        assert_eq!(frame.dimensions(), (160, 240));
+        snapshots().check("resized", &frame).unwrap();
+    })
+}
@@ -0,0 +1,124 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+mod common;
+
+use glam::vec2;
+use image::{DynamicImage, Rgba, RgbaImage};
+use shared::{
+    camera::CameraController,
+    graphics::{AtlasDesc, ColorSpace, GpuConfig, Sprite, SpriteBatch, TextureAtlas, TypedBuffer},
+    snapshot::read_offscreen,
+};
+use wgpu::{BufferUsages, Color, LoadOp, TextureFormat, TextureUsages};
+
+fn solid(color: [u8; 4]) -> DynamicImage {
+    RgbaImage::from_pixel(8, 8, Rgba(color)).into()
+}
+
+#[test]
+fn sorted_draws() {
+    futures::executor::block_on(async {
+        let config = GpuConfig::default().with_sample_count(1);
+        let Some(gpu) = common::headless_gpu_with(&config, 32, 32).await else {
+            return;
+        };
+
+        // Shows the square from -1 to 1
+        let camera = CameraController::new(gpu.size(), 1.0).with_extent(1.0);
+        let camera_buffer = TypedBuffer::new(
+            &gpu,
+            "camera_buffer",
+            BufferUsages::UNIFORM,
+            &[camera.camera()],
+        );
+
+        let desc = AtlasDesc {
+            page_size: 64,
+            max_pages: 1,
+            padding: 1,
+            color_space: ColorSpace::Linear,
+        };
+        let mut first = TextureAtlas::new(&gpu, desc);
+        let red = first.add(&gpu, &solid([255, 0, 0, 255])).unwrap();
+        let green = first.add(&gpu, &solid([0, 255, 0, 255])).unwrap();
+
+        let mut second = TextureAtlas::new(&gpu, desc);
+        let blue = second.add(&gpu, &solid([0, 0, 255, 255])).unwrap();
+
+        let mut batch = SpriteBatch::new(&gpu, &camera_buffer, gpu.surface_format()).unwrap();
+        let first_texture = batch.add_texture(&gpu, first.view());
+        let second_texture = batch.add_texture(&gpu, second.view());
+
+        let red = Sprite::from_atlas(first_texture, first.get(red));
+        let green = Sprite::from_atlas(first_texture, first.get(green));
+        let blue = Sprite::from_atlas(second_texture, second.get(blue));
+
+        // Pushed out of order, with the green sprite covering the blue one below it
+        batch.push(
+            green
+                .with_position(vec2(0.0, -0.5))
+                .with_scale(vec2(2.0, 1.0))
+                .with_layer(1),
+        );
+        batch.push(
+            blue.with_position(vec2(0.0, -0.5))
+                .with_scale(vec2(2.0, 1.0)),
+        );
+        batch.push(red.with_position(vec2(-0.5, 0.5)));
+        batch.push(
+            blue.with_position(vec2(0.5, 0.5))
+                .with_rotation(std::f32::consts::FRAC_PI_2),
+        );
+
+        batch.prepare(&gpu);
+        assert_eq!(batch.draw_count(), 3);
+
+        let depth = gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("depth"),
+            size: wgpu::Extent3d {
+                width: 32,
+                height: 32,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: TextureFormat::Depth32Float,
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let depth = depth.create_view(&Default::default());
+
+        gpu.render(|encoder, view| {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("sprites"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: LoadOp::Clear(Color::BLACK),
+                        store: true,
+                    },
+                })],
+                // Cleared to the near plane as if the scene covered the target, which the sprites
+                // are still drawn over
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth,
+                    depth_ops: Some(wgpu::Operations {
+                        load: LoadOp::Clear(0.0),
+                        store: false,
+                    }),
+                    stencil_ops: None,
+                }),
+            });
+
+            batch.render(&mut render_pass);
+        })
+        .unwrap();
+
+        let frame = read_offscreen(&gpu).await.unwrap();
+        assert_eq!(frame.get_pixel(8, 8).0, [255, 0, 0, 255]);
+        assert_eq!(frame.get_pixel(24, 8).0, [0, 0, 255, 255]);
+        assert_eq!(frame.get_pixel(16, 24).0, [0, 255, 0, 255]);
+    });
+}
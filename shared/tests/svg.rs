@@ -0,0 +1,46 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+mod common;
+
+use glam::vec2;
+use shared::graphics::{parse_svg, rasterize_svg, ImageOptions, SvgTexture};
+
+const ASTEROID: &[u8] = include_bytes!("../../assets/asteroid.svg");
+
+#[test]
+fn rasterize() {
+    let tree = parse_svg(ASTEROID).unwrap();
+    let image = rasterize_svg(&tree, 64, 64).to_rgba8();
+
+    assert_eq!(image.dimensions(), (64, 64));
+    // The asteroid covers the center, but not the corners
+    assert_eq!(image.get_pixel(32, 32)[3], 255);
+    assert_eq!(image.get_pixel(0, 0)[3], 0);
+}
+
+#[test]
+fn rerasterize_on_scale_change() {
+    futures::executor::block_on(async {
+        let Some(gpu) = common::headless_gpu(16, 16).await else {
+            return;
+        };
+
+        let size = vec2(40.0, 20.0);
+        let mut texture =
+            SvgTexture::new(&gpu, ASTEROID, ImageOptions::default(), size, 1.0).unwrap();
+
+        // The square document is fit within the on-screen size
+        assert_eq!(texture.texture().size().width, 20);
+        assert_eq!(texture.texture().size().height, 20);
+
+        // Small changes reuse the current rasterization
+        assert!(!texture.update(&gpu, size * 1.1, 1.0));
+        assert!(!texture.update(&gpu, size * 0.6, 1.0));
+        assert_eq!(texture.generation(), 0);
+
+        // Moving to a high-DPI display doubles the resolution
+        assert!(texture.update(&gpu, size, 2.0));
+        assert_eq!(texture.texture().size().width, 40);
+        assert_eq!(texture.generation(), 1);
+    });
+}
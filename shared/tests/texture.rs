@@ -0,0 +1,143 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+mod common;
+
+use image::DynamicImage;
+use shared::graphics::{Gpu, ImageOptions, SamplerDesc, Texture, TextureDesc, TypedBuffer};
+use wgpu::{BufferUsages, TextureFormat, TextureUsages, TextureViewDimension};
+
+/// Reads back the first row of a mip level of `layer`
+async fn read_level(gpu: &Gpu, texture: &Texture, layer: u32, level: u32) -> Vec<[u8; 4]> {
+    let size = texture.size().width >> level;
+    let buffer = TypedBuffer::<[u8; 4]>::new_uninit(
+        gpu,
+        "readback",
+        BufferUsages::empty(),
+        (size * size) as usize,
+    );
+
+    gpu.submit("read_level", |encoder| {
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: level,
+                origin: wgpu::Origin3d {
+                    x: 0,
+                    y: 0,
+                    z: layer,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(size * 4),
+                    rows_per_image: Some(size),
+                },
+            },
+            wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+        )
+    });
+
+    let texels = buffer.read_all(gpu).await.unwrap();
+    texels.into_iter().take(size as usize).collect()
+}
+
+#[test]
+fn generate_mips() {
+    futures::executor::block_on(async {
+        let Some(gpu) = common::headless_gpu(16, 16).await else {
+            return;
+        };
+
+        // Rows need to be 256 byte aligned for the readback
+        let desc = TextureDesc::new(
+            256,
+            256,
+            TextureFormat::Rgba8Unorm,
+            TextureUsages::TEXTURE_BINDING
+                | TextureUsages::RENDER_ATTACHMENT
+                | TextureUsages::COPY_DST
+                | TextureUsages::COPY_SRC,
+        )
+        .with_mips();
+        assert_eq!(desc.mip_level_count, 9);
+
+        let texture = Texture::from_desc(&gpu, &desc);
+
+        // Stripes of 2 red and 2 blue texels
+        let texels = (0..256 * 256)
+            .flat_map(|i| {
+                if i / 2 % 2 == 0 {
+                    [255, 0, 0, 255]
+                } else {
+                    [0, 0, 255, 255]
+                }
+            })
+            .collect::<Vec<u8>>();
+
+        texture.write_layer(&gpu, 0, &texels);
+        texture.generate_mips(&gpu);
+
+        const RED: [u8; 4] = [255, 0, 0, 255];
+        const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+        // Each level halves the previous one
+        let level = read_level(&gpu, &texture, 0, 1).await;
+        assert_eq!(&level[..4], &[RED, BLUE, RED, BLUE]);
+
+        let level = read_level(&gpu, &texture, 0, 2).await;
+        assert!(level
+            .iter()
+            .all(|v| v[0].abs_diff(128) <= 1 && v[1] == 0 && v[2].abs_diff(128) <= 1));
+    });
+}
+
+#[test]
+fn shared_samplers() {
+    futures::executor::block_on(async {
+        let Some(gpu) = common::headless_gpu(16, 16).await else {
+            return;
+        };
+
+        let a = gpu.sampler(&SamplerDesc::linear());
+        let b = gpu.sampler(&SamplerDesc::default());
+        let c = gpu.sampler(&SamplerDesc::nearest());
+
+        assert!(std::sync::Arc::ptr_eq(&a, &b));
+        assert!(!std::sync::Arc::ptr_eq(&a, &c));
+    });
+}
+
+#[test]
+fn layer_counts() {
+    futures::executor::block_on(async {
+        let Some(gpu) = common::headless_gpu(16, 16).await else {
+            return;
+        };
+
+        let from_layers = |count: usize, dimension| {
+            let images = vec![DynamicImage::new_rgba8(4, 4); count];
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                Texture::from_layers(&gpu, &images, dimension, ImageOptions::default())
+            }))
+            .is_ok()
+        };
+
+        assert!(from_layers(1, TextureViewDimension::D2));
+        assert!(from_layers(3, TextureViewDimension::D2Array));
+        assert!(from_layers(6, TextureViewDimension::Cube));
+        assert!(from_layers(12, TextureViewDimension::CubeArray));
+
+        assert!(!from_layers(0, TextureViewDimension::D2Array));
+        assert!(!from_layers(2, TextureViewDimension::D2));
+        assert!(!from_layers(5, TextureViewDimension::Cube));
+        assert!(!from_layers(12, TextureViewDimension::Cube));
+        assert!(!from_layers(9, TextureViewDimension::CubeArray));
+    });
+}